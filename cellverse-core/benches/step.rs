@@ -0,0 +1,32 @@
+//! Compares the scalar `Universe::step` with the bit-packed `BitGrid` kernel
+//! on the largest universe the GUI can grow to.
+
+use cellverse_core::{BitGrid, Engine, Universe, MAX_GRID_HEIGHT, MAX_GRID_WIDTH};
+use criterion::{criterion_group, criterion_main, Criterion};
+
+fn random_universe() -> Universe {
+    let mut universe = Universe::new(MAX_GRID_WIDTH, MAX_GRID_HEIGHT);
+    universe.set_auto_grow(false);
+    universe.randomize();
+    universe
+}
+
+fn step(c: &mut Criterion) {
+    let mut group = c.benchmark_group("step 5000x3000");
+    group.sample_size(10);
+
+    let mut universe = random_universe();
+    group.bench_function("universe", |b| b.iter(|| universe.step()));
+
+    let mut packed = BitGrid::from(&random_universe());
+    group.bench_function("bitgrid", |b| b.iter(|| Engine::step(&mut packed)));
+
+    let mut single_thread = BitGrid::from(&random_universe());
+    single_thread.set_threads(1);
+    group.bench_function("bitgrid single thread", |b| b.iter(|| Engine::step(&mut single_thread)));
+
+    group.finish();
+}
+
+criterion_group!(benches, step);
+criterion_main!(benches);
@@ -0,0 +1,351 @@
+//! Bit-packed toroidal grid with a word-parallel stepping kernel.
+//!
+//! Each row is stored as 64 cells per `u64`. Neighbour counts for a whole
+//! word are computed at once with a bit-sliced adder, and rows are split into
+//! bands that are stepped on separate threads. Non-totalistic rules look up
+//! the neighbourhood of each cell instead of its count.
+
+use std::thread;
+
+use crate::{Engine, Rule, Stats, Universe};
+
+/// A toroidal grid storing one bit per cell.
+///
+/// One bit only holds two states, and the kernel only knows the square
+/// lattice's eight neighbours, so the grid runs Life-like rules alone;
+/// setting any other rule panics.
+#[derive(Debug, Clone)]
+pub struct BitGrid {
+    width: usize,
+    height: usize,
+    words_per_row: usize,
+    words: Vec<u64>,
+    rule: Rule,
+    stats: Stats,
+    threads: usize,
+}
+
+impl BitGrid {
+    /// Creates an empty grid. Stepping uses every available CPU core.
+    pub fn new(width: usize, height: usize) -> Self {
+        let words_per_row = width.div_ceil(64);
+        Self {
+            width,
+            height,
+            words_per_row,
+            words: vec![0; words_per_row * height],
+            rule: Rule::default(),
+            stats: Stats::default(),
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Sets how many threads `step` splits the rows across.
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height && bit(self.row(y), x)
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, alive: bool) {
+        if x >= self.width || y >= self.height || self.get(x, y) == alive {
+            return;
+        }
+
+        self.words[y * self.words_per_row + x / 64] ^= 1 << (x % 64);
+        if alive {
+            self.stats.population += 1;
+            self.stats.births += 1;
+        } else {
+            self.stats.population -= 1;
+            self.stats.deaths += 1;
+        }
+    }
+
+    /// Copies the grid into `universe`, which is expected to have the same size.
+    pub fn copy_to(&self, universe: &mut Universe) {
+        let live = (0..self.height).flat_map(|y| {
+            (0..self.width)
+                .filter(move |&x| self.get(x, y))
+                .map(move |x| (x as i64, y as i64, 1))
+        });
+        universe.replace_cells(live, self.stats.generation);
+    }
+
+    fn row(&self, y: usize) -> &[u64] {
+        &self.words[y * self.words_per_row..(y + 1) * self.words_per_row]
+    }
+
+    // Compute one band of output rows starting at `first_row`
+    fn step_band(&self, first_row: usize, band: &mut [u64], masks: &RuleMasks) -> (u64, u64) {
+        let mut births = 0;
+        let mut deaths = 0;
+
+        for (offset, out) in band.chunks_mut(self.words_per_row).enumerate() {
+            let y = first_row + offset;
+            let above = self.row((y + self.height - 1) % self.height);
+            let current = self.row(y);
+            let below = self.row((y + 1) % self.height);
+
+            for (w, out_word) in out.iter_mut().enumerate() {
+                let neighbors = [
+                    self.west(above, w),
+                    above[w],
+                    self.east(above, w),
+                    self.west(current, w),
+                    self.east(current, w),
+                    self.west(below, w),
+                    below[w],
+                    self.east(below, w),
+                ];
+
+                let alive = current[w];
+                let (born, survive) = masks.apply(neighbors);
+                let next = ((alive & survive) | (!alive & born)) & self.word_mask(w);
+
+                births += (next & !alive).count_ones() as u64;
+                deaths += (alive & !next).count_ones() as u64;
+                *out_word = next;
+            }
+        }
+
+        (births, deaths)
+    }
+
+    // Cells to the west (x - 1) of each cell in word `w`, wrapping around the row
+    fn west(&self, row: &[u64], w: usize) -> u64 {
+        let carry = if w == 0 {
+            u64::from(bit(row, self.width - 1))
+        } else {
+            row[w - 1] >> 63
+        };
+        (row[w] << 1) | carry
+    }
+
+    // Cells to the east (x + 1) of each cell in word `w`, wrapping around the row
+    fn east(&self, row: &[u64], w: usize) -> u64 {
+        if w + 1 == self.words_per_row {
+            (row[w] >> 1) | (u64::from(bit(row, 0)) << ((self.width - 1) % 64))
+        } else {
+            (row[w] >> 1) | (row[w + 1] << 63)
+        }
+    }
+
+    // Bits of word `w` that hold real cells rather than row padding
+    fn word_mask(&self, w: usize) -> u64 {
+        let used = self.width - w * 64;
+        if used >= 64 {
+            u64::MAX
+        } else {
+            (1 << used) - 1
+        }
+    }
+}
+
+impl From<&Universe> for BitGrid {
+    fn from(universe: &Universe) -> Self {
+        let mut grid = Self::new(universe.width(), universe.height());
+        for y in 0..grid.height {
+            for x in 0..grid.width {
+                if universe.get(x, y) {
+                    grid.words[y * grid.words_per_row + x / 64] |= 1 << (x % 64);
+                }
+            }
+        }
+        grid.set_rule(universe.rule());
+        grid.stats = universe.stats();
+        grid
+    }
+}
+
+impl Engine for BitGrid {
+    fn step(&mut self) {
+        if self.width == 0 || self.height == 0 {
+            return;
+        }
+
+        let masks = RuleMasks::new(&self.rule);
+        let mut next = vec![0; self.words.len()];
+        let rows_per_band = self.height.div_ceil(self.threads);
+
+        let (births, deaths) = thread::scope(|scope| {
+            let bands: Vec<_> = next
+                .chunks_mut(rows_per_band * self.words_per_row)
+                .enumerate()
+                .map(|(index, band)| {
+                    let grid = &*self;
+                    let masks = &masks;
+                    scope.spawn(move || grid.step_band(index * rows_per_band, band, masks))
+                })
+                .collect();
+
+            bands
+                .into_iter()
+                .map(|band| band.join().unwrap())
+                .fold((0, 0), |(b, d), (births, deaths)| (b + births, d + deaths))
+        });
+
+        self.words = next;
+        self.stats.generation += 1;
+        self.stats.births += births;
+        self.stats.deaths += deaths;
+        self.stats.population = self.words.iter().map(|w| w.count_ones() as u64).sum();
+    }
+
+    fn get_cell(&self, x: i64, y: i64) -> bool {
+        match (usize::try_from(x), usize::try_from(y)) {
+            (Ok(x), Ok(y)) => self.get(x, y),
+            _ => false,
+        }
+    }
+
+    fn set_cell(&mut self, x: i64, y: i64, alive: bool) {
+        if let (Ok(x), Ok(y)) = (usize::try_from(x), usize::try_from(y)) {
+            self.set(x, y, alive);
+        }
+    }
+
+    fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    fn rule(&self) -> Rule {
+        self.rule.clone()
+    }
+
+    /// # Panics
+    ///
+    /// If `rule` is not a two-state rule on the square lattice.
+    fn set_rule(&mut self, rule: Rule) {
+        assert!(rule.is_life_like(), "the bit-packed grid cannot run {}", rule);
+        self.rule = rule;
+    }
+
+    fn clear(&mut self) {
+        self.words.fill(0);
+        self.stats = Stats::default();
+    }
+}
+
+// Bit-sliced count of eight neighbour words: bit i of `count[k]` is bit k of
+// the neighbour count of cell i
+fn count_neighbors(neighbors: [u64; 8]) -> [u64; 4] {
+    let mut count = [0u64; 4];
+    for neighbor in neighbors {
+        let mut carry = neighbor;
+        for plane in &mut count {
+            let next = *plane & carry;
+            *plane ^= carry;
+            carry = next;
+        }
+    }
+    count
+}
+
+// The birth and survival conditions of a rule, for matching a word of cells
+// at once
+pub(crate) enum RuleMasks {
+    // Neighbour counts, matched against bit-sliced counts
+    Totalistic { birth: Vec<usize>, survival: Vec<usize> },
+    // Whether each neighbourhood causes a birth and allows survival, looked
+    // up cell by cell
+    Isotropic(Box<[(bool, bool); 256]>),
+}
+
+impl RuleMasks {
+    pub(crate) fn new(rule: &Rule) -> Self {
+        if rule.is_totalistic() {
+            Self::Totalistic {
+                birth: (0..=8).filter(|&n| rule.is_born(n)).collect(),
+                survival: (0..=8).filter(|&n| rule.survives(n)).collect(),
+            }
+        } else {
+            Self::Isotropic(Box::new(std::array::from_fn(|n| {
+                (rule.is_born_with(n as u8), rule.survives_with(n as u8))
+            })))
+        }
+    }
+
+    // Drop births on zero neighbours, which would fill an unbounded plane
+    pub(crate) fn without_b0(mut self) -> Self {
+        match &mut self {
+            Self::Totalistic { birth, .. } => birth.retain(|&n| n != 0),
+            Self::Isotropic(table) => table[0].0 = false,
+        }
+        self
+    }
+
+    // Returns the cells whose neighbours allow a birth and those whose
+    // neighbours allow survival, given the neighbour words in the order
+    // NW, N, NE, W, E, SW, S, SE
+    pub(crate) fn apply(&self, neighbors: [u64; 8]) -> (u64, u64) {
+        match self {
+            Self::Totalistic { birth, survival } => {
+                let count = count_neighbors(neighbors);
+                let matches = |n: usize| {
+                    count.iter().enumerate().fold(u64::MAX, |acc, (k, &plane)| {
+                        acc & if n & (1 << k) != 0 { plane } else { !plane }
+                    })
+                };
+                let any = |counts: &[usize]| counts.iter().fold(0, |acc, &n| acc | matches(n));
+                (any(birth), any(survival))
+            }
+            Self::Isotropic(table) => {
+                let mut born = 0;
+                let mut survive = 0;
+                for i in 0..64 {
+                    // The neighbourhood byte takes NW as its high bit, as in `Rule`
+                    let neighbourhood = neighbors.iter().fold(0, |acc, &word| (acc << 1) | ((word >> i) & 1) as usize);
+                    let (birth, survival) = table[neighbourhood];
+                    born |= u64::from(birth) << i;
+                    survive |= u64::from(survival) << i;
+                }
+                (born, survive)
+            }
+        }
+    }
+}
+
+fn bit(row: &[u64], x: usize) -> bool {
+    row[x / 64] & (1 << (x % 64)) != 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Pattern;
+
+    #[test]
+    #[should_panic(expected = "cannot run 345/2/4")]
+    fn rules_beyond_two_square_states_are_refused() {
+        BitGrid::new(8, 8).set_rule("345/2/4".parse().unwrap());
+    }
+
+    #[test]
+    fn hensel_rules_run_cell_by_cell() {
+        let rule: Rule = "B2-a/S12".parse().unwrap();
+        let mut universe = Universe::new(16, 16);
+        universe.set_rule(rule);
+        for (x, y) in [(3, 3), (4, 3), (5, 4), (9, 9), (10, 10)] {
+            universe.set_cell(x, y, true);
+        }
+        let mut grid = BitGrid::from(&universe);
+        for _ in 0..8 {
+            universe.step();
+            grid.step();
+        }
+        let mut copy = universe.clone();
+        grid.copy_to(&mut copy);
+        assert_eq!(Pattern::from_universe(&copy), Pattern::from_universe(&universe));
+    }
+}
@@ -0,0 +1,188 @@
+//! Cell shapes for the drawing tools.
+//!
+//! Shapes are rasterized between two corner cells, both included, so that
+//! the same code serves dragging in any direction.
+
+use std::fmt;
+
+use crate::Engine;
+
+/// A shape drawn by dragging from one cell to another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Shape {
+    Line,
+    Rectangle,
+    FilledRectangle,
+    Ellipse,
+}
+
+impl Shape {
+    pub const ALL: [Shape; 4] = [Shape::Line, Shape::Rectangle, Shape::FilledRectangle, Shape::Ellipse];
+
+    /// The cells of the shape spanning `from` to `to`. For rectangles and
+    /// ellipses the two cells are opposite corners of the bounding box.
+    pub fn cells(self, from: (i64, i64), to: (i64, i64)) -> Vec<(i64, i64)> {
+        match self {
+            Shape::Line => line(from, to),
+            Shape::Rectangle => rectangle(from, to, false),
+            Shape::FilledRectangle => rectangle(from, to, true),
+            Shape::Ellipse => ellipse(from, to),
+        }
+    }
+}
+
+impl fmt::Display for Shape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Shape::Line => "Line",
+            Shape::Rectangle => "Rectangle",
+            Shape::FilledRectangle => "Filled rectangle",
+            Shape::Ellipse => "Ellipse",
+        })
+    }
+}
+
+// Bresenham's line, visiting every cell once from `from` to `to`
+fn line((mut x, mut y): (i64, i64), (x1, y1): (i64, i64)) -> Vec<(i64, i64)> {
+    let (dx, dy) = ((x1 - x).abs(), -(y1 - y).abs());
+    let (sx, sy) = ((x1 - x).signum(), (y1 - y).signum());
+    let mut err = dx + dy;
+    let mut cells = Vec::with_capacity(dx.max(-dy) as usize + 1);
+    loop {
+        cells.push((x, y));
+        if (x, y) == (x1, y1) {
+            return cells;
+        }
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            x += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            y += sy;
+        }
+    }
+}
+
+fn rectangle((x0, y0): (i64, i64), (x1, y1): (i64, i64), filled: bool) -> Vec<(i64, i64)> {
+    let (left, right) = (x0.min(x1), x0.max(x1));
+    let (top, bottom) = (y0.min(y1), y0.max(y1));
+    let mut cells = Vec::new();
+    for y in top..=bottom {
+        if filled || y == top || y == bottom {
+            cells.extend((left..=right).map(|x| (x, y)));
+        } else {
+            cells.push((left, y));
+            if right != left {
+                cells.push((right, y));
+            }
+        }
+    }
+    cells
+}
+
+// The outline of the ellipse inscribed in the box, using Alois Zingl's
+// midpoint algorithm, which also handles even widths and heights
+fn ellipse((mut x0, mut y0): (i64, i64), (mut x1, mut y1): (i64, i64)) -> Vec<(i64, i64)> {
+    let mut a = (x1 - x0).abs();
+    let b = (y1 - y0).abs();
+    let mut b1 = b & 1;
+    let mut dx = 4 * (1 - a) * b * b;
+    let mut dy = 4 * (b1 + 1) * a * a;
+    let mut err = dx + dy + b1 * a * a;
+
+    if x0 > x1 {
+        x0 = x1;
+        x1 += a;
+    }
+    if y0 > y1 {
+        y0 = y1;
+    }
+    y0 += (b + 1) / 2;
+    y1 = y0 - b1;
+    a = 8 * a * a;
+    b1 = 8 * b * b;
+
+    let mut cells = Vec::new();
+    loop {
+        cells.extend([(x1, y0), (x0, y0), (x0, y1), (x1, y1)]);
+        let e2 = 2 * err;
+        if e2 <= dy {
+            y0 += 1;
+            y1 -= 1;
+            dy += a;
+            err += dy;
+        }
+        if e2 >= dx || 2 * err > dy {
+            x0 += 1;
+            x1 -= 1;
+            dx += b1;
+            err += dx;
+        }
+        if x0 > x1 {
+            break;
+        }
+    }
+
+    // Finish the tips of very flat ellipses
+    while y0 - y1 <= b {
+        cells.extend([(x0 - 1, y0), (x1 + 1, y0), (x0 - 1, y1), (x1 + 1, y1)]);
+        y0 += 1;
+        y1 -= 1;
+    }
+
+    cells.sort_unstable_by_key(|&(x, y)| (y, x));
+    cells.dedup();
+    cells
+}
+
+/// Flips the 4-connected region of cells sharing the state of `(x, y)`,
+/// staying within `left <= x < right`, `top <= y < bottom`. Returns the
+/// number of cells changed.
+pub fn flood_fill(engine: &mut dyn Engine, x: i64, y: i64, bounds: (i64, i64, i64, i64)) -> u64 {
+    let (left, top, right, bottom) = bounds;
+    if !(left..right).contains(&x) || !(top..bottom).contains(&y) {
+        return 0;
+    }
+
+    // Scanline fill: flip a whole run of the row at a time and queue the
+    // rows above and below it
+    let target = engine.get_cell(x, y);
+    let mut seeds = vec![(x, y)];
+    let mut changed = 0;
+    while let Some((x, y)) = seeds.pop() {
+        if engine.get_cell(x, y) != target {
+            continue;
+        }
+        let mut start = x;
+        while start > left && engine.get_cell(start - 1, y) == target {
+            start -= 1;
+        }
+        let mut end = x + 1;
+        while end < right && engine.get_cell(end, y) == target {
+            end += 1;
+        }
+
+        for x in start..end {
+            engine.set_cell(x, y, !target);
+        }
+        changed += (end - start) as u64;
+
+        for row in [y - 1, y + 1] {
+            if !(top..bottom).contains(&row) {
+                continue;
+            }
+            // One seed per run of matching cells
+            let mut in_run = false;
+            for x in start..end {
+                let matches = engine.get_cell(x, row) == target;
+                if matches && !in_run {
+                    seeds.push((x, row));
+                }
+                in_run = matches;
+            }
+        }
+    }
+    changed
+}
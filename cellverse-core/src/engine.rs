@@ -0,0 +1,84 @@
+use crate::{Rule, Stats, Universe};
+
+/// The stepping interface shared by every simulation backend.
+///
+/// Coordinates are signed so that backends on an unbounded plane can be
+/// addressed the same way as the bounded [`Universe`].
+pub trait Engine {
+    /// Advances by one generation.
+    fn step(&mut self);
+
+    /// Advances by `generations` generations. Backends that can jump ahead
+    /// faster than one step at a time override this.
+    fn step_by(&mut self, generations: u64) {
+        for _ in 0..generations {
+            self.step();
+        }
+    }
+
+    /// Returns whether the cell at `(x, y)` is alive.
+    fn get_cell(&self, x: i64, y: i64) -> bool;
+
+    /// Sets the cell at `(x, y)`. Backends ignore cells they cannot store.
+    fn set_cell(&mut self, x: i64, y: i64, alive: bool);
+
+    /// Returns the state of the cell at `(x, y)`: 0 when dead, 1 when alive.
+    /// Backends with more states override this.
+    fn get_state(&self, x: i64, y: i64) -> u8 {
+        u8::from(self.get_cell(x, y))
+    }
+
+    /// Puts the cell at `(x, y)` into `state`. Backends with only two states
+    /// take every state but 0 as alive; others ignore states their rule
+    /// does not have.
+    fn set_state(&mut self, x: i64, y: i64, state: u8) {
+        self.set_cell(x, y, state != 0);
+    }
+
+    fn stats(&self) -> Stats;
+
+    fn rule(&self) -> Rule;
+
+    fn set_rule(&mut self, rule: Rule);
+
+    /// Kills every cell and resets all counters.
+    fn clear(&mut self);
+}
+
+impl Engine for Universe {
+    fn step(&mut self) {
+        Universe::step(self);
+    }
+
+    fn get_cell(&self, x: i64, y: i64) -> bool {
+        self.cell(x, y)
+    }
+
+    fn set_cell(&mut self, x: i64, y: i64, alive: bool) {
+        self.set_at(x, y, alive);
+    }
+
+    fn get_state(&self, x: i64, y: i64) -> u8 {
+        Universe::state(self, x, y)
+    }
+
+    fn set_state(&mut self, x: i64, y: i64, state: u8) {
+        Universe::set_state(self, x, y, state);
+    }
+
+    fn stats(&self) -> Stats {
+        Universe::stats(self)
+    }
+
+    fn rule(&self) -> Rule {
+        Universe::rule(self)
+    }
+
+    fn set_rule(&mut self, rule: Rule) {
+        Universe::set_rule(self, rule);
+    }
+
+    fn clear(&mut self) {
+        Universe::clear(self);
+    }
+}
@@ -0,0 +1,144 @@
+//! The Life 1.05 format: blocks of cells positioned relative to the origin.
+//!
+//! After the `#Life 1.05` header come `#D` description lines, an optional
+//! rule (`#N` for Conway's Life or `#R 23/3` in survival/birth order) and one
+//! or more `#P x y` blocks whose rows use `*` for live and `.` for dead cells.
+
+use super::{Extent, ParsePatternError};
+use crate::{Pattern, Rule};
+
+pub(crate) const HEADER: &str = "#Life 1.05";
+
+/// Parses a Life 1.05 pattern.
+pub fn read(text: &str) -> Result<Pattern, ParsePatternError> {
+    let mut name = None;
+    let mut author = None;
+    let mut comments = Vec::new();
+    let mut rule = None;
+    let mut cells = Vec::new();
+    let mut extent = Extent::default();
+    // Top-left corner of the current block and the row being read within it
+    let (mut left, mut top) = (0i64, 0i64);
+    let mut row = 0;
+
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(HEADER) {
+            continue;
+        }
+
+        if let Some(directive) = line.strip_prefix('#') {
+            let mut chars = directive.chars();
+            let tag = chars.next().unwrap_or(' ');
+            let content = chars.as_str().trim();
+            match tag {
+                // Names and authors only exist as descriptions, as written by `write`
+                'D' | 'C' => match content.split_once(':') {
+                    Some(("Name", value)) => name = Some(value.trim().to_string()),
+                    Some(("Author", value)) => author = Some(value.trim().to_string()),
+                    _ => comments.push(content.to_string()),
+                },
+                'N' => rule = Some(Rule::conway()),
+                'R' => rule = Some(content.parse::<Rule>()?),
+                'P' => {
+                    (left, top) = parse_position(content, line_number)?;
+                    row = 0;
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        for (x, c) in line.chars().enumerate() {
+            match c {
+                '*' | 'O' => {
+                    let offset = |start: i64, by: usize| i64::try_from(by).ok().and_then(|by| start.checked_add(by));
+                    let (Some(x), Some(y)) = (offset(left, x), offset(top, row)) else {
+                        return Err(ParsePatternError::malformed(line_number, "cell position is out of range"));
+                    };
+                    extent.include(x, y, line_number)?;
+                    cells.push((x, y));
+                }
+                '.' => {}
+                c => {
+                    return Err(ParsePatternError::malformed(
+                        line_number,
+                        format!("unexpected character '{}' in pattern data", c),
+                    ))
+                }
+            }
+        }
+        row += 1;
+    }
+
+    let mut pattern = Pattern::from_cells(cells);
+    pattern.name = name;
+    pattern.author = author;
+    pattern.comments = comments;
+    pattern.rule = rule;
+    Ok(pattern)
+}
+
+/// Writes `pattern` as a single block centred on the origin. The name and
+/// author become `#D` lines, since the format has no fields for them.
+pub fn write(pattern: &Pattern) -> String {
+    let mut out = format!("{}\n", HEADER);
+    if let Some(name) = &pattern.name {
+        out.push_str(&format!("#D Name: {}\n", name));
+    }
+    if let Some(author) = &pattern.author {
+        out.push_str(&format!("#D Author: {}\n", author));
+    }
+    for comment in &pattern.comments {
+        out.push_str(&format!("#D {}\n", comment));
+    }
+
+    match &pattern.rule {
+        None => {}
+        Some(rule) if *rule == Rule::conway() => out.push_str("#N\n"),
+        // Only totalistic two-state rules on the square lattice can be
+        // written; Hensel's neighbourhood letters are left out with the rest
+        Some(rule) if rule.is_life_like() && rule.is_totalistic() => {
+            // Life 1.05 lists survival before birth, without the letters
+            let text = rule.to_string();
+            if let Some((birth, survival)) = text
+                .split_once('/')
+                .and_then(|(birth, survival)| Some((birth.strip_prefix('B')?, survival.strip_prefix('S')?)))
+            {
+                out.push_str(&format!("#R {}/{}\n", survival, birth));
+            }
+        }
+        Some(_) => {}
+    }
+
+    let left = -(pattern.width() as i64 / 2);
+    let top = -(pattern.height() as i64 / 2);
+    out.push_str(&format!("#P {} {}\n", left, top));
+
+    let mut cells = pattern.live_cells().peekable();
+    for y in 0..pattern.height() as i64 {
+        let mut row = String::new();
+        while let Some((x, _)) = cells.next_if(|&(_, cy)| cy == y) {
+            while (row.len() as i64) < x {
+                row.push('.');
+            }
+            row.push('*');
+        }
+        if row.is_empty() {
+            row.push('.');
+        }
+        out.push_str(&row);
+        out.push('\n');
+    }
+    out
+}
+
+// Parse the `x y` of a `#P` line
+fn parse_position(content: &str, line_number: usize) -> Result<(i64, i64), ParsePatternError> {
+    let mut numbers = content.split_whitespace().map(str::parse::<i64>);
+    match (numbers.next(), numbers.next(), numbers.next()) {
+        (Some(Ok(x)), Some(Ok(y)), None) => Ok((x, y)),
+        _ => Err(ParsePatternError::malformed(line_number, "expected '#P x y'")),
+    }
+}
@@ -0,0 +1,51 @@
+//! The Life 1.06 format: a `#Life 1.06` header followed by one `x y` line
+//! per live cell.
+
+use super::{Extent, ParsePatternError};
+use crate::Pattern;
+
+pub(crate) const HEADER: &str = "#Life 1.06";
+
+/// Parses a Life 1.06 pattern. Other `#` lines are kept as comments.
+pub fn read(text: &str) -> Result<Pattern, ParsePatternError> {
+    let mut comments = Vec::new();
+    let mut cells = Vec::new();
+    let mut extent = Extent::default();
+
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(HEADER) {
+            continue;
+        }
+        if let Some(comment) = line.strip_prefix('#') {
+            comments.push(comment.trim().to_string());
+            continue;
+        }
+
+        let mut numbers = line.split_whitespace().map(str::parse::<i64>);
+        match (numbers.next(), numbers.next(), numbers.next()) {
+            (Some(Ok(x)), Some(Ok(y)), None) => {
+                extent.include(x, y, index + 1)?;
+                cells.push((x, y));
+            }
+            _ => return Err(ParsePatternError::malformed(index + 1, "expected 'x y'")),
+        }
+    }
+
+    let mut pattern = Pattern::from_cells(cells);
+    pattern.comments = comments;
+    Ok(pattern)
+}
+
+/// Writes the live cells of `pattern` centred on the origin. Metadata and
+/// the rule have no place in the format and are left out.
+pub fn write(pattern: &Pattern) -> String {
+    let left = pattern.width() as i64 / 2;
+    let top = pattern.height() as i64 / 2;
+
+    let mut out = format!("{}\n", HEADER);
+    for (x, y) in pattern.live_cells() {
+        out.push_str(&format!("{} {}\n", x - left, y - top));
+    }
+    out
+}
@@ -0,0 +1,309 @@
+//! Golly's Macrocell (`.mc`) format, which stores the HashLife quadtree
+//! directly so that patterns with billions of cells stay small.
+//!
+//! After the `[M2]` header and `#` lines (`#R` rule, `#G` generation, `#C`
+//! comments) every line defines one node, numbered from 1 in file order, with
+//! 0 standing for an empty node. The two-state variant writes 8x8 leaves as
+//! bitmaps such as `.**$*.*$..*$`; the multi-state variant uses `1 a b c d`
+//! lines whose children are cell states. Larger nodes are `level nw ne sw se`.
+//!
+//! Files are read into a [`HashLife`] plane as they are, quadtree and all,
+//! rather than cell by cell.
+
+use std::collections::HashMap;
+
+use super::ParsePatternError;
+use crate::hashlife::{NodeId, ALIVE, DEAD, MAX_LEVEL};
+use crate::{Engine, HashLife, Pattern, Rule};
+
+const HEADER: &str = "[M2] (cellverse)";
+
+// Bitmap leaves are 8x8 cells, i.e. level 3 nodes
+const LEAF_LEVEL: u8 = 3;
+const LEAF_SIZE: usize = 1 << LEAF_LEVEL;
+
+/// The contents of a Macrocell file.
+#[derive(Debug, Clone)]
+pub struct Macrocell {
+    /// The quadtree, centred on the origin.
+    pub hashlife: HashLife,
+    /// The `#R` rule when it is not one [`Rule`] can parse, such as the name
+    /// of a rule table. The plane keeps the default rule then.
+    pub rule_name: Option<String>,
+}
+
+impl Macrocell {
+    /// Every cell of the file that is not dead, as a pattern.
+    pub fn to_pattern(&self) -> Pattern {
+        let mut pattern = Pattern::from_hashlife(&self.hashlife);
+        if self.rule_name.is_some() {
+            pattern.rule = None;
+            pattern.rule_name = self.rule_name.clone();
+        }
+        pattern
+    }
+}
+
+/// Parses a Macrocell file of either variant into a HashLife plane, with
+/// the quadtree centred on the origin. Cell states are checked against the
+/// `#R` rule, which comes before the nodes as in Golly's files.
+pub fn read(text: &str) -> Result<Macrocell, ParsePatternError> {
+    let mut hashlife = HashLife::new(Rule::default());
+    let mut rule_name = None;
+    let mut generation = 0;
+    // Our node for each node number in the file, starting with the empty node 0
+    let mut nodes: Vec<NodeId> = vec![DEAD];
+
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('[') {
+            continue;
+        }
+
+        if let Some(comment) = line.strip_prefix('#') {
+            let mut chars = comment.chars();
+            let tag = chars.next().unwrap_or(' ');
+            let content = chars.as_str().trim();
+            match tag {
+                'R' => match content.parse::<Rule>() {
+                    Ok(rule) => hashlife.set_rule(rule),
+                    Err(_) => rule_name = Some(content.to_string()),
+                },
+                'G' => {
+                    generation = content.parse::<u64>().map_err(|_| {
+                        ParsePatternError::malformed(line_number, format!("'{}' is not a valid generation", content))
+                    })?
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        let node = if line.starts_with(['.', '*', '$']) {
+            read_leaf(&mut hashlife, line, line_number)?
+        } else {
+            // A rule known only by name may have any number of states
+            let states = match rule_name {
+                Some(_) => u16::from(u8::MAX) + 1,
+                None => u16::from(hashlife.rule().states()),
+            };
+            read_node(&mut hashlife, &nodes, states, line, line_number)?
+        };
+        nodes.push(node);
+    }
+
+    if nodes.len() > 1 {
+        hashlife.set_root(nodes[nodes.len() - 1], generation);
+    }
+    Ok(Macrocell { hashlife, rule_name })
+}
+
+/// Writes the two-state variant, with 8x8 bitmap leaves.
+pub fn write(hashlife: &HashLife) -> String {
+    Writer::new(hashlife, LEAF_LEVEL, hashlife.rule().to_string()).finish()
+}
+
+/// Writes the multi-state variant, with `1 a b c d` leaves as Golly uses
+/// for rules with more than two states.
+pub fn write_multistate(hashlife: &HashLife) -> String {
+    Writer::new(hashlife, 1, hashlife.rule().to_string()).finish()
+}
+
+// Write `pattern` in the two-state variant if its cells are all alive and in
+// the multi-state one otherwise. `#R` gives the rule's name when the pattern
+// only has that.
+pub(super) fn write_pattern(pattern: &Pattern) -> String {
+    let rule = pattern.rule.clone().unwrap_or_default();
+    let name = match (&pattern.rule, &pattern.rule_name) {
+        (None, Some(name)) => name.clone(),
+        _ => rule.to_string(),
+    };
+    // Without the rule itself the plane cannot tell which states are valid,
+    // so the cells go in as they are
+    let mut hashlife = HashLife::new(rule);
+    for &(x, y, state) in pattern.cells() {
+        hashlife.put_state(x, y, state);
+    }
+    let multistate = pattern.cells().iter().any(|&(_, _, state)| state > 1);
+    Writer::new(&hashlife, if multistate { 1 } else { LEAF_LEVEL }, name).finish()
+}
+
+// Parse a bitmap line into a level 3 node
+fn read_leaf(hashlife: &mut HashLife, line: &str, line_number: usize) -> Result<NodeId, ParsePatternError> {
+    let mut cells = [[false; LEAF_SIZE]; LEAF_SIZE];
+    let (mut x, mut y) = (0, 0);
+    for c in line.chars() {
+        match c {
+            '.' | '*' => {
+                if x >= LEAF_SIZE || y >= LEAF_SIZE {
+                    return Err(ParsePatternError::malformed(line_number, "leaf is larger than 8x8"));
+                }
+                cells[y][x] = c == '*';
+                x += 1;
+            }
+            '$' => {
+                y += 1;
+                x = 0;
+            }
+            c => {
+                return Err(ParsePatternError::malformed(
+                    line_number,
+                    format!("unexpected character '{}' in leaf", c),
+                ))
+            }
+        }
+    }
+    Ok(build_leaf(hashlife, &cells, 0, 0, LEAF_LEVEL))
+}
+
+fn build_leaf(hashlife: &mut HashLife, cells: &[[bool; LEAF_SIZE]; LEAF_SIZE], x: usize, y: usize, level: u8) -> NodeId {
+    if level == 0 {
+        return if cells[y][x] { ALIVE } else { DEAD };
+    }
+    let half = 1 << (level - 1);
+    let children = [(x, y), (x + half, y), (x, y + half), (x + half, y + half)]
+        .map(|(x, y)| build_leaf(hashlife, cells, x, y, level - 1));
+    hashlife.join(children)
+}
+
+// Parse `level nw ne sw se`, where level 1 nodes hold cell states below `states`
+fn read_node(
+    hashlife: &mut HashLife,
+    nodes: &[NodeId],
+    states: u16,
+    line: &str,
+    line_number: usize,
+) -> Result<NodeId, ParsePatternError> {
+    let numbers = line
+        .split_whitespace()
+        .map(|field| field.parse::<u64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| ParsePatternError::malformed(line_number, "expected 'level nw ne sw se'"))?;
+    let [level, nw, ne, sw, se] = numbers[..] else {
+        return Err(ParsePatternError::malformed(line_number, "expected 'level nw ne sw se'"));
+    };
+    let children = [nw, ne, sw, se];
+    if level == 0 || level > u64::from(MAX_LEVEL) {
+        return Err(ParsePatternError::malformed(line_number, format!("level {} is out of range", level)));
+    }
+    let level = level as u8;
+
+    // The children of level 1 nodes are cell states, which are also the ids
+    // of the leaves
+    if level == 1 {
+        if let Some(&state) = children.iter().find(|&&state| state >= u64::from(states)) {
+            return Err(ParsePatternError::malformed(
+                line_number,
+                format!("state {} is not one of the rule's {} states", state, states),
+            ));
+        }
+        return Ok(hashlife.join(children.map(|state| state as NodeId)));
+    }
+
+    let mut ids = [DEAD; 4];
+    for (id, &child) in ids.iter_mut().zip(&children) {
+        *id = match child {
+            0 => hashlife.empty_node(level - 1),
+            child => {
+                let node = *nodes.get(child as usize).ok_or_else(|| {
+                    ParsePatternError::malformed(line_number, format!("node {} is not defined yet", child))
+                })?;
+                if hashlife.node_level(node) != level - 1 {
+                    return Err(ParsePatternError::malformed(
+                        line_number,
+                        format!("node {} does not have level {}", child, level - 1),
+                    ));
+                }
+                node
+            }
+        };
+    }
+    Ok(hashlife.join(ids))
+}
+
+// Numbers nodes in the order they are written, children before parents
+struct Writer<'a> {
+    hashlife: &'a HashLife,
+    leaf_level: u8,
+    numbers: HashMap<NodeId, usize>,
+    out: String,
+}
+
+impl<'a> Writer<'a> {
+    fn new(hashlife: &'a HashLife, leaf_level: u8, rule: String) -> Self {
+        let mut out = format!("{}\n#R {}\n", HEADER, rule);
+        let generation = hashlife.stats().generation;
+        if generation != 0 {
+            out.push_str(&format!("#G {}\n", generation));
+        }
+        Self {
+            hashlife,
+            leaf_level,
+            numbers: HashMap::new(),
+            out,
+        }
+    }
+
+    fn finish(mut self) -> String {
+        // An empty plane has no nodes at all
+        self.write_node(self.hashlife.root());
+        self.out
+    }
+
+    // Returns the node's number, writing it and its descendants if needed
+    fn write_node(&mut self, node: NodeId) -> usize {
+        if self.hashlife.occupied(node) == 0 {
+            return 0;
+        }
+        if let Some(&number) = self.numbers.get(&node) {
+            return number;
+        }
+
+        let level = self.hashlife.node_level(node);
+        let children = self.hashlife.children(node);
+        let line = if level == LEAF_LEVEL && self.leaf_level == LEAF_LEVEL {
+            self.bitmap(node)
+        } else if level == 1 {
+            // Leaf ids are the cell states
+            let [nw, ne, sw, se] = children;
+            format!("1 {} {} {} {}", nw, ne, sw, se)
+        } else {
+            let [nw, ne, sw, se] = children.map(|child| self.write_node(child));
+            format!("{} {} {} {} {}", level, nw, ne, sw, se)
+        };
+
+        self.out.push_str(&line);
+        self.out.push('\n');
+        let number = self.numbers.len() + 1;
+        self.numbers.insert(node, number);
+        number
+    }
+
+    // Rows of `.` and `*` ending in `$`, without trailing dead cells or rows
+    fn bitmap(&self, node: NodeId) -> String {
+        let mut cells = [[false; LEAF_SIZE]; LEAF_SIZE];
+        self.fill_cells(node, 0, 0, LEAF_LEVEL, &mut cells);
+
+        let rows: Vec<String> = cells
+            .iter()
+            .map(|row| {
+                let used = row.iter().rposition(|&alive| alive).map_or(0, |last| last + 1);
+                row[..used].iter().map(|&alive| if alive { '*' } else { '.' }).collect()
+            })
+            .collect();
+        let used = rows.iter().rposition(|row| !row.is_empty()).map_or(0, |last| last + 1);
+        rows[..used].iter().map(|row| format!("{}$", row)).collect()
+    }
+
+    fn fill_cells(&self, node: NodeId, x: usize, y: usize, level: u8, cells: &mut [[bool; LEAF_SIZE]; LEAF_SIZE]) {
+        if level == 0 {
+            cells[y][x] = node == ALIVE;
+            return;
+        }
+        let half = 1 << (level - 1);
+        for (index, child) in self.hashlife.children(node).into_iter().enumerate() {
+            self.fill_cells(child, x + (index % 2) * half, y + (index / 2) * half, level - 1, cells);
+        }
+    }
+}
@@ -0,0 +1,190 @@
+//! Readers and writers for pattern file formats.
+//!
+//! Each format has its own module with `read` and `write` functions; [`read`]
+//! and [`write`] here pick the module from a [`Format`].
+
+use std::fmt;
+
+use crate::{ParseRuleError, Pattern};
+
+pub mod life105;
+pub mod life106;
+pub mod macrocell;
+pub mod plaintext;
+pub mod rle;
+
+/// A supported pattern file format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Format {
+    Rle,
+    Macrocell,
+    Plaintext,
+    Life105,
+    Life106,
+}
+
+impl Format {
+    pub const ALL: [Format; 5] = [
+        Format::Rle,
+        Format::Macrocell,
+        Format::Plaintext,
+        Format::Life105,
+        Format::Life106,
+    ];
+
+    /// Guesses the format of a file from its contents. Anything that is not
+    /// recognisably another format is taken to be RLE.
+    pub fn detect(text: &str) -> Format {
+        let Some(first) = text.lines().map(str::trim).find(|line| !line.is_empty()) else {
+            return Format::Rle;
+        };
+
+        if first.starts_with("[M2]") {
+            Format::Macrocell
+        } else if first.starts_with(life105::HEADER) {
+            Format::Life105
+        } else if first.starts_with(life106::HEADER) {
+            Format::Life106
+        } else if plaintext::matches(text) {
+            Format::Plaintext
+        } else {
+            Format::Rle
+        }
+    }
+
+    /// The usual file extension, without the dot.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Rle => "rle",
+            Format::Macrocell => "mc",
+            Format::Plaintext => "cells",
+            Format::Life105 | Format::Life106 => "lif",
+        }
+    }
+
+    /// The format written for a file extension. `.lif` and `.life` files are
+    /// written as Life 1.06.
+    pub fn from_extension(extension: &str) -> Option<Format> {
+        match extension.to_ascii_lowercase().as_str() {
+            "rle" => Some(Format::Rle),
+            "mc" => Some(Format::Macrocell),
+            "cells" => Some(Format::Plaintext),
+            "lif" | "life" => Some(Format::Life106),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Format::Rle => "RLE",
+            Format::Macrocell => "Macrocell",
+            Format::Plaintext => "Plaintext",
+            Format::Life105 => "Life 1.05",
+            Format::Life106 => "Life 1.06",
+        })
+    }
+}
+
+/// Reads a pattern in any supported format, detected from its contents.
+pub fn read(text: &str) -> Result<Pattern, ParsePatternError> {
+    match Format::detect(text) {
+        Format::Rle => rle::read(text),
+        Format::Macrocell => macrocell::read(text).map(|macrocell| macrocell.to_pattern()),
+        Format::Plaintext => plaintext::read(text),
+        Format::Life105 => life105::read(text),
+        Format::Life106 => life106::read(text),
+    }
+}
+
+/// Writes `pattern` in `format`. Metadata the format cannot hold is dropped.
+pub fn write(pattern: &Pattern, format: Format) -> String {
+    match format {
+        Format::Rle => rle::write(pattern),
+        Format::Macrocell => macrocell::write_pattern(pattern),
+        Format::Plaintext => plaintext::write(pattern),
+        Format::Life105 => life105::write(pattern),
+        Format::Life106 => life106::write(pattern),
+    }
+}
+
+/// Error returned when a pattern file cannot be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParsePatternError {
+    /// The file does not follow the format; `line` is 1-based.
+    Malformed { line: usize, message: String },
+    /// The file names a rule that cannot be parsed.
+    Rule(ParseRuleError),
+}
+
+impl ParsePatternError {
+    pub(crate) fn malformed(line: usize, message: impl Into<String>) -> Self {
+        ParsePatternError::Malformed {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+// The bounding box of the cells a reader has found so far, so coordinates too
+// far apart for a pattern's size are reported on the line they appear
+#[derive(Default)]
+pub(crate) struct Extent(Option<(i64, i64, i64, i64)>);
+
+impl Extent {
+    pub(crate) fn include(&mut self, x: i64, y: i64, line: usize) -> Result<(), ParsePatternError> {
+        let (min_x, max_x, min_y, max_y) = match self.0 {
+            Some((min_x, max_x, min_y, max_y)) => (min_x.min(x), max_x.max(x), min_y.min(y), max_y.max(y)),
+            None => (x, x, y, y),
+        };
+        // The size is the span plus one, which must fit as well
+        let fits = |min: i64, max: i64| max.checked_sub(min).is_some_and(|span| span < i64::MAX);
+        if !fits(min_x, max_x) || !fits(min_y, max_y) {
+            return Err(ParsePatternError::malformed(line, "cells are too far apart"));
+        }
+        self.0 = Some((min_x, max_x, min_y, max_y));
+        Ok(())
+    }
+}
+
+impl fmt::Display for ParsePatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParsePatternError::Malformed { line, message } => write!(f, "line {}: {}", line, message),
+            ParsePatternError::Rule(err) => write!(f, "invalid rule: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ParsePatternError {}
+
+impl From<ParseRuleError> for ParsePatternError {
+    fn from(err: ParseRuleError) -> Self {
+        ParsePatternError::Rule(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn written_patterns_are_detected_and_read_back() {
+        let glider = Pattern::from_cells([(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)]);
+        for format in Format::ALL {
+            let text = write(&glider, format);
+            assert_eq!(Format::detect(&text), format, "{}", text);
+            assert_eq!(read(&text).unwrap().cells(), glider.cells(), "{}", format);
+        }
+
+        // Indented rows are not plaintext, which has no place for spaces
+        let indented = "!Name: Blinker\n  OOO\n";
+        assert_ne!(Format::detect(indented), Format::Plaintext);
+        assert!(plaintext::read(indented).is_err());
+        // Trailing spaces are fine in both
+        let trailing = "!Name: Blinker\nOOO  \n";
+        assert_eq!(Format::detect(trailing), Format::Plaintext);
+        assert_eq!(read(trailing).unwrap().population(), 3);
+    }
+}
@@ -0,0 +1,96 @@
+//! The plaintext (`.cells`) format used by the LifeWiki.
+//!
+//! Lines starting with `!` are comments, with `!Name:` and `!Author:` giving
+//! the pattern's metadata. Every other line is a row of cells, `O` for live
+//! and `.` for dead; short rows are padded with dead cells.
+
+use super::ParsePatternError;
+use crate::Pattern;
+
+// Whether every line of `text` is a comment or a row of cells, read the
+// way `read` reads them
+pub(super) fn matches(text: &str) -> bool {
+    text.lines()
+        .map(str::trim_end)
+        .all(|line| line.starts_with('!') || line.chars().all(|c| matches!(c, '.' | 'O' | '*')))
+}
+
+/// Parses a plaintext pattern. `*` is accepted as a live cell too.
+pub fn read(text: &str) -> Result<Pattern, ParsePatternError> {
+    let mut name = None;
+    let mut author = None;
+    let mut comments = Vec::new();
+    let mut cells = Vec::new();
+    let mut width = 0;
+    let mut y = 0i64;
+
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim_end();
+        if let Some(comment) = line.strip_prefix('!') {
+            if let Some(value) = comment.strip_prefix("Name:") {
+                name = Some(value.trim().to_string());
+            } else if let Some(value) = comment.strip_prefix("Author:") {
+                author = Some(value.trim().to_string());
+            } else {
+                comments.push(comment.trim().to_string());
+            }
+            continue;
+        }
+
+        for (x, c) in line.chars().enumerate() {
+            match c {
+                'O' | '*' => cells.push((x as i64, y, 1)),
+                '.' => {}
+                c => {
+                    return Err(ParsePatternError::malformed(
+                        index + 1,
+                        format!("unexpected character '{}' in pattern data", c),
+                    ))
+                }
+            }
+        }
+        width = width.max(line.chars().count() as u64);
+        y += 1;
+    }
+
+    // Trailing blank rows are usually just the end of the file
+    let mut pattern = Pattern::from_placed_cells(cells);
+    pattern.set_size(width, pattern.height());
+    pattern.name = name;
+    pattern.author = author;
+    pattern.comments = comments;
+    Ok(pattern)
+}
+
+/// Writes `pattern` as plaintext. The rule is not part of the format and is
+/// left out.
+pub fn write(pattern: &Pattern) -> String {
+    let mut out = String::new();
+    if let Some(name) = &pattern.name {
+        out.push_str(&format!("!Name: {}\n", name));
+    }
+    if let Some(author) = &pattern.author {
+        out.push_str(&format!("!Author: {}\n", author));
+    }
+    for comment in &pattern.comments {
+        out.push_str(&format!("!{}\n", comment));
+    }
+
+    let mut cells = pattern.live_cells().peekable();
+    for y in 0..pattern.height() as i64 {
+        // Rows stop at their last live cell; empty rows are a single dead cell
+        let mut row = String::new();
+        while let Some((x, _)) = cells.next_if(|&(_, cy)| cy == y) {
+            while (row.len() as i64) < x {
+                row.push('.');
+            }
+            row.push('O');
+        }
+        if row.is_empty() {
+            row.push('.');
+        }
+        out.push_str(&row);
+        out.push('\n');
+    }
+    out
+}
@@ -0,0 +1,318 @@
+//! Run Length Encoded (`.rle`) patterns, as used by the LifeWiki and Golly.
+//!
+//! A file consists of optional `#` comment lines (`#N` name, `#O` author,
+//! `#C` comments), a header such as `x = 3, y = 3, rule = B3/S23` and a body
+//! of runs like `bo$2bo$3o!`, where `b` is a dead cell, `o` a live one and `$`
+//! ends a row.
+//!
+//! Rules with more than two states write dead cells as `.` and states 1 to 24
+//! as `A` to `X`. Higher states put one of `p` to `y` in front, each adding
+//! 24: `pA` is state 25 and `yO` state 255.
+//!
+//! A rule [`Rule`] cannot parse, such as the name of a rule table, does not
+//! stop the cells from being read; it ends up in [`Pattern::rule_name`].
+
+use super::ParsePatternError;
+use crate::{Pattern, Rule};
+
+// Golly and the LifeWiki keep RLE lines at most this long
+const MAX_LINE_LENGTH: usize = 70;
+
+// Coordinates and live cells a pattern may have, so a corrupt or hostile file
+// can't make the reader allocate without bound
+const MAX_EXTENT: i64 = 1 << 31;
+const MAX_CELLS: usize = 1 << 26;
+
+/// Parses an RLE pattern.
+pub fn read(text: &str) -> Result<Pattern, ParsePatternError> {
+    let mut name = None;
+    let mut author = None;
+    let mut comments = Vec::new();
+    let mut rule = None;
+    let mut rule_name = None;
+    let mut size = None;
+    let mut in_body = false;
+
+    let mut cells = Vec::new();
+    let (mut x, mut y) = (0i64, 0i64);
+    let mut run: Option<i64> = None;
+    let mut prefix: Option<u8> = None; // Of a state above 24
+
+    'lines: for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if !in_body {
+            if let Some(comment) = line.strip_prefix('#') {
+                let mut chars = comment.chars();
+                let tag = chars.next().unwrap_or(' ');
+                let content = chars.as_str().trim().to_string();
+                match tag {
+                    'N' => name = Some(content),
+                    'O' => author = Some(content),
+                    'r' => (rule, rule_name) = named_rule(&content)?,
+                    // #C, #c and anything we don't interpret are kept as comments
+                    _ => comments.push(content),
+                }
+                continue;
+            }
+
+            if line.starts_with('x') {
+                let (header_size, header_rule) = parse_header(line, line_number)?;
+                size = Some(header_size);
+                if let Some(header_rule) = header_rule {
+                    (rule, rule_name) = named_rule(header_rule)?;
+                }
+                in_body = true;
+                continue;
+            }
+        }
+
+        in_body = true;
+        // Live cells must stay inside the header's box, if there was one
+        let (max_x, max_y) = size.map_or((MAX_EXTENT, MAX_EXTENT), |(width, height)| {
+            (width.min(MAX_EXTENT as u64) as i64, height.min(MAX_EXTENT as u64) as i64)
+        });
+        let advance = |position: i64, count: i64| {
+            position
+                .checked_add(count)
+                .filter(|&end| end <= MAX_EXTENT)
+                .ok_or_else(|| ParsePatternError::malformed(line_number, "pattern is too large"))
+        };
+        for c in line.chars() {
+            if prefix.is_some() && !c.is_ascii_uppercase() {
+                return Err(ParsePatternError::malformed(line_number, format!("'{}' must be followed by a state letter", c)));
+            }
+            match c {
+                '0'..='9' => {
+                    let digit = i64::from(c as u8 - b'0');
+                    run = run
+                        .unwrap_or(0)
+                        .checked_mul(10)
+                        .and_then(|count| count.checked_add(digit));
+                    if run.is_none() {
+                        return Err(ParsePatternError::malformed(line_number, "run count is too large"));
+                    }
+                }
+                'b' | '.' => x = advance(x, run.take().unwrap_or(1))?,
+                'o' | 'A'..='X' => {
+                    let state = match c {
+                        'o' => 1,
+                        _ => letter_state(prefix.take(), c)
+                            .ok_or_else(|| ParsePatternError::malformed(line_number, "cell state is above 255"))?,
+                    };
+                    let count = run.take().unwrap_or(1);
+                    let end = advance(x, count)?;
+                    if end > max_x || y >= max_y {
+                        return Err(ParsePatternError::malformed(line_number, "cells lie outside the pattern's size"));
+                    }
+                    if cells.len() + count as usize > MAX_CELLS {
+                        return Err(ParsePatternError::malformed(line_number, "pattern has too many cells"));
+                    }
+                    cells.extend((x..end).map(|cx| (cx, y, state)));
+                    x = end;
+                }
+                'p'..='y' => prefix = Some(c as u8 - b'p' + 1),
+                '$' => {
+                    y = advance(y, run.take().unwrap_or(1))?;
+                    x = 0;
+                }
+                '!' => break 'lines,
+                c if c.is_whitespace() => {}
+                c => {
+                    return Err(ParsePatternError::malformed(
+                        line_number,
+                        format!("unexpected character '{}' in pattern data", c),
+                    ))
+                }
+            }
+        }
+    }
+
+    // Under two-state rules every state but dead is alive
+    if rule.as_ref().is_some_and(|rule| rule.states() == 2) {
+        for cell in &mut cells {
+            cell.2 = 1;
+        }
+    }
+
+    // Leading blank rows and columns are part of the pattern's box
+    let mut pattern = Pattern::from_placed_cells(cells);
+    if let Some((width, height)) = size {
+        pattern.set_size(width, height);
+    }
+    pattern.name = name;
+    pattern.author = author;
+    pattern.comments = comments;
+    pattern.rule = rule;
+    pattern.rule_name = rule_name;
+    Ok(pattern)
+}
+
+/// Writes `pattern` as RLE, including its name, author, comments and rule.
+pub fn write(pattern: &Pattern) -> String {
+    let mut out = String::new();
+    if let Some(name) = &pattern.name {
+        out.push_str(&format!("#N {}\n", name));
+    }
+    if let Some(author) = &pattern.author {
+        out.push_str(&format!("#O {}\n", author));
+    }
+    for comment in &pattern.comments {
+        out.push_str(&format!("#C {}\n", comment));
+    }
+
+    out.push_str(&format!("x = {}, y = {}", pattern.width(), pattern.height()));
+    if let Some(rule) = &pattern.rule {
+        out.push_str(&format!(", rule = {}", rule));
+    } else if let Some(name) = &pattern.rule_name {
+        out.push_str(&format!(", rule = {}", name));
+    }
+    out.push('\n');
+
+    // Rules with more than two states, or cells in them, call for letters
+    let multistate =
+        pattern.rule.as_ref().is_some_and(|rule| rule.states() > 2) || pattern.cells().iter().any(|&(_, _, state)| state > 1);
+    let tag = |state: u8| match (multistate, state) {
+        (false, 0) => "b".to_string(),
+        (false, _) => "o".to_string(),
+        (true, 0) => ".".to_string(),
+        (true, _) => state_letters(state),
+    };
+
+    let mut line = String::new();
+    let push = |count: i64, tag: &str, line: &mut String, out: &mut String| {
+        let token = if count == 1 {
+            tag.to_string()
+        } else {
+            format!("{}{}", count, tag)
+        };
+        if line.len() + token.len() > MAX_LINE_LENGTH {
+            out.push_str(line);
+            out.push('\n');
+            line.clear();
+        }
+        line.push_str(&token);
+    };
+
+    // Cells are sorted row by row, so runs can be emitted in a single pass
+    let (mut x, mut y) = (0i64, 0i64);
+    let mut cells = pattern.cells().iter().peekable();
+    while let Some(&(cx, cy, state)) = cells.next() {
+        if cy > y {
+            push(cy - y, "$", &mut line, &mut out);
+            y = cy;
+            x = 0;
+        }
+        if cx > x {
+            push(cx - x, &tag(0), &mut line, &mut out);
+        }
+
+        let mut end = cx + 1;
+        while cells.next_if(|&&(nx, ny, next)| ny == cy && nx == end && next == state).is_some() {
+            end += 1;
+        }
+        push(end - cx, &tag(state), &mut line, &mut out);
+        x = end;
+    }
+    push(1, "!", &mut line, &mut out);
+
+    out.push_str(&line);
+    out.push('\n');
+    out
+}
+
+// The state written as `letter`, after the prefix for states above 24 if any
+fn letter_state(prefix: Option<u8>, letter: char) -> Option<u8> {
+    let state = u32::from(prefix.unwrap_or(0)) * 24 + u32::from(letter) - u32::from('A') + 1;
+    u8::try_from(state).ok()
+}
+
+// The letters of a state from 1 up
+fn state_letters(state: u8) -> String {
+    let (prefix, letter) = ((state - 1) / 24, (state - 1) % 24);
+    let letter = char::from(b'A' + letter);
+    match prefix {
+        0 => letter.to_string(),
+        _ => format!("{}{}", char::from(b'p' + prefix - 1), letter),
+    }
+}
+
+// A rule as a file gives it: parsed, or else kept as a name. Text written in
+// rule notation, with a `/` or Larger than Life's `R<range>`, has to parse.
+fn named_rule(text: &str) -> Result<(Option<Rule>, Option<String>), ParsePatternError> {
+    let text = text.trim();
+    let mut chars = text.chars();
+    let notation = text.contains('/')
+        || (matches!(chars.next(), Some('R' | 'r')) && chars.next().is_some_and(|c| c.is_ascii_digit()));
+    match text.parse::<Rule>() {
+        Ok(rule) => Ok((Some(rule), None)),
+        Err(_) if text.is_empty() => Ok((None, None)),
+        Err(err) if notation => Err(err.into()),
+        Err(_) => Ok((None, Some(text.to_string()))),
+    }
+}
+
+// Parse `x = 3, y = 3, rule = B3/S23` into the size and optional rule text
+fn parse_header(line: &str, line_number: usize) -> Result<((u64, u64), Option<&str>), ParsePatternError> {
+    let mut width = None;
+    let mut height = None;
+    // The rule comes last and may contain commas itself, as Larger than Life
+    // rules do, so split it off first
+    let (fields, rule) = match line.split_once("rule") {
+        Some((fields, rest)) => (fields, rest.trim_start().strip_prefix('=')),
+        None => (line, None),
+    };
+    // Golly appends bounded grid settings such as ":T100,100"
+    let rule = rule.map(|value| value.split(':').next().unwrap_or(value));
+
+    for field in fields.split(',') {
+        let Some((key, value)) = field.split_once('=') else {
+            continue;
+        };
+        let value = value.trim();
+        let number = || {
+            value
+                .parse::<u64>()
+                .map_err(|_| ParsePatternError::malformed(line_number, format!("'{}' is not a valid size", value)))
+        };
+
+        match key.trim() {
+            "x" => width = Some(number()?),
+            "y" => height = Some(number()?),
+            _ => {}
+        }
+    }
+
+    match (width, height) {
+        (Some(width), Some(height)) => Ok(((width, height), rule)),
+        _ => Err(ParsePatternError::malformed(line_number, "header must give both x and y")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ParseRuleError;
+
+    #[test]
+    fn rules_in_rule_notation_have_to_parse() {
+        let error = |text: &str| read(text).unwrap_err();
+        assert_eq!(
+            error("x = 1, y = 1, rule = B9/S23\no!\n"),
+            ParsePatternError::Rule(ParseRuleError::InvalidCount('9'))
+        );
+        assert_eq!(
+            error("#r 23/3/1\nx = 1, y = 1\no!\n"),
+            ParsePatternError::Rule(ParseRuleError::InvalidStates("1".to_string()))
+        );
+        assert!(matches!(error("x = 1, y = 1, rule = R5,C0,NX\no!\n"), ParsePatternError::Rule(_)));
+
+        // Anything else names a rule table
+        let pattern = read("x = 1, y = 1, rule = WireWorld\no!\n").unwrap();
+        assert_eq!((pattern.rule, pattern.rule_name.as_deref()), (None, Some("WireWorld")));
+    }
+}
@@ -0,0 +1,665 @@
+//! Gosper's HashLife: a hash-consed quadtree with memoized evolution.
+//!
+//! Identical sub-patterns are stored once, and the future of every node is
+//! cached, so regular patterns such as glider guns and breeders can be
+//! advanced by billions of generations in a handful of steps.
+//!
+//! Every cell state has a leaf of its own, so besides Life-like rules the
+//! tree also runs Generations rules and rule tables, as long as each cell
+//! only looks at its eight nearest neighbours. Hexagonal rules qualify, since
+//! Golly's skewed hexagons have the Moore neighbours but NE and SW.
+
+use std::collections::HashMap;
+
+use crate::{Engine, Lattice, Rule, Stats, Topology, Universe};
+
+/// Default number of nodes kept before a garbage collection is triggered.
+pub const DEFAULT_MAX_NODES: usize = 2_000_000;
+
+/// Default number of memoized results kept before the cache is flushed.
+pub const DEFAULT_MAX_CACHED_RESULTS: usize = 4_000_000;
+
+// The root never grows beyond this level so coordinates fit in an i64
+pub(crate) const MAX_LEVEL: u8 = 62;
+
+pub(crate) type NodeId = u32;
+
+// The leaves come first, one per state, so a leaf's id is its state
+const LEAVES: usize = 256;
+pub(crate) const DEAD: NodeId = 0;
+pub(crate) const ALIVE: NodeId = 1;
+
+// The bits of a neighbourhood byte, NW first, that are hexagonal neighbours:
+// all but NE and SW
+const HEXAGONAL_MASK: u8 = 0b1101_1011;
+
+// Children are stored in the order north-west, north-east, south-west, south-east
+#[derive(Debug, Clone, Copy)]
+struct Node {
+    children: [NodeId; 4],
+    level: u8,
+    population: u64, // Live cells
+    occupied: u64,   // Cells in any state but dead
+}
+
+/// An unbounded plane of cells evolved with the HashLife algorithm.
+///
+/// The root node is centred on the origin and is expanded automatically as
+/// cells are set or the pattern grows. Births and deaths are not tracked, and
+/// rules containing `B0`, or tables that bring empty space to life, are
+/// evolved as if empty space stayed empty.
+#[derive(Debug, Clone)]
+pub struct HashLife {
+    nodes: Vec<Node>,
+    interned: HashMap<[NodeId; 4], NodeId>,
+    results: HashMap<(NodeId, u8), NodeId>,
+    empty: Vec<NodeId>,
+    root: NodeId,
+    rule: Rule,
+    generation: u64,
+    max_nodes: usize,
+    max_cached_results: usize,
+}
+
+impl HashLife {
+    /// Creates an empty plane evolving under `rule`.
+    pub fn new(rule: Rule) -> Self {
+        let leaf = |state: usize| Node {
+            children: [DEAD; 4],
+            level: 0,
+            population: u64::from(state == 1),
+            occupied: u64::from(state != 0),
+        };
+        let mut hashlife = Self {
+            nodes: (0..LEAVES).map(leaf).collect(),
+            interned: HashMap::new(),
+            results: HashMap::new(),
+            empty: vec![DEAD],
+            root: DEAD,
+            rule,
+            generation: 0,
+            max_nodes: DEFAULT_MAX_NODES,
+            max_cached_results: DEFAULT_MAX_CACHED_RESULTS,
+        };
+        hashlife.root = hashlife.empty_node(3);
+        hashlife
+    }
+
+    /// Whether HashLife can evolve `rule`: only rules where cells look at no
+    /// more than their eight nearest neighbours fit its 4x4 base case. The
+    /// hexagonal lattice qualifies, its neighbours being a subset of those.
+    pub fn supports(rule: &Rule) -> bool {
+        rule.table().is_some()
+            || (rule.range().is_none() && matches!(rule.lattice(), Lattice::Square | Lattice::Hexagonal))
+    }
+
+    /// Number of distinct quadtree nodes currently stored.
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Number of memoized node results currently cached.
+    pub fn cached_results(&self) -> usize {
+        self.results.len()
+    }
+
+    /// Sets how many nodes may exist before unreachable ones are collected.
+    pub fn set_max_nodes(&mut self, max_nodes: usize) {
+        self.max_nodes = max_nodes;
+    }
+
+    /// Sets how many memoized results may be kept before the cache is flushed.
+    pub fn set_max_cached_results(&mut self, max_cached_results: usize) {
+        self.max_cached_results = max_cached_results;
+    }
+
+    /// Advances by `2^exponent` generations in a single HashLife step.
+    /// Exponents are capped so the universe stays addressable with `i64`.
+    pub fn step_pow2(&mut self, exponent: u8) {
+        let exponent = exponent.min(MAX_LEVEL - 3);
+        self.enforce_limits();
+
+        // Pad the pattern so that it sits in the central quarter of a root big
+        // enough for the jump; nothing can then escape the returned centre.
+        while self.level() < MAX_LEVEL && (self.level() < exponent + 3 || !self.is_padded()) {
+            self.expand();
+        }
+
+        self.root = self.advance(self.root, exponent);
+        self.generation = self.generation.wrapping_add(1u64 << exponent);
+        self.enforce_limits();
+    }
+
+    /// Drops every node that is not part of the current pattern, along with
+    /// all memoized results.
+    pub fn collect_garbage(&mut self) {
+        let old_nodes = std::mem::take(&mut self.nodes);
+        self.nodes = old_nodes[..LEAVES].to_vec();
+        self.interned.clear();
+        self.results.clear();
+        self.empty = vec![DEAD];
+
+        let mut remap = HashMap::new();
+        self.root = self.copy_node(&old_nodes, self.root, &mut remap);
+    }
+
+    /// Calls `f` for every live cell inside the rectangle starting at
+    /// `(x, y)` with the given size.
+    pub fn for_each_live(&self, x: i64, y: i64, width: u64, height: u64, f: &mut dyn FnMut(i64, i64)) {
+        self.for_each_cell(x, y, width, height, &mut |x, y, state| {
+            if state == 1 {
+                f(x, y);
+            }
+        });
+    }
+
+    /// Like [`HashLife::for_each_live`], but calls `f` with the state of
+    /// every cell that is not dead.
+    pub fn for_each_cell(&self, x: i64, y: i64, width: u64, height: u64, f: &mut dyn FnMut(i64, i64, u8)) {
+        let half = self.half_size();
+        let area = (x, y, x.saturating_add(width as i64), y.saturating_add(height as i64));
+        self.visit(self.root, -half, -half, area, 0, &mut |x, y, leaf| f(x, y, leaf as u8));
+    }
+
+    /// Calls `f` for every block of `2^level` x `2^level` cells, aligned to
+    /// its size, that overlaps the rectangle and has cells that are not dead.
+    /// `f` gets the block's top-left corner, its live cells and its cells in
+    /// any state but dead, so a zoomed-out view can be drawn without
+    /// visiting each cell.
+    pub fn for_each_block(&self, x: i64, y: i64, width: u64, height: u64, level: u8, f: &mut dyn FnMut(i64, i64, u64, u64)) {
+        let half = self.half_size();
+        let area = (x, y, x.saturating_add(width as i64), y.saturating_add(height as i64));
+        self.visit(self.root, -half, -half, area, level, &mut |x, y, node| {
+            f(x, y, self.population(node), self.occupied(node))
+        });
+    }
+
+    /// The smallest rectangle holding every cell that is not dead, as
+    /// `(left, top, right, bottom)` with inclusive ends.
+    pub fn bounds(&self) -> Option<(i64, i64, i64, i64)> {
+        let half = self.half_size();
+        let mut bounds = None;
+        self.grow_bounds(self.root, -half, -half, &mut bounds);
+        bounds
+    }
+
+    /// Copies the cells back into `universe`, together with the generation
+    /// count. Bounded universes only receive the cells inside them.
+    pub fn copy_to(&self, universe: &mut Universe) {
+        let mut cells = Vec::new();
+        let mut collect = |x, y, state| cells.push((x, y, state));
+        match universe.topology() {
+            Topology::Unbounded => self.for_each_cell_anywhere(&mut collect),
+            _ => self.for_each_cell(0, 0, universe.width() as u64, universe.height() as u64, &mut collect),
+        }
+        universe.replace_cells(cells, self.generation);
+    }
+
+    // Calls `f` for every cell on the plane that is not dead
+    pub(crate) fn for_each_cell_anywhere(&self, f: &mut dyn FnMut(i64, i64, u8)) {
+        let half = self.half_size();
+        self.for_each_cell(-half, -half, 1 << self.level(), 1 << self.level(), f);
+    }
+
+    pub(crate) fn root(&self) -> NodeId {
+        self.root
+    }
+
+    // Install a quadtree built with `join`, centred on the origin like every root
+    pub(crate) fn set_root(&mut self, root: NodeId, generation: u64) {
+        self.root = root;
+        self.generation = generation;
+        while self.level() < 3 {
+            self.expand();
+        }
+    }
+
+    pub(crate) fn node_level(&self, node: NodeId) -> u8 {
+        self.nodes[node as usize].level
+    }
+
+    fn level(&self) -> u8 {
+        self.nodes[self.root as usize].level
+    }
+
+    fn half_size(&self) -> i64 {
+        1 << (self.level() - 1)
+    }
+
+    pub(crate) fn population(&self, node: NodeId) -> u64 {
+        self.nodes[node as usize].population
+    }
+
+    pub(crate) fn occupied(&self, node: NodeId) -> u64 {
+        self.nodes[node as usize].occupied
+    }
+
+    pub(crate) fn children(&self, node: NodeId) -> [NodeId; 4] {
+        self.nodes[node as usize].children
+    }
+
+    // Hash-cons a node from its four children
+    pub(crate) fn join(&mut self, children: [NodeId; 4]) -> NodeId {
+        if let Some(&id) = self.interned.get(&children) {
+            return id;
+        }
+
+        let level = self.nodes[children[0] as usize].level + 1;
+        let sum = |count: &dyn Fn(NodeId) -> u64| children.iter().fold(0u64, |sum, &child| sum.saturating_add(count(child)));
+        let population = sum(&|child| self.population(child));
+        let occupied = sum(&|child| self.occupied(child));
+        let id = self.nodes.len() as NodeId;
+        self.nodes.push(Node {
+            children,
+            level,
+            population,
+            occupied,
+        });
+        self.interned.insert(children, id);
+        id
+    }
+
+    pub(crate) fn empty_node(&mut self, level: u8) -> NodeId {
+        while self.empty.len() <= level as usize {
+            let below = *self.empty.last().unwrap();
+            let node = self.join([below; 4]);
+            self.empty.push(node);
+        }
+        self.empty[level as usize]
+    }
+
+    // The node one level down made of the four innermost grandchildren
+    fn centre(&mut self, node: NodeId) -> NodeId {
+        let [nw, ne, sw, se] = self.children(node);
+        self.join([self.children(nw)[3], self.children(ne)[2], self.children(sw)[1], self.children(se)[0]])
+    }
+
+    fn is_padded(&mut self) -> bool {
+        let centre = self.centre(self.root);
+        let inner = self.centre(centre);
+        self.occupied(inner) == self.occupied(self.root)
+    }
+
+    // Double the root's size, keeping the pattern centred on the origin
+    fn expand(&mut self) {
+        let level = self.level();
+        let empty = self.empty_node(level - 1);
+        let [nw, ne, sw, se] = self.children(self.root);
+        let children = [
+            self.join([empty, empty, empty, nw]),
+            self.join([empty, empty, ne, empty]),
+            self.join([empty, sw, empty, empty]),
+            self.join([se, empty, empty, empty]),
+        ];
+        self.root = self.join(children);
+    }
+
+    // Returns the centre of `node` (one level down) advanced by 2^exponent
+    // generations, where exponent <= level - 2.
+    fn advance(&mut self, node: NodeId, exponent: u8) -> NodeId {
+        let level = self.nodes[node as usize].level;
+        if self.occupied(node) == 0 {
+            return self.empty_node(level - 1);
+        }
+        if let Some(&result) = self.results.get(&(node, exponent)) {
+            return result;
+        }
+
+        let result = if level == 2 {
+            self.base_case(node)
+        } else {
+            let [nw, ne, sw, se] = self.children(node);
+            let [_, nw_ne, nw_sw, nw_se] = self.children(nw);
+            let [ne_nw, _, ne_sw, ne_se] = self.children(ne);
+            let [sw_nw, sw_ne, _, sw_se] = self.children(sw);
+            let [se_nw, se_ne, se_sw, _] = self.children(se);
+
+            // Nine overlapping sub-squares one level down
+            let squares = [
+                nw,
+                self.join([nw_ne, ne_nw, nw_se, ne_sw]),
+                ne,
+                self.join([nw_sw, nw_se, sw_nw, sw_ne]),
+                self.join([nw_se, ne_sw, sw_ne, se_nw]),
+                self.join([ne_sw, ne_se, se_nw, se_ne]),
+                sw,
+                self.join([sw_ne, se_nw, sw_se, se_sw]),
+                se,
+            ];
+
+            // At full speed both halves of the jump happen here; for smaller
+            // jumps the first half is skipped by taking plain centres.
+            let full_speed = exponent == level - 2;
+            let mut inner = [DEAD; 9];
+            for (slot, &square) in inner.iter_mut().zip(&squares) {
+                *slot = if full_speed {
+                    self.advance(square, level - 3)
+                } else {
+                    self.centre(square)
+                };
+            }
+
+            let next = exponent.min(level - 3);
+            let quadrants = [
+                self.join([inner[0], inner[1], inner[3], inner[4]]),
+                self.join([inner[1], inner[2], inner[4], inner[5]]),
+                self.join([inner[3], inner[4], inner[6], inner[7]]),
+                self.join([inner[4], inner[5], inner[7], inner[8]]),
+            ];
+            let mut result = [DEAD; 4];
+            for (slot, &quadrant) in result.iter_mut().zip(&quadrants) {
+                *slot = self.advance(quadrant, next);
+            }
+            self.join(result)
+        };
+
+        self.results.insert((node, exponent), result);
+        result
+    }
+
+    // Evolve the centre 2x2 of a 4x4 node by one generation
+    fn base_case(&mut self, node: NodeId) -> NodeId {
+        // Leaf ids are the cell states
+        let mut cells = [[0u8; 4]; 4];
+        for (quadrant, &child) in self.children(node).iter().enumerate() {
+            for (index, &leaf) in self.children(child).iter().enumerate() {
+                let x = (quadrant % 2) * 2 + index % 2;
+                let y = (quadrant / 2) * 2 + index / 2;
+                cells[y][x] = leaf as u8;
+            }
+        }
+
+        let mut next = [DEAD; 4];
+        for (index, slot) in next.iter_mut().enumerate() {
+            let (x, y) = (1 + index % 2, 1 + index / 2);
+            let state = match self.rule.table() {
+                Some(table) => table.next(x as i64, y as i64, |x, y| cells[y as usize][x as usize]),
+                None => {
+                    // The neighbourhood byte takes NW as its high bit, as in `Rule`
+                    let neighbourhood = cells[y - 1..=y + 1]
+                        .iter()
+                        .flat_map(|row| &row[x - 1..=x + 1])
+                        .enumerate()
+                        .filter(|&(i, _)| i != 4)
+                        .fold(0, |acc, (_, &cell)| (acc << 1) | u8::from(cell == 1));
+                    match self.rule.lattice() {
+                        Lattice::Hexagonal => self.rule.next_by_count(cells[y][x], (neighbourhood & HEXAGONAL_MASK).count_ones()),
+                        _ => self.rule.next(cells[y][x], neighbourhood),
+                    }
+                }
+            };
+            *slot = NodeId::from(state);
+        }
+        self.join(next)
+    }
+
+    fn enforce_limits(&mut self) {
+        if self.results.len() > self.max_cached_results {
+            self.results.clear();
+        }
+        if self.nodes.len() > self.max_nodes {
+            self.collect_garbage();
+        }
+    }
+
+    fn copy_node(&mut self, old_nodes: &[Node], node: NodeId, remap: &mut HashMap<NodeId, NodeId>) -> NodeId {
+        if (node as usize) < LEAVES {
+            return node;
+        }
+        if let Some(&id) = remap.get(&node) {
+            return id;
+        }
+
+        let mut children = old_nodes[node as usize].children;
+        for child in &mut children {
+            *child = self.copy_node(old_nodes, *child, remap);
+        }
+        let id = self.join(children);
+        remap.insert(node, id);
+        id
+    }
+
+    // Call `f` with each node of `block_level` that overlaps `area` and has cells
+    // that are not dead; a root below that level counts as one such node
+    fn visit(&self, node: NodeId, x: i64, y: i64, area: (i64, i64, i64, i64), block_level: u8, f: &mut dyn FnMut(i64, i64, NodeId)) {
+        let Node { children, level, occupied, .. } = self.nodes[node as usize];
+        let size = 1i64 << level;
+        let (left, top, right, bottom) = area;
+        if occupied == 0 || x >= right || y >= bottom || x + size <= left || y + size <= top {
+            return;
+        }
+
+        if level <= block_level {
+            f(x, y, node);
+            return;
+        }
+
+        let half = size / 2;
+        for (index, &child) in children.iter().enumerate() {
+            let dx = (index % 2) as i64 * half;
+            let dy = (index / 2) as i64 * half;
+            self.visit(child, x + dx, y + dy, area, block_level, f);
+        }
+    }
+
+    // Widen `bounds` to take in the cells of `node`, skipping nodes that lie
+    // within them already
+    fn grow_bounds(&self, node: NodeId, x: i64, y: i64, bounds: &mut Option<(i64, i64, i64, i64)>) {
+        let Node { children, level, occupied, .. } = self.nodes[node as usize];
+        let last = (1i64 << level) - 1;
+        let inside = bounds.is_some_and(|(left, top, right, bottom)| x >= left && y >= top && x + last <= right && y + last <= bottom);
+        if occupied == 0 || inside {
+            return;
+        }
+
+        if level == 0 {
+            *bounds = Some(bounds.map_or((x, y, x, y), |(left, top, right, bottom)| {
+                (left.min(x), top.min(y), right.max(x), bottom.max(y))
+            }));
+            return;
+        }
+
+        let half = 1i64 << (level - 1);
+        for (index, &child) in children.iter().enumerate() {
+            self.grow_bounds(child, x + (index % 2) as i64 * half, y + (index / 2) as i64 * half, bounds);
+        }
+    }
+
+    // Set a cell whether or not the rule has its state, growing the root to reach it
+    pub(crate) fn put_state(&mut self, x: i64, y: i64, state: u8) {
+        loop {
+            let half = self.half_size();
+            if (-half..half).contains(&x) && (-half..half).contains(&y) {
+                self.root = self.set_in(self.root, x + half, y + half, state);
+                return;
+            }
+            if self.level() >= MAX_LEVEL || state == 0 {
+                return;
+            }
+            self.expand();
+        }
+    }
+
+    fn set_in(&mut self, node: NodeId, x: i64, y: i64, state: u8) -> NodeId {
+        let level = self.nodes[node as usize].level;
+        if level == 0 {
+            return NodeId::from(state);
+        }
+
+        let half = 1i64 << (level - 1);
+        let index = usize::from(x >= half) + 2 * usize::from(y >= half);
+        let mut children = self.children(node);
+        children[index] = self.set_in(children[index], x % half, y % half, state);
+        self.join(children)
+    }
+}
+
+impl Default for HashLife {
+    fn default() -> Self {
+        Self::new(Rule::default())
+    }
+}
+
+impl From<&Universe> for HashLife {
+    fn from(universe: &Universe) -> Self {
+        let mut hashlife = Self::new(universe.rule());
+        for (x, y, state) in universe.cells() {
+            hashlife.set_state(x, y, state);
+        }
+        hashlife.generation = universe.stats().generation;
+        hashlife
+    }
+}
+
+impl Engine for HashLife {
+    fn step(&mut self) {
+        self.step_pow2(0);
+    }
+
+    /// Advances by `generations`, one HashLife jump per set bit.
+    fn step_by(&mut self, generations: u64) {
+        for exponent in 0..64 {
+            if generations & (1 << exponent) != 0 {
+                self.step_pow2(exponent);
+            }
+        }
+    }
+
+    fn get_cell(&self, x: i64, y: i64) -> bool {
+        self.get_state(x, y) == 1
+    }
+
+    fn set_cell(&mut self, x: i64, y: i64, alive: bool) {
+        self.set_state(x, y, u8::from(alive));
+    }
+
+    fn get_state(&self, x: i64, y: i64) -> u8 {
+        let half = self.half_size();
+        if x < -half || x >= half || y < -half || y >= half {
+            return 0;
+        }
+
+        let (mut x, mut y) = (x + half, y + half);
+        let mut node = self.root;
+        loop {
+            let Node { children, level, .. } = self.nodes[node as usize];
+            if level == 0 {
+                return node as u8;
+            }
+            let half = 1i64 << (level - 1);
+            node = children[usize::from(x >= half) + 2 * usize::from(y >= half)];
+            x %= half;
+            y %= half;
+        }
+    }
+
+    fn set_state(&mut self, x: i64, y: i64, state: u8) {
+        if state < self.rule.states() {
+            self.put_state(x, y, state);
+        }
+    }
+
+    fn stats(&self) -> Stats {
+        Stats {
+            generation: self.generation,
+            population: self.population(self.root),
+            ..Stats::default()
+        }
+    }
+
+    fn rule(&self) -> Rule {
+        self.rule.clone()
+    }
+
+    fn set_rule(&mut self, rule: Rule) {
+        if rule != self.rule {
+            self.rule = rule;
+            self.results.clear();
+        }
+    }
+
+    fn clear(&mut self) {
+        *self = Self {
+            max_nodes: self.max_nodes,
+            max_cached_results: self.max_cached_results,
+            ..Self::new(self.rule.clone())
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A 32x32 soup, a third of it alive, on the unbounded plane
+    fn soup(rule: &str) -> Universe {
+        let mut universe = Universe::new(32, 32);
+        universe.set_topology(Topology::Unbounded);
+        universe.set_rule(rule.parse().unwrap());
+        let mut seed = 0x2545_f491_4f6c_dd1du64;
+        for y in 0..32 {
+            for x in 0..32 {
+                seed ^= seed << 13;
+                seed ^= seed >> 7;
+                seed ^= seed << 17;
+                if seed.is_multiple_of(3) {
+                    universe.set_state(x, y, 1);
+                }
+            }
+        }
+        universe
+    }
+
+    fn cells(hashlife: &HashLife) -> Vec<(i64, i64, u8)> {
+        let mut cells = Vec::new();
+        hashlife.for_each_cell_anywhere(&mut |x, y, state| cells.push((x, y, state)));
+        cells.sort_unstable();
+        cells
+    }
+
+    #[test]
+    fn jumps_land_where_the_grid_steps_to() {
+        for rule in ["B3/S23", "B36/S23", "B2-a/S12", "345/2/4", "B2/S34H"] {
+            let mut universe = soup(rule);
+            let start = HashLife::from(&universe);
+            for _ in 0..64 {
+                universe.step();
+            }
+            let mut expected = universe.cells();
+            expected.sort_unstable();
+
+            let mut single = start.clone();
+            for _ in 0..64 {
+                single.step();
+            }
+            let mut jump = start.clone();
+            jump.step_by(64);
+            let mut mixed = start;
+            mixed.step_by(37);
+            mixed.step_by(27);
+
+            for (name, hashlife) in [("single steps", single), ("one jump", jump), ("mixed jumps", mixed)] {
+                assert_eq!(cells(&hashlife), expected, "{} under {}", name, rule);
+                assert_eq!(hashlife.stats().generation, 64);
+                assert_eq!(hashlife.stats().population, universe.stats().population, "{} under {}", name, rule);
+            }
+
+            // Bounds and blocks are found without visiting every cell
+            let mut jump = HashLife::from(&soup(rule));
+            jump.step_by(64);
+            let bounds = expected.iter().fold(None, |bounds, &(x, y, _)| {
+                Some(bounds.map_or((x, y, x, y), |(left, top, right, bottom): (i64, i64, i64, i64)| {
+                    (left.min(x), top.min(y), right.max(x), bottom.max(y))
+                }))
+            });
+            assert_eq!(jump.bounds(), bounds, "{}", rule);
+            let (left, top, right, bottom) = bounds.unwrap();
+            let mut occupied = 0;
+            jump.for_each_block(left, top, (right - left + 1) as u64, (bottom - top + 1) as u64, 3, &mut |x, y, _, cells| {
+                assert!(x % 8 == 0 && y % 8 == 0);
+                occupied += cells;
+            });
+            assert_eq!(occupied, expected.len() as u64, "{}", rule);
+        }
+    }
+}
@@ -0,0 +1,242 @@
+//! Undo and redo for a [`Universe`].
+//!
+//! Edits are stored as the cells they changed along with their states from
+//! the other side, so a small change to a large universe costs little. Anything that changes the universe wholesale,
+//! such as a run of generations, a resize or a new rule, is stored as a
+//! checkpoint: a full copy of the universe from before the change.
+
+use std::collections::VecDeque;
+
+use crate::{Stats, Universe};
+
+/// Memory the history may use unless told otherwise, in bytes.
+pub const DEFAULT_MEMORY_LIMIT: usize = 256 * 1024 * 1024;
+
+// One step of history. Applying it moves the universe to the other side of
+// the change and leaves behind the change that moves it back again.
+#[derive(Debug, Clone)]
+enum Change {
+    // Cells that changed with their states on the other side, and the
+    // counters from there
+    Edit { cells: Vec<(i64, i64, u8)>, stats: Stats },
+    // The whole universe from the other side
+    Checkpoint(Box<Universe>),
+}
+
+impl Change {
+    fn apply(&mut self, universe: &mut Universe) {
+        match self {
+            Change::Edit { cells, stats } => {
+                for (x, y, state) in cells.iter_mut() {
+                    *state = universe.swap_state(*x, *y, *state);
+                }
+                std::mem::swap(stats, universe.stats_mut());
+            }
+            Change::Checkpoint(saved) => {
+                // Auto-grow is a preference rather than part of the state
+                let auto_grow = universe.auto_grow();
+                std::mem::swap(&mut **saved, universe);
+                saved.set_auto_grow(universe.auto_grow());
+                universe.set_auto_grow(auto_grow);
+            }
+        }
+    }
+
+    fn memory_size(&self) -> usize {
+        std::mem::size_of::<Change>()
+            + match self {
+                Change::Edit { cells, .. } => cells.len() * std::mem::size_of::<(i64, i64, u8)>(),
+                Change::Checkpoint(saved) => std::mem::size_of::<Universe>() + saved.memory_size(),
+            }
+    }
+}
+
+/// Undo and redo stacks for a universe, holding on to at most a given amount
+/// of memory. The oldest steps are forgotten first.
+#[derive(Debug, Clone)]
+pub struct History {
+    undo: VecDeque<Change>,
+    redo: Vec<Change>,
+    pending: Option<Box<Universe>>, // The universe before the edit in progress
+    memory_limit: usize,
+    memory_used: usize,
+}
+
+impl History {
+    pub fn new(memory_limit: usize) -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            pending: None,
+            memory_limit,
+            memory_used: 0,
+        }
+    }
+
+    pub fn memory_limit(&self) -> usize {
+        self.memory_limit
+    }
+
+    /// Changes the memory limit, forgetting old steps that no longer fit.
+    pub fn set_memory_limit(&mut self, memory_limit: usize) {
+        self.memory_limit = memory_limit;
+        self.enforce_limit();
+    }
+
+    /// Approximate memory held by the recorded steps, in bytes.
+    pub fn memory_used(&self) -> usize {
+        self.memory_used
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Notes the state of `universe` before it is edited. Further calls are
+    /// ignored until [`History::end_edit`], so one edit can span many
+    /// changes, such as every cell of a pencil stroke.
+    pub fn begin_edit(&mut self, universe: &Universe) {
+        if self.pending.is_none() {
+            self.pending = Some(Box::new(universe.clone()));
+        }
+    }
+
+    /// Records everything that changed since [`History::begin_edit`] as one
+    /// step. Returns false, recording nothing, if no edit was begun or
+    /// nothing changed.
+    pub fn end_edit(&mut self, universe: &Universe) -> bool {
+        let Some(before) = self.pending.take() else {
+            return false;
+        };
+
+        if !before.same_shape(universe) {
+            self.push(Change::Checkpoint(before));
+            return true;
+        }
+
+        let cells = changed_cells(&before, universe);
+        if cells.is_empty() && before.stats() == universe.stats() {
+            return false;
+        }
+        self.push(Change::Edit {
+            cells,
+            stats: before.stats(),
+        });
+        true
+    }
+
+    /// Records the universe as it is now, e.g. before running it.
+    pub fn checkpoint(&mut self, universe: &Universe) {
+        self.end_edit(universe);
+        self.push(Change::Checkpoint(Box::new(universe.clone())));
+    }
+
+    /// Reverts the last step. Returns false if there was none.
+    pub fn undo(&mut self, universe: &mut Universe) -> bool {
+        self.end_edit(universe);
+        let Some(change) = self.undo.pop_back() else {
+            return false;
+        };
+        let change = self.apply(change, universe);
+        self.redo.push(change);
+        true
+    }
+
+    /// Repeats the last undone step. Returns false if there was none.
+    pub fn redo(&mut self, universe: &mut Universe) -> bool {
+        self.end_edit(universe);
+        let Some(change) = self.redo.pop() else {
+            return false;
+        };
+        let change = self.apply(change, universe);
+        self.undo.push_back(change);
+        true
+    }
+
+    /// Forgets every step.
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.pending = None;
+        self.memory_used = 0;
+    }
+
+    // Apply a change, keeping track of its size, which differs on the other side
+    fn apply(&mut self, mut change: Change, universe: &mut Universe) -> Change {
+        self.memory_used -= change.memory_size();
+        change.apply(universe);
+        self.memory_used += change.memory_size();
+        self.enforce_limit();
+        change
+    }
+
+    // Record a new step, which makes the undone ones unreachable
+    fn push(&mut self, change: Change) {
+        for change in self.redo.drain(..) {
+            self.memory_used -= change.memory_size();
+        }
+        self.memory_used += change.memory_size();
+        self.undo.push_back(change);
+        self.enforce_limit();
+    }
+
+    // Forget the oldest undo steps, then the furthest redo steps, until the
+    // history fits in its memory limit
+    fn enforce_limit(&mut self) {
+        while self.memory_used > self.memory_limit {
+            let Some(change) = self.undo.pop_front().or_else(|| (!self.redo.is_empty()).then(|| self.redo.remove(0))) else {
+                break;
+            };
+            self.memory_used -= change.memory_size();
+        }
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new(DEFAULT_MEMORY_LIMIT)
+    }
+}
+
+// Cells whose state differs between the two universes, with their states in
+// `before`
+fn changed_cells(before: &Universe, after: &Universe) -> Vec<(i64, i64, u8)> {
+    let cells = |universe: &Universe| {
+        let mut cells: Vec<_> = universe.cells().into_iter().map(|(x, y, state)| ((y, x), state)).collect();
+        cells.sort_unstable();
+        cells
+    };
+    let (before, after) = (cells(before), cells(after));
+
+    // Merge the sorted lists, keeping cells found in only one of them or in
+    // different states
+    let mut changed = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < before.len() && j < after.len() {
+        let ((position, state), (other, other_state)) = (before[i], after[j]);
+        match position.cmp(&other) {
+            std::cmp::Ordering::Less => {
+                changed.push((position, state));
+                i += 1;
+            }
+            std::cmp::Ordering::Greater => {
+                changed.push((other, 0));
+                j += 1;
+            }
+            std::cmp::Ordering::Equal => {
+                if state != other_state {
+                    changed.push((position, state));
+                }
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    changed.extend_from_slice(&before[i..]);
+    changed.extend(after[j..].iter().map(|&(position, _)| (position, 0)));
+    changed.into_iter().map(|((y, x), state)| (x, y, state)).collect()
+}
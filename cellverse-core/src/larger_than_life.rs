@@ -0,0 +1,225 @@
+//! Larger than Life: outer-totalistic rules over an extended neighbourhood.
+//!
+//! A cell looks at every cell within `radius` of it, in a square, a diamond
+//! or a disc, and is born or survives when the number of live cells there
+//! falls inside a range. Counts for a whole window are worked out at once:
+//! squares from a summed-area table, the other shapes from per-row prefix
+//! sums, so the cost per cell does not grow with the area of the
+//! neighbourhood.
+
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use crate::ParseRuleError;
+
+/// Largest neighbourhood radius a rule may use.
+pub const MAX_RADIUS: u16 = 500;
+
+/// The cells a Larger than Life rule counts around each cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum NeighbourhoodShape {
+    /// The square of cells within `radius` in both directions (`NM`).
+    #[default]
+    Moore,
+    /// The diamond of cells within `radius` steps along the axes (`NN`).
+    VonNeumann,
+    /// The disc of cells within `radius + 1/2` of the cell (`NC`).
+    Circular,
+}
+
+impl NeighbourhoodShape {
+    fn letter(self) -> char {
+        match self {
+            NeighbourhoodShape::Moore => 'M',
+            NeighbourhoodShape::VonNeumann => 'N',
+            NeighbourhoodShape::Circular => 'C',
+        }
+    }
+
+    // How far the neighbourhood reaches left and right on the row `dy` away
+    fn half_width(self, radius: u16, dy: i64) -> i64 {
+        let (radius, dy) = (i64::from(radius), dy.abs());
+        match self {
+            NeighbourhoodShape::Moore => radius,
+            NeighbourhoodShape::VonNeumann => radius - dy,
+            // dx² + dy² <= (r + 1/2)², i.e. dx² + dy² <= r² + r
+            NeighbourhoodShape::Circular => (radius * radius + radius - dy * dy).isqrt(),
+        }
+    }
+}
+
+/// The neighbourhood and ranges of a Larger than Life rule, written as in
+/// Golly: `R5,C0,M1,S34..58,B34..45,NM`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LargerThanLife {
+    /// How far the neighbourhood reaches, from 1 to [`MAX_RADIUS`].
+    pub radius: u16,
+    pub shape: NeighbourhoodShape,
+    /// Whether a cell counts itself along with its neighbours.
+    pub middle: bool,
+    /// Counts that let a live cell survive, inclusive.
+    pub survival: (u32, u32),
+    /// Counts that cause a birth, inclusive.
+    pub birth: (u32, u32),
+}
+
+impl LargerThanLife {
+    /// Whether a dead cell with `count` live cells in its neighbourhood comes alive.
+    pub fn is_born(&self, count: u32) -> bool {
+        (self.birth.0..=self.birth.1).contains(&count)
+    }
+
+    /// Whether a live cell with `count` live cells in its neighbourhood stays alive.
+    pub fn survives(&self, count: u32) -> bool {
+        (self.survival.0..=self.survival.1).contains(&count)
+    }
+
+    /// Counts the live cells in the neighbourhood of every cell of a window.
+    ///
+    /// `alive` holds `width` x `height` cells row by row, 1 for alive and 0
+    /// otherwise. The counts are for the cells at least `radius` from every
+    /// edge, again row by row, so there are `width - 2 * radius` of them per
+    /// row: the caller pads the window with whatever lies beyond its edges.
+    pub(crate) fn counts(&self, alive: &[u8], width: usize, height: usize) -> Vec<u32> {
+        let radius = usize::from(self.radius);
+        let (inner_width, inner_height) = (width - 2 * radius, height - 2 * radius);
+        let mut counts = Vec::with_capacity(inner_width * inner_height);
+
+        if self.shape == NeighbourhoodShape::Moore {
+            // sums[y][x] is the number of live cells above and left of (x, y)
+            let stride = width + 1;
+            let mut sums = vec![0u32; stride * (height + 1)];
+            for y in 0..height {
+                let mut row = 0;
+                for x in 0..width {
+                    row += u32::from(alive[y * width + x]);
+                    sums[(y + 1) * stride + x + 1] = sums[y * stride + x + 1] + row;
+                }
+            }
+            let side = 2 * radius + 1;
+            for y in 0..inner_height {
+                for x in 0..inner_width {
+                    let (top, bottom) = (y * stride, (y + side) * stride);
+                    counts.push(sums[bottom + x + side] + sums[top + x] - sums[top + x + side] - sums[bottom + x]);
+                }
+            }
+        } else {
+            // sums[y][x] is the number of live cells left of (x, y) on its row
+            let stride = width + 1;
+            let mut sums = vec![0u32; stride * height];
+            for y in 0..height {
+                for x in 0..width {
+                    sums[y * stride + x + 1] = sums[y * stride + x] + u32::from(alive[y * width + x]);
+                }
+            }
+            let spans: Vec<(usize, usize)> = (-(radius as i64)..=radius as i64)
+                .map(|dy| {
+                    let half = self.shape.half_width(self.radius, dy) as usize;
+                    ((dy + radius as i64) as usize, half)
+                })
+                .collect();
+            for y in 0..inner_height {
+                for x in 0..inner_width {
+                    let centre = x + radius;
+                    let count = spans.iter().fold(0, |count, &(row, half)| {
+                        let row = (y + row) * stride;
+                        count + sums[row + centre + half + 1] - sums[row + centre - half]
+                    });
+                    counts.push(count);
+                }
+            }
+        }
+
+        if !self.middle {
+            for y in 0..inner_height {
+                for x in 0..inner_width {
+                    counts[y * inner_width + x] -= u32::from(alive[(y + radius) * width + x + radius]);
+                }
+            }
+        }
+        counts
+    }
+
+    // Parse the Golly form, returning the rule and its number of states
+    pub(crate) fn parse(rule: &str) -> Result<(Self, u8), ParseRuleError> {
+        let mut radius = None;
+        let mut states = 2;
+        let mut middle = false;
+        let mut survival = None;
+        let mut birth = None;
+        let mut shape = NeighbourhoodShape::default();
+
+        for term in rule.split(',').map(str::trim) {
+            let invalid = || ParseRuleError::InvalidTerm(term.to_string());
+            let mut chars = term.chars();
+            let tag = chars.next().ok_or_else(invalid)?.to_ascii_uppercase();
+            let value = chars.as_str();
+            match tag {
+                'R' => {
+                    radius = match value.parse::<u16>() {
+                        Ok(radius) if (1..=MAX_RADIUS).contains(&radius) => Some(radius),
+                        _ => return Err(ParseRuleError::InvalidRadius(value.to_string())),
+                    }
+                }
+                // C0 and C1 both mean two states
+                'C' => {
+                    states = match value.parse::<u8>() {
+                        Ok(states) => states.max(2),
+                        Err(_) => return Err(ParseRuleError::InvalidStates(term.to_string())),
+                    }
+                }
+                'M' => {
+                    middle = match value {
+                        "0" => false,
+                        "1" => true,
+                        _ => return Err(invalid()),
+                    }
+                }
+                'S' => survival = Some(parse_range(value).ok_or_else(invalid)?),
+                'B' => birth = Some(parse_range(value).ok_or_else(invalid)?),
+                'N' => {
+                    shape = match value.to_ascii_uppercase().as_str() {
+                        "M" => NeighbourhoodShape::Moore,
+                        "N" => NeighbourhoodShape::VonNeumann,
+                        "C" => NeighbourhoodShape::Circular,
+                        _ => return Err(invalid()),
+                    }
+                }
+                _ => return Err(invalid()),
+            }
+        }
+
+        let malformed = || ParseRuleError::Malformed(rule.to_string());
+        let range = Self {
+            radius: radius.ok_or_else(malformed)?,
+            shape,
+            middle,
+            survival: survival.ok_or_else(malformed)?,
+            birth: birth.ok_or_else(malformed)?,
+        };
+        Ok((range, states))
+    }
+
+    // Write the Golly form for a rule with `states` states
+    pub(crate) fn fmt(&self, states: u8, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "R{},C{},M{},S{}..{},B{}..{},N{}",
+            self.radius,
+            if states == 2 { 0 } else { states },
+            u8::from(self.middle),
+            self.survival.0,
+            self.survival.1,
+            self.birth.0,
+            self.birth.1,
+            self.shape.letter()
+        )
+    }
+}
+
+// A count range `min..max`, or a single count
+fn parse_range(range: &str) -> Option<(u32, u32)> {
+    let (min, max) = range.split_once("..").unwrap_or((range, range));
+    let range: RangeInclusive<u32> = min.parse().ok()?..=max.parse().ok()?;
+    (!range.is_empty()).then(|| range.into_inner())
+}
@@ -0,0 +1,165 @@
+//! The shape of the cells: squares, hexagons or triangles.
+//!
+//! Cells keep the square lattice's coordinates on every lattice; only their
+//! shape on screen changes. Hexagons use Golly's skewed layout: the
+//! neighbours of `(x, y)` are its Moore neighbours but NE and SW, and each
+//! row is drawn half a cell left of the one above it. Triangles point up
+//! where `x + y` is even and down elsewhere, each one twice as wide as a
+//! square cell and overlapping its neighbours' squares by half.
+//!
+//! Triangular neighbourhoods depend on the parity of the coordinates, so
+//! edges that wrap keep their shape only across even sizes.
+
+use std::fmt;
+
+/// How cells are shaped and which cells are neighbours.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Lattice {
+    /// Square cells with the eight Moore neighbours.
+    #[default]
+    Square,
+    /// Hexagonal cells with six neighbours, marked `H` in rules.
+    Hexagonal,
+    /// Triangular cells with the twelve neighbours sharing an edge or a
+    /// corner, marked `L` in rules.
+    Triangular,
+    /// Triangular cells with the three neighbours sharing an edge, marked
+    /// `LE` in rules.
+    TriangularEdges,
+}
+
+// Neighbour offsets, in the order of the rows above, beside and below
+const SQUARE: [(i64, i64); 8] = [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)];
+const HEXAGONAL: [(i64, i64); 6] = [(-1, -1), (0, -1), (-1, 0), (1, 0), (0, 1), (1, 1)];
+const TRIANGLE_UP: [(i64, i64); 12] = [
+    (-1, -1), (0, -1), (1, -1),
+    (-2, 0), (-1, 0), (1, 0), (2, 0),
+    (-2, 1), (-1, 1), (0, 1), (1, 1), (2, 1),
+];
+const TRIANGLE_DOWN: [(i64, i64); 12] = [
+    (-2, -1), (-1, -1), (0, -1), (1, -1), (2, -1),
+    (-2, 0), (-1, 0), (1, 0), (2, 0),
+    (-1, 1), (0, 1), (1, 1),
+];
+const TRIANGLE_UP_EDGES: [(i64, i64); 3] = [(-1, 0), (1, 0), (0, 1)];
+const TRIANGLE_DOWN_EDGES: [(i64, i64); 3] = [(0, -1), (-1, 0), (1, 0)];
+
+// Height of a hexagon's pointed top and bottom beyond its row
+const HEX_TIP: f64 = 1.0 / 6.0;
+
+impl Lattice {
+    pub const ALL: [Lattice; 4] = [Lattice::Square, Lattice::Hexagonal, Lattice::Triangular, Lattice::TriangularEdges];
+
+    /// Number of neighbours of every cell.
+    pub fn neighbours(self) -> u8 {
+        match self {
+            Lattice::Square => 8,
+            Lattice::Hexagonal => 6,
+            Lattice::Triangular => 12,
+            Lattice::TriangularEdges => 3,
+        }
+    }
+
+    /// The letters that follow a rule for this lattice, as in `B2/S34H`.
+    pub fn suffix(self) -> &'static str {
+        match self {
+            Lattice::Square => "",
+            Lattice::Hexagonal => "H",
+            Lattice::Triangular => "L",
+            Lattice::TriangularEdges => "LE",
+        }
+    }
+
+    /// Where the point `(x, y)` of the square lattice is drawn along its
+    /// row: hexagons move half a cell left with every row down, and other
+    /// cells stay put.
+    pub fn shear(self, x: f64, y: f64) -> f64 {
+        match self {
+            Lattice::Hexagonal => x - y * 0.5,
+            _ => x,
+        }
+    }
+
+    /// Offsets from the cell `(x, y)` to each of its neighbours.
+    pub fn neighbour_offsets(self, x: i64, y: i64) -> &'static [(i64, i64)] {
+        let up = (x + y).rem_euclid(2) == 0;
+        match self {
+            Lattice::Square => &SQUARE,
+            Lattice::Hexagonal => &HEXAGONAL,
+            Lattice::Triangular if up => &TRIANGLE_UP,
+            Lattice::Triangular => &TRIANGLE_DOWN,
+            Lattice::TriangularEdges if up => &TRIANGLE_UP_EDGES,
+            Lattice::TriangularEdges => &TRIANGLE_DOWN_EDGES,
+        }
+    }
+
+    /// The corners of the cell `(x, y)`, going clockwise, in the coordinates
+    /// where a square cell spans one unit.
+    pub fn outline(self, x: i64, y: i64) -> Vec<(f64, f64)> {
+        let (x, y, up) = (x as f64, y as f64, (x + y).rem_euclid(2) == 0);
+        match self {
+            Lattice::Square => vec![(x, y), (x + 1.0, y), (x + 1.0, y + 1.0), (x, y + 1.0)],
+            Lattice::Hexagonal => {
+                let left = self.shear(x, y);
+                vec![
+                    (left + 0.5, y - HEX_TIP),
+                    (left + 1.0, y + HEX_TIP),
+                    (left + 1.0, y + 1.0 - HEX_TIP),
+                    (left + 0.5, y + 1.0 + HEX_TIP),
+                    (left, y + 1.0 - HEX_TIP),
+                    (left, y + HEX_TIP),
+                ]
+            }
+            Lattice::Triangular | Lattice::TriangularEdges if up => {
+                vec![(x + 0.5, y), (x + 1.5, y + 1.0), (x - 0.5, y + 1.0)]
+            }
+            Lattice::Triangular | Lattice::TriangularEdges => {
+                vec![(x - 0.5, y), (x + 1.5, y), (x + 0.5, y + 1.0)]
+            }
+        }
+    }
+
+    /// The cell containing the point `(x, y)`, in the coordinates of
+    /// [`Lattice::outline`].
+    pub fn cell_at(self, x: f64, y: f64) -> (i64, i64) {
+        let row = y.floor() as i64;
+        match self {
+            Lattice::Square => (x.floor() as i64, row),
+            // The nearest centre, measured with rows squeezed to the spacing
+            // of regular hexagons
+            Lattice::Hexagonal => (row - 1..=row + 1)
+                .map(|row| {
+                    let column = (x - self.shear(0.0, row as f64)).floor() as i64;
+                    let dx = x - (self.shear(column as f64, row as f64) + 0.5);
+                    let dy = (y - (row as f64 + 0.5)) * 3f64.sqrt() / 2.0;
+                    ((column, row), dx * dx + dy * dy)
+                })
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .map_or((x.floor() as i64, row), |(cell, _)| cell),
+            // A triangle's width across the row grows from its point
+            Lattice::Triangular | Lattice::TriangularEdges => {
+                let depth = y - row as f64;
+                let column = x.floor() as i64;
+                (column - 1..=column + 1)
+                    .find(|&column| {
+                        let centre = column as f64 + 0.5;
+                        let half_width = if (column + row).rem_euclid(2) == 0 { depth } else { 1.0 - depth };
+                        (x - centre).abs() <= half_width
+                    })
+                    .map_or((column, row), |column| (column, row))
+            }
+        }
+    }
+}
+
+
+impl fmt::Display for Lattice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Lattice::Square => "Square",
+            Lattice::Hexagonal => "Hexagonal",
+            Lattice::Triangular => "Triangular",
+            Lattice::TriangularEdges => "Triangular (edges)",
+        })
+    }
+}
@@ -0,0 +1,45 @@
+//! Simulation core for Cellverse.
+//!
+//! This crate holds the cellular automaton engine with no GUI dependencies, so
+//! it can be driven from tests, batch jobs or any front-end. The GTK
+//! application in the repository root is one such client.
+
+mod bitgrid;
+pub mod draw;
+mod engine;
+pub mod formats;
+pub mod hashlife;
+pub mod history;
+mod larger_than_life;
+mod lattice;
+pub mod library;
+mod pattern;
+mod rule;
+mod rule_table;
+mod run;
+mod selection;
+pub mod timeline;
+mod sparse;
+mod topology;
+mod universe;
+
+pub use bitgrid::BitGrid;
+pub use draw::Shape;
+pub use engine::Engine;
+pub use formats::{Format, ParsePatternError};
+pub use hashlife::HashLife;
+pub use history::History;
+pub use larger_than_life::{LargerThanLife, NeighbourhoodShape, MAX_RADIUS};
+pub use lattice::Lattice;
+pub use library::Library;
+pub use pattern::{PasteMode, Pattern};
+pub use rule::{ParseRuleError, Rule};
+pub use rule_table::{Icon, ParseRuleTableError, RuleTable, TableNeighbourhood};
+pub use run::{RunUntil, StopCondition};
+pub use selection::Selection;
+pub use timeline::Timeline;
+pub use topology::Topology;
+pub use universe::{
+    Stats, Universe, GROWTH_AMOUNT, GROWTH_INTERVAL, INITIAL_GRID_HEIGHT, INITIAL_GRID_WIDTH,
+    MAX_GRID_HEIGHT, MAX_GRID_WIDTH,
+};
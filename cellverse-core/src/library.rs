@@ -0,0 +1,152 @@
+//! The bundled pattern library.
+//!
+//! Patterns are RLE files under `patterns/`, one directory per category,
+//! embedded into the binary at compile time.
+
+use std::fmt;
+
+use crate::formats::rle;
+use crate::Pattern;
+
+/// The kind of object a library pattern is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Category {
+    StillLife,
+    Oscillator,
+    Spaceship,
+    Gun,
+    Methuselah,
+    Puffer,
+}
+
+impl Category {
+    pub const ALL: [Category; 6] = [
+        Category::StillLife,
+        Category::Oscillator,
+        Category::Spaceship,
+        Category::Gun,
+        Category::Methuselah,
+        Category::Puffer,
+    ];
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Category::StillLife => "Still lifes",
+            Category::Oscillator => "Oscillators",
+            Category::Spaceship => "Spaceships",
+            Category::Gun => "Guns",
+            Category::Methuselah => "Methuselahs",
+            Category::Puffer => "Puffers",
+        })
+    }
+}
+
+macro_rules! bundled {
+    ($($category:ident: [$($file:literal),* $(,)?]),* $(,)?) => {
+        &[$($((Category::$category, include_str!(concat!("../patterns/", $file)))),*),*]
+    };
+}
+
+const BUNDLED: &[(Category, &str)] = bundled! {
+    StillLife: [
+        "still-lifes/block.rle",
+        "still-lifes/beehive.rle",
+        "still-lifes/loaf.rle",
+        "still-lifes/boat.rle",
+        "still-lifes/tub.rle",
+        "still-lifes/ship.rle",
+        "still-lifes/pond.rle",
+    ],
+    Oscillator: [
+        "oscillators/blinker.rle",
+        "oscillators/toad.rle",
+        "oscillators/beacon.rle",
+        "oscillators/pulsar.rle",
+        "oscillators/pentadecathlon.rle",
+        "oscillators/galaxy.rle",
+    ],
+    Spaceship: [
+        "spaceships/glider.rle",
+        "spaceships/lwss.rle",
+        "spaceships/mwss.rle",
+        "spaceships/hwss.rle",
+    ],
+    Gun: [
+        "guns/gosper-glider-gun.rle",
+        "guns/simkin-glider-gun.rle",
+    ],
+    Methuselah: [
+        "methuselahs/r-pentomino.rle",
+        "methuselahs/diehard.rle",
+        "methuselahs/acorn.rle",
+    ],
+    Puffer: [
+        "puffers/blinker-puffer-1.rle",
+    ],
+};
+
+/// A pattern in the library together with its category.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub category: Category,
+    pub pattern: Pattern,
+}
+
+impl Entry {
+    /// The pattern's name, which every bundled file provides.
+    pub fn name(&self) -> &str {
+        self.pattern.name.as_deref().unwrap_or("Unnamed")
+    }
+
+    // Case-insensitive match on the name, author, comments or category
+    fn matches(&self, query: &str) -> bool {
+        let query = query.to_lowercase();
+        let pattern = &self.pattern;
+        std::iter::once(self.name())
+            .chain(pattern.author.as_deref())
+            .chain(pattern.comments.iter().map(String::as_str))
+            .map(str::to_lowercase)
+            .chain(std::iter::once(self.category.to_string().to_lowercase()))
+            .any(|text| text.contains(&query))
+    }
+}
+
+/// A searchable collection of named patterns.
+#[derive(Debug, Clone, Default)]
+pub struct Library {
+    entries: Vec<Entry>,
+}
+
+impl Library {
+    /// The patterns shipped with Cellverse.
+    pub fn bundled() -> Self {
+        let entries = BUNDLED
+            .iter()
+            .map(|&(category, text)| Entry {
+                category,
+                pattern: rle::read(text).expect("bundled patterns are valid RLE"),
+            })
+            .collect();
+        Self { entries }
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Looks a pattern up by name, ignoring case.
+    pub fn get(&self, name: &str) -> Option<&Entry> {
+        self.entries.iter().find(|entry| entry.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Entries in `category` (or any category for `None`) whose name, author,
+    /// comments or category contain `query`, ignoring case.
+    pub fn search<'a>(&'a self, query: &'a str, category: Option<Category>) -> impl Iterator<Item = &'a Entry> + 'a {
+        self.entries
+            .iter()
+            .filter(move |entry| category.is_none_or(|category| entry.category == category))
+            .filter(move |entry| entry.matches(query))
+    }
+}
@@ -0,0 +1,246 @@
+use std::fmt;
+
+use crate::{Engine, HashLife, Rule, Universe};
+
+/// How a pattern's cells combine with the cells already in place.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum PasteMode {
+    /// The pattern's cells are added; existing cells are kept.
+    #[default]
+    Or,
+    /// The pattern's cells toggle the cells underneath: a cell already in
+    /// the same state dies, any other takes the pattern's state.
+    Xor,
+    /// The pattern's whole bounding box replaces what was there.
+    Copy,
+}
+
+impl PasteMode {
+    pub const ALL: [PasteMode; 3] = [PasteMode::Or, PasteMode::Xor, PasteMode::Copy];
+}
+
+impl fmt::Display for PasteMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PasteMode::Or => "OR",
+            PasteMode::Xor => "XOR",
+            PasteMode::Copy => "Copy",
+        })
+    }
+}
+
+/// A finite arrangement of cells together with its metadata, as read from or
+/// written to a pattern file.
+///
+/// Cell coordinates are relative to the top-left corner of the pattern's
+/// bounding box. Besides live cells, patterns for rules with more than two
+/// states keep the cells in every other state but dead.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Pattern {
+    pub name: Option<String>,
+    pub author: Option<String>,
+    pub comments: Vec<String>,
+    pub rule: Option<Rule>,
+    /// The rule the file names when it is not one [`Rule`] can parse, such
+    /// as a rule table that has to be loaded from its own `.rule` file.
+    /// `rule` is `None` then.
+    pub rule_name: Option<String>,
+    width: u64,
+    height: u64,
+    cells: Vec<(i64, i64, u8)>,
+}
+
+impl Pattern {
+    /// Builds a pattern from live cell coordinates, shifting them so the
+    /// bounding box starts at the origin.
+    pub fn from_cells(cells: impl IntoIterator<Item = (i64, i64)>) -> Self {
+        Self::from_states(cells.into_iter().map(|(x, y)| (x, y, 1)))
+    }
+
+    /// Like [`Pattern::from_cells`], with the state of each cell. Dead cells
+    /// are left out; of cells given twice, the last state counts.
+    pub fn from_states(cells: impl IntoIterator<Item = (i64, i64, u8)>) -> Self {
+        let mut cells = sorted(cells.into_iter().collect());
+
+        let Some(min_x) = cells.iter().map(|&(x, _, _)| x).min() else {
+            return Self::default();
+        };
+        let min_y = cells[0].1;
+        let max_x = cells.iter().map(|&(x, _, _)| x).max().unwrap_or(min_x);
+        let max_y = cells[cells.len() - 1].1;
+
+        for cell in &mut cells {
+            cell.0 -= min_x;
+            cell.1 -= min_y;
+        }
+
+        Self {
+            width: (max_x - min_x + 1) as u64,
+            height: (max_y - min_y + 1) as u64,
+            cells,
+            ..Self::default()
+        }
+    }
+
+    // Builds a pattern from non-negative coordinates that are already relative
+    // to the box's top-left corner, keeping any blank leading rows or columns
+    pub(crate) fn from_placed_cells(cells: Vec<(i64, i64, u8)>) -> Self {
+        let cells = sorted(cells);
+        let width = cells.iter().map(|&(x, _, _)| x + 1).max().unwrap_or(0) as u64;
+        let height = cells.last().map_or(0, |&(_, y, _)| y + 1) as u64;
+        Self {
+            width,
+            height,
+            cells,
+            ..Self::default()
+        }
+    }
+
+    /// Captures every cell of `universe` that is not dead, along with its
+    /// rule.
+    pub fn from_universe(universe: &Universe) -> Self {
+        Self {
+            rule: Some(universe.rule()),
+            ..Self::from_states(universe.cells())
+        }
+    }
+
+    /// Captures every cell of a HashLife plane that is not dead, along with
+    /// its rule.
+    pub fn from_hashlife(hashlife: &HashLife) -> Self {
+        let mut cells = Vec::new();
+        hashlife.for_each_cell_anywhere(&mut |x, y, state| cells.push((x, y, state)));
+        Self {
+            rule: Some(hashlife.rule()),
+            ..Self::from_states(cells)
+        }
+    }
+
+    pub fn width(&self) -> u64 {
+        self.width
+    }
+
+    pub fn height(&self) -> u64 {
+        self.height
+    }
+
+    /// Cells that are not dead with their states, sorted row by row.
+    pub fn cells(&self) -> &[(i64, i64, u8)] {
+        &self.cells
+    }
+
+    /// Live cells, sorted row by row.
+    pub fn live_cells(&self) -> impl Iterator<Item = (i64, i64)> + '_ {
+        self.cells.iter().filter(|&&(_, _, state)| state == 1).map(|&(x, y, _)| (x, y))
+    }
+
+    /// Number of live cells.
+    pub fn population(&self) -> usize {
+        self.live_cells().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    /// Enlarges the bounding box, e.g. to honour the size given in a file
+    /// header. The box never shrinks below the live cells.
+    pub fn set_size(&mut self, width: u64, height: u64) {
+        self.width = self.width.max(width);
+        self.height = self.height.max(height);
+    }
+
+    /// Whether the cell at `(x, y)` relative to the pattern's corner is alive.
+    pub fn contains(&self, x: i64, y: i64) -> bool {
+        self.state(x, y) == 1
+    }
+
+    /// The state of the cell at `(x, y)` relative to the pattern's corner.
+    pub fn state(&self, x: i64, y: i64) -> u8 {
+        self.cells
+            .binary_search_by_key(&(y, x), |&(x, y, _)| (y, x))
+            .map_or(0, |index| self.cells[index].2)
+    }
+
+    /// Sets the pattern's cells in `engine` with its top-left corner at `(x, y)`.
+    pub fn stamp(&self, engine: &mut dyn Engine, x: i64, y: i64) {
+        self.paste(engine, x, y, PasteMode::Or);
+    }
+
+    /// Combines the pattern with the cells of `engine`, placing its top-left
+    /// corner at `(x, y)`.
+    pub fn paste(&self, engine: &mut dyn Engine, x: i64, y: i64, mode: PasteMode) {
+        match mode {
+            PasteMode::Or => {
+                for &(dx, dy, state) in &self.cells {
+                    engine.set_state(x + dx, y + dy, state);
+                }
+            }
+            PasteMode::Xor => {
+                for &(dx, dy, state) in &self.cells {
+                    let current = engine.get_state(x + dx, y + dy);
+                    engine.set_state(x + dx, y + dy, if current == state { 0 } else { state });
+                }
+            }
+            PasteMode::Copy => {
+                for dy in 0..self.height as i64 {
+                    for dx in 0..self.width as i64 {
+                        engine.set_state(x + dx, y + dy, self.state(dx, dy));
+                    }
+                }
+            }
+        }
+    }
+
+    /// The pattern turned a quarter turn clockwise.
+    pub fn rotated_clockwise(&self) -> Self {
+        let height = self.height as i64;
+        self.transformed(self.height, self.width, |x, y| (height - 1 - y, x))
+    }
+
+    /// The pattern mirrored left to right.
+    pub fn flipped_horizontally(&self) -> Self {
+        let width = self.width as i64;
+        self.transformed(self.width, self.height, |x, y| (width - 1 - x, y))
+    }
+
+    /// The pattern mirrored top to bottom.
+    pub fn flipped_vertically(&self) -> Self {
+        let height = self.height as i64;
+        self.transformed(self.width, self.height, |x, y| (x, height - 1 - y))
+    }
+
+    // Map every cell into a box of the given size, keeping the metadata
+    fn transformed(&self, width: u64, height: u64, map: impl Fn(i64, i64) -> (i64, i64)) -> Self {
+        let cells = self.cells.iter().map(|&(x, y, state)| {
+            let (x, y) = map(x, y);
+            (x, y, state)
+        });
+        let mut pattern = Self::from_placed_cells(cells.collect());
+        pattern.set_size(width, height);
+        Self {
+            name: self.name.clone(),
+            author: self.author.clone(),
+            comments: self.comments.clone(),
+            rule: self.rule.clone(),
+            rule_name: self.rule_name.clone(),
+            ..pattern
+        }
+    }
+}
+
+// Sort cells row by row, dropping dead ones and all but the last of any
+// given twice
+fn sorted(mut cells: Vec<(i64, i64, u8)>) -> Vec<(i64, i64, u8)> {
+    cells.retain(|&(_, _, state)| state != 0);
+    // A stable sort keeps repeated cells in order, so the last is found last
+    cells.sort_by_key(|&(x, y, _)| (y, x));
+    let mut unique: Vec<(i64, i64, u8)> = Vec::with_capacity(cells.len());
+    for cell in cells {
+        match unique.last_mut() {
+            Some(last) if (last.0, last.1) == (cell.0, cell.1) => *last = cell,
+            _ => unique.push(cell),
+        }
+    }
+    unique
+}
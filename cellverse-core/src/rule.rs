@@ -0,0 +1,736 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::{Lattice, LargerThanLife, RuleTable, TableNeighbourhood, MAX_RADIUS};
+
+/// A Life-like rule such as `B3/S23`, or a Generations rule such as Brian's
+/// Brain, `/2/3`.
+///
+/// On the square lattice, births and survivals are stored as sets of
+/// neighbourhoods: each of the 256 arrangements of live cells among the
+/// eight neighbours is a byte with the bits
+///
+/// ```text
+/// 7 6 5      NW N  NE
+/// 4 . 3  =   W  .  E
+/// 2 1 0      SW S  SE
+/// ```
+///
+/// Outer-totalistic rules only look at the number of live neighbours, but
+/// isotropic non-totalistic rules written in Hensel notation, such as
+/// `B2-a/S12`, tell the arrangements of each count apart by letter.
+///
+/// Rules for the hexagonal and triangular lattices, such as `B2/S34H`, only
+/// count neighbours; see [`Lattice`]. Larger than Life rules such as
+/// `R5,C0,M1,S34..58,B34..45,NM` count the live cells in a wider
+/// neighbourhood instead; see [`LargerThanLife`]. Rules read from Golly
+/// `.rule` files give the next state for every combination of neighbour
+/// states; see [`RuleTable`].
+///
+/// Cell states are numbered 0 for dead and 1 for alive. Generations rules
+/// add the dying states 2 and up: a live cell that does not survive moves
+/// to state 2 rather than dying at once, then one state further each
+/// generation until it wraps back to 0. Dying cells are not counted as
+/// neighbours and cannot be born into. Rule tables give every state its own
+/// meaning instead.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Rule {
+    conditions: Conditions,
+    states: u8,
+}
+
+// When cells are born and survive
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Conditions {
+    // Sets of neighbourhoods on the square lattice
+    Square { birth: [u64; 4], survival: [u64; 4] },
+    // Masks of neighbour counts on the other lattices
+    Counts { lattice: Lattice, birth: u16, survival: u16 },
+    Range(LargerThanLife),
+    // Shared, since tables can be large and rules are cloned freely
+    Table(Arc<RuleTable>),
+}
+
+impl Rule {
+    /// Builds a rule from the neighbour counts that cause a birth and those
+    /// that let a live cell survive. Counts above 8 are ignored.
+    pub fn new(birth: &[u8], survival: &[u8]) -> Self {
+        Self::on_lattice(Lattice::Square, birth, survival)
+    }
+
+    /// Like [`Rule::new`], for any lattice. Counts above its number of
+    /// neighbours are ignored.
+    pub fn on_lattice(lattice: Lattice, birth: &[u8], survival: &[u8]) -> Self {
+        let conditions = match lattice {
+            Lattice::Square => Conditions::Square {
+                birth: totalistic(birth),
+                survival: totalistic(survival),
+            },
+            _ => {
+                let mask = |counts: &[u8]| {
+                    counts
+                        .iter()
+                        .filter(|&&n| n <= lattice.neighbours())
+                        .fold(0, |mask, &n| mask | (1 << n))
+                };
+                Conditions::Counts {
+                    lattice,
+                    birth: mask(birth),
+                    survival: mask(survival),
+                }
+            }
+        };
+        Self { conditions, states: 2 }
+    }
+
+    /// Builds a Larger than Life rule with `states` states in all, as for
+    /// [`Rule::generations`].
+    pub fn larger_than_life(range: LargerThanLife, states: u8) -> Self {
+        Self {
+            conditions: Conditions::Range(range),
+            states: states.max(2),
+        }
+    }
+
+    /// Builds a rule from a Golly rule table or tree.
+    pub fn from_table(table: RuleTable) -> Self {
+        Self {
+            states: table.states(),
+            conditions: Conditions::Table(Arc::new(table)),
+        }
+    }
+
+    /// Builds a Generations rule with `states` states in all, counting dead
+    /// and alive. Two states give the Life-like rule.
+    pub fn generations(survival: &[u8], birth: &[u8], states: u8) -> Self {
+        Self {
+            states: states.max(2),
+            ..Self::new(birth, survival)
+        }
+    }
+
+    /// Conway's Game of Life, `B3/S23`.
+    pub fn conway() -> Self {
+        Self::new(&[3], &[2, 3])
+    }
+
+    /// Number of cell states, 2 for Life-like rules.
+    pub fn states(&self) -> u8 {
+        self.states
+    }
+
+    /// The lattice the rule runs on.
+    pub fn lattice(&self) -> Lattice {
+        match &self.conditions {
+            Conditions::Counts { lattice, .. } => *lattice,
+            Conditions::Table(table) if table.neighbourhood() == TableNeighbourhood::Hexagonal => Lattice::Hexagonal,
+            _ => Lattice::Square,
+        }
+    }
+
+    /// The same rule moved to another lattice, keeping the neighbour counts
+    /// that lattice has. Larger than Life rules and rule tables bring their
+    /// own neighbourhood and stay as they are.
+    pub fn with_lattice(&self, lattice: Lattice) -> Self {
+        if lattice == self.lattice() || self.range().is_some() || self.table().is_some() {
+            return self.clone();
+        }
+        let counts = |keep: &dyn Fn(usize) -> bool| -> Vec<u8> {
+            (0..=lattice.neighbours()).filter(|&n| keep(n.into())).collect()
+        };
+        Self {
+            states: self.states,
+            ..Self::on_lattice(lattice, &counts(&|n| self.is_born(n)), &counts(&|n| self.survives(n)))
+        }
+    }
+
+    /// The extended neighbourhood of a Larger than Life rule.
+    pub fn range(&self) -> Option<&LargerThanLife> {
+        match &self.conditions {
+            Conditions::Range(range) => Some(range),
+            _ => None,
+        }
+    }
+
+    /// The rule table or tree of a rule read from a `.rule` file.
+    pub fn table(&self) -> Option<&RuleTable> {
+        match &self.conditions {
+            Conditions::Table(table) => Some(table),
+            _ => None,
+        }
+    }
+
+    /// Whether cells only ever are dead or alive and only look at their
+    /// eight neighbours on the square lattice, as the bit-packed and
+    /// HashLife engines require.
+    pub fn is_life_like(&self) -> bool {
+        self.states == 2 && matches!(self.conditions, Conditions::Square { .. })
+    }
+
+    /// Whether births and survivals only depend on the number of live
+    /// neighbours, not on where they are.
+    pub fn is_totalistic(&self) -> bool {
+        let (birth, survival) = match &self.conditions {
+            Conditions::Square { birth, survival } => (*birth, *survival),
+            Conditions::Table(_) => return false,
+            _ => return true,
+        };
+        (0..=8).all(|count| {
+            [birth, survival].iter().all(|set| {
+                let mut members = neighbourhoods(count).map(|n| contains(set, n));
+                let first = members.next().unwrap_or(false);
+                members.all(|member| member == first)
+            })
+        })
+    }
+
+    /// Whether a dead cell with `neighbors` live neighbours comes alive. For
+    /// non-totalistic rules, whether some arrangement of them does.
+    pub fn is_born(&self, neighbors: usize) -> bool {
+        match &self.conditions {
+            Conditions::Square { birth, .. } => neighbors <= 8 && neighbourhoods(neighbors as u32).any(|n| contains(birth, n)),
+            Conditions::Counts { birth, .. } => neighbors < 16 && birth & (1 << neighbors) != 0,
+            Conditions::Range(range) => u32::try_from(neighbors).is_ok_and(|count| range.is_born(count)),
+            Conditions::Table(_) => false,
+        }
+    }
+
+    /// Whether a live cell with `neighbors` live neighbours stays alive. For
+    /// non-totalistic rules, whether some arrangement of them lets it.
+    pub fn survives(&self, neighbors: usize) -> bool {
+        match &self.conditions {
+            Conditions::Square { survival, .. } => neighbors <= 8 && neighbourhoods(neighbors as u32).any(|n| contains(survival, n)),
+            Conditions::Counts { survival, .. } => neighbors < 16 && survival & (1 << neighbors) != 0,
+            Conditions::Range(range) => u32::try_from(neighbors).is_ok_and(|count| range.survives(count)),
+            Conditions::Table(_) => false,
+        }
+    }
+
+    /// Returns the next state of a cell given its current state and live neighbour count.
+    pub fn next_state(&self, alive: bool, neighbors: usize) -> bool {
+        if alive {
+            self.survives(neighbors)
+        } else {
+            self.is_born(neighbors)
+        }
+    }
+
+    /// Whether a dead cell with the live neighbours `neighbourhood` on the
+    /// square lattice comes alive.
+    pub fn is_born_with(&self, neighbourhood: u8) -> bool {
+        match &self.conditions {
+            Conditions::Square { birth, .. } => contains(birth, neighbourhood),
+            _ => self.is_born(neighbourhood.count_ones() as usize),
+        }
+    }
+
+    /// Whether a live cell with the live neighbours `neighbourhood` on the
+    /// square lattice stays alive.
+    pub fn survives_with(&self, neighbourhood: u8) -> bool {
+        match &self.conditions {
+            Conditions::Square { survival, .. } => contains(survival, neighbourhood),
+            _ => self.survives(neighbourhood.count_ones() as usize),
+        }
+    }
+
+    /// Returns the next state of a cell in any state, including the dying
+    /// states of Generations rules, given its live neighbours `neighbourhood`.
+    pub fn next(&self, state: u8, neighbourhood: u8) -> u8 {
+        self.advance(state, || self.is_born_with(neighbourhood), || self.survives_with(neighbourhood))
+    }
+
+    /// Like [`Rule::next`], given the number of live cells in the
+    /// neighbourhood. Rules for the hexagonal and triangular lattices and
+    /// Larger than Life rules step this way, the latter counting the cell
+    /// itself when their `middle` flag says so.
+    pub fn next_by_count(&self, state: u8, count: u32) -> u8 {
+        let count = count as usize;
+        self.advance(state, || self.is_born(count), || self.survives(count))
+    }
+
+    fn advance(&self, state: u8, born: impl Fn() -> bool, survives: impl Fn() -> bool) -> u8 {
+        match state {
+            0 => u8::from(born()),
+            1 if survives() => 1,
+            _ if state < self.states - 1 => state + 1,
+            _ => 0,
+        }
+    }
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        Self::conway()
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (birth, survival) = match &self.conditions {
+            Conditions::Range(range) => return range.fmt(self.states, f),
+            Conditions::Table(table) => return f.write_str(table.name()),
+            Conditions::Square { birth, survival } => (hensel(birth), hensel(survival)),
+            Conditions::Counts { birth, survival, .. } => (counts(*birth), counts(*survival)),
+        };
+        let suffix = self.lattice().suffix();
+        if self.states == 2 {
+            write!(f, "B{}/S{}{}", birth, survival, suffix)
+        } else {
+            write!(f, "{}/{}/{}{}", survival, birth, self.states, suffix)
+        }
+    }
+}
+
+/// Error returned when a rule string cannot be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseRuleError {
+    /// The string is not of the form `B<digits>/S<digits>`, `<digits>/<digits>`,
+    /// the Generations form `<digits>/<digits>/<states>` or a Larger than
+    /// Life rule with at least `R`, `S` and `B`.
+    Malformed(String),
+    /// A neighbour count outside `0..=8` or a stray character was found.
+    InvalidCount(char),
+    /// The number of Generations states is not between 2 and 255.
+    InvalidStates(String),
+    /// A Hensel letter that does not name an arrangement of that many neighbours.
+    InvalidLetter { count: u8, letter: char },
+    /// A Hensel letter that does not follow a neighbour count.
+    LetterWithoutCount(char),
+    /// A `-` that is not followed by the letters to leave out.
+    MissingLetters(u8),
+    /// A part of a Larger than Life rule that is not one of `R`, `C`, `M`,
+    /// `S`, `B` or `N` with a valid value.
+    InvalidTerm(String),
+    /// A Larger than Life radius outside `1..=500`.
+    InvalidRadius(String),
+    /// A neighbour count or stray character in a rule for the hexagonal or
+    /// triangular lattice.
+    InvalidLatticeCount { count: char, lattice: Lattice },
+}
+
+impl fmt::Display for ParseRuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseRuleError::Malformed(rule) => {
+                write!(
+                    f,
+                    "'{}' is not a rule in B/S notation (e.g. B3/S23), S/B/C notation (e.g. /2/3) \
+                     or Larger than Life notation (e.g. R5,C0,M1,S34..58,B34..45,NM)",
+                    rule
+                )
+            }
+            ParseRuleError::InvalidCount(c) => {
+                write!(f, "'{}' is not a neighbour count between 0 and 8", c)
+            }
+            ParseRuleError::InvalidStates(states) => {
+                write!(f, "'{}' is not a number of states between 2 and 255", states)
+            }
+            ParseRuleError::InvalidLetter { count, letter } => {
+                let letters: String = classes(*count).iter().map(|&(letter, _)| letter).collect();
+                if letters.is_empty() {
+                    write!(f, "'{}{}' is not valid: {} neighbours can only be arranged one way and take no letters", count, letter, count)
+                } else {
+                    write!(f, "'{}{}' is not valid: the letters for {} neighbours are {}", count, letter, count, letters)
+                }
+            }
+            ParseRuleError::LetterWithoutCount(letter) => {
+                write!(f, "'{}' must follow a neighbour count, as in B2{}", letter, letter)
+            }
+            ParseRuleError::MissingLetters(count) => {
+                write!(f, "'{}-' must be followed by the letters to leave out, as in {}-a", count, count)
+            }
+            ParseRuleError::InvalidTerm(term) => {
+                write!(f, "'{}' is not a Larger than Life term such as R5, C0, M1, S34..58, B34..45 or NM", term)
+            }
+            ParseRuleError::InvalidRadius(radius) => {
+                write!(f, "'{}' is not a radius between 1 and {}", radius, MAX_RADIUS)
+            }
+            ParseRuleError::InvalidLatticeCount { count, lattice } => {
+                let neighbours = lattice.neighbours();
+                write!(f, "'{}' is not a neighbour count between 0 and {} on the {} lattice", count, neighbours, lattice.to_string().to_lowercase())?;
+                if neighbours > 9 {
+                    write!(f, " (counts from 10 up are written A, B and C)")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseRuleError {}
+
+impl FromStr for Rule {
+    type Err = ParseRuleError;
+
+    /// Parses `B3/S23` style rules (letters in either order and case) as well
+    /// as the older `S/B` form such as `23/3`. Generations rules are written
+    /// `S/B/C` with the number of states last, such as `345/2/4`, or tagged
+    /// as in `B2/S345/C4`.
+    ///
+    /// Counts may be followed by Hensel letters to pick out arrangements of
+    /// the neighbours, or by `-` and the letters to leave out, as in
+    /// `B2-a/S12` or `B3/S2-i34q`.
+    ///
+    /// Rules for other lattices end in the lattice's suffix: `H` for
+    /// hexagonal as in `B2/S34H`, `L` for triangular and `LE` for triangular
+    /// with edge neighbours only. Triangular counts from 10 to 12 are
+    /// written `A`, `B` and `C`.
+    ///
+    /// Larger than Life rules are written as in Golly, starting with the
+    /// radius: `R5,C0,M1,S34..58,B34..45,NM`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rule = s.trim();
+        if rule.starts_with(['R', 'r']) {
+            let (range, states) = LargerThanLife::parse(rule)?;
+            return Ok(Self::larger_than_life(range, states));
+        }
+
+        // The longest suffix first, since `L` ends `LE` too
+        let (rule, lattice) = [Lattice::TriangularEdges, Lattice::Triangular, Lattice::Hexagonal]
+            .into_iter()
+            .find_map(|lattice| {
+                let suffix = lattice.suffix();
+                let split = rule.len().checked_sub(suffix.len())?;
+                let (rest, end) = (rule.get(..split)?, rule.get(split..)?);
+                end.eq_ignore_ascii_case(suffix).then_some((rest, lattice))
+            })
+            .unwrap_or((rule, Lattice::Square));
+
+        let malformed = || ParseRuleError::Malformed(rule.to_string());
+        let (rule_part, states) = match rule.matches('/').count() {
+            1 => (rule, 2),
+            2 => {
+                let (rest, states) = rule.rsplit_once('/').ok_or_else(malformed)?;
+                (rest, parse_states(states)?)
+            }
+            _ => return Err(malformed()),
+        };
+        let (first, second) = rule_part.split_once('/').ok_or_else(malformed)?;
+
+        let tagged = |part: &str, tag: char| -> Option<String> {
+            part.strip_prefix(tag)
+                .or_else(|| part.strip_prefix(tag.to_ascii_lowercase()))
+                .map(str::to_string)
+        };
+
+        let (birth, survival) = match (tagged(first, 'B'), tagged(second, 'S')) {
+            (Some(b), Some(s)) => (b, s),
+            _ => match (tagged(first, 'S'), tagged(second, 'B')) {
+                (Some(s), Some(b)) => (b, s),
+                // Untagged rules use the traditional survival/birth order
+                _ if !first.starts_with(char::is_alphabetic)
+                    && !second.starts_with(char::is_alphabetic) =>
+                {
+                    (second.to_string(), first.to_string())
+                }
+                _ => return Err(malformed()),
+            },
+        };
+
+        let conditions = match lattice {
+            Lattice::Square => Conditions::Square {
+                birth: parse_neighbourhoods(&birth)?,
+                survival: parse_neighbourhoods(&survival)?,
+            },
+            _ => Conditions::Counts {
+                lattice,
+                birth: parse_counts(&birth, lattice)?,
+                survival: parse_counts(&survival, lattice)?,
+            },
+        };
+        Ok(Self { conditions, states })
+    }
+}
+
+// The state count of a Generations rule, bare or tagged with C or G
+fn parse_states(states: &str) -> Result<u8, ParseRuleError> {
+    let count = states.strip_prefix(['C', 'c', 'G', 'g']).unwrap_or(states);
+    match count.parse::<u8>() {
+        Ok(count) if count >= 2 => Ok(count),
+        _ => Err(ParseRuleError::InvalidStates(states.to_string())),
+    }
+}
+
+// Parse the counts of one half of a rule, each optionally followed by Hensel
+// letters or by `-` and the letters to leave out
+fn parse_neighbourhoods(part: &str) -> Result<[u64; 4], ParseRuleError> {
+    let mut set = [0; 4];
+    let mut chars = part.chars().peekable();
+    while let Some(c) = chars.next() {
+        let count = match c.to_digit(10) {
+            Some(n) if n <= 8 => n as u8,
+            _ if c.is_ascii_lowercase() => return Err(ParseRuleError::LetterWithoutCount(c)),
+            _ => return Err(ParseRuleError::InvalidCount(c)),
+        };
+        let exclude = chars.next_if_eq(&'-').is_some();
+        let mut letters = Vec::new();
+        while let Some(letter) = chars.next_if(char::is_ascii_lowercase) {
+            letters.push(letter);
+        }
+
+        let classes = classes(count);
+        if let Some(&letter) = letters.iter().find(|letter| !classes.iter().any(|(l, _)| l == *letter)) {
+            return Err(ParseRuleError::InvalidLetter { count, letter });
+        }
+        if letters.is_empty() {
+            if exclude {
+                return Err(ParseRuleError::MissingLetters(count));
+            }
+            neighbourhoods(count.into()).for_each(|n| insert(&mut set, n));
+        } else {
+            for &(letter, neighbourhood) in &classes {
+                if letters.contains(&letter) != exclude {
+                    symmetries(neighbourhood).into_iter().for_each(|n| insert(&mut set, n));
+                }
+            }
+        }
+    }
+    Ok(set)
+}
+
+// Parse the counts of one half of a rule for the hexagonal or triangular
+// lattice, with A to C standing for 10 to 12
+fn parse_counts(part: &str, lattice: Lattice) -> Result<u16, ParseRuleError> {
+    part.chars().try_fold(0, |mask, c| match c.to_digit(13) {
+        Some(n) if n <= lattice.neighbours().into() && !c.is_ascii_lowercase() => Ok(mask | (1 << n)),
+        _ => Err(ParseRuleError::InvalidLatticeCount { count: c, lattice }),
+    })
+}
+
+// Write the counts of one half of a rule for the hexagonal or triangular
+// lattice
+fn counts(mask: u16) -> String {
+    (0..=12)
+        .filter(|n| mask & (1 << n) != 0)
+        .filter_map(|n| char::from_digit(n, 13))
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+// Write one half of a rule in the shortest Hensel form: a bare count when
+// every arrangement is included, otherwise the letters that are or, when
+// fewer, `-` and those that are not
+fn hensel(set: &[u64; 4]) -> String {
+    let mut out = String::new();
+    for count in 0..=8 {
+        let classes = classes(count);
+        let (included, excluded): (Vec<_>, Vec<_>) =
+            classes.iter().partition(|&&(_, neighbourhood)| contains(set, neighbourhood));
+        let letters = |classes: Vec<&(char, u8)>| classes.iter().map(|&&(letter, _)| letter).collect::<String>();
+
+        if classes.is_empty() {
+            // 0 and 8 neighbours can only be arranged one way
+            if neighbourhoods(count.into()).any(|n| contains(set, n)) {
+                out.push(char::from(b'0' + count));
+            }
+        } else if excluded.is_empty() {
+            out.push(char::from(b'0' + count));
+        } else if included.len() * 2 <= classes.len() {
+            if !included.is_empty() {
+                out.push(char::from(b'0' + count));
+                out.push_str(&letters(included));
+            }
+        } else {
+            out.push(char::from(b'0' + count));
+            out.push('-');
+            out.push_str(&letters(excluded));
+        }
+    }
+    out
+}
+
+fn contains(set: &[u64; 4], neighbourhood: u8) -> bool {
+    set[usize::from(neighbourhood / 64)] & (1 << (neighbourhood % 64)) != 0
+}
+
+fn insert(set: &mut [u64; 4], neighbourhood: u8) {
+    set[usize::from(neighbourhood / 64)] |= 1 << (neighbourhood % 64);
+}
+
+// Every neighbourhood with `count` live neighbours
+fn neighbourhoods(count: u32) -> impl Iterator<Item = u8> {
+    (0..=255u8).filter(move |n| n.count_ones() == count)
+}
+
+fn totalistic(counts: &[u8]) -> [u64; 4] {
+    let mut set = [0; 4];
+    for &count in counts.iter().filter(|&&n| n <= 8) {
+        neighbourhoods(count.into()).for_each(|n| insert(&mut set, n));
+    }
+    set
+}
+
+// Hensel letters for 1 to 4 live neighbours, in Golly's order, with one
+// arrangement of each. Arrangements are drawn row by row as in the `Rule`
+// docs: NW N NE, W E, SW S SE.
+#[allow(clippy::unusual_byte_groupings)]
+const HENSEL: [&[(char, u8)]; 4] = [
+    &[('c', 0b000_00_001), ('e', 0b000_00_010)],
+    &[
+        ('c', 0b000_00_101),
+        ('e', 0b000_01_010),
+        ('a', 0b000_00_011),
+        ('i', 0b000_11_000),
+        ('k', 0b000_10_001),
+        ('n', 0b001_00_100),
+    ],
+    &[
+        ('c', 0b001_00_101),
+        ('e', 0b000_11_010),
+        ('a', 0b000_01_011),
+        ('i', 0b000_00_111),
+        ('k', 0b001_10_010),
+        ('n', 0b000_01_101),
+        ('j', 0b000_01_110),
+        ('q', 0b001_00_110),
+        ('r', 0b000_11_001),
+        ('y', 0b001_10_001),
+    ],
+    &[
+        ('c', 0b101_00_101),
+        ('e', 0b010_11_010),
+        ('a', 0b000_01_111),
+        ('i', 0b000_11_101),
+        ('k', 0b001_10_011),
+        ('n', 0b001_00_111),
+        ('j', 0b001_11_010),
+        ('q', 0b001_10_110),
+        ('r', 0b000_11_011),
+        ('t', 0b001_10_101),
+        ('w', 0b001_11_001),
+        ('y', 0b001_01_110),
+        ('z', 0b001_11_100),
+    ],
+];
+
+// The Hensel letters for `count` live neighbours with an arrangement of
+// each. Five to seven neighbours mirror three to one: the letter of an
+// arrangement is that of its complement.
+fn classes(count: u8) -> Vec<(char, u8)> {
+    match count {
+        1..=4 => HENSEL[usize::from(count) - 1].to_vec(),
+        5..=7 => classes(8 - count).into_iter().map(|(letter, n)| (letter, !n)).collect(),
+        _ => Vec::new(),
+    }
+}
+
+// The eight rotations and reflections of a neighbourhood
+fn symmetries(neighbourhood: u8) -> [u8; 8] {
+    // Offsets of the neighbours, by bit
+    const OFFSETS: [(i8, i8); 8] = [(1, 1), (0, 1), (-1, 1), (1, 0), (-1, 0), (1, -1), (0, -1), (-1, -1)];
+    let transform = |map: &dyn Fn(i8, i8) -> (i8, i8)| -> u8 {
+        (0..8)
+            .filter(|bit| neighbourhood & (1 << bit) != 0)
+            .map(|bit| {
+                let (dx, dy) = OFFSETS[bit];
+                let moved = map(dx, dy);
+                1 << OFFSETS.iter().position(|&offset| offset == moved).unwrap_or(bit)
+            })
+            .fold(0, |acc, bit: u8| acc | bit)
+    };
+    [
+        transform(&|dx, dy| (dx, dy)),
+        transform(&|dx, dy| (-dy, dx)),
+        transform(&|dx, dy| (-dx, -dy)),
+        transform(&|dx, dy| (dy, -dx)),
+        transform(&|dx, dy| (-dx, dy)),
+        transform(&|dx, dy| (dx, -dy)),
+        transform(&|dx, dy| (dy, dx)),
+        transform(&|dx, dy| (-dy, -dx)),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(text: &str) -> Rule {
+        text.parse().unwrap_or_else(|err| panic!("{}: {}", text, err))
+    }
+
+    #[test]
+    fn hensel_letters_split_each_count_into_disjoint_classes() {
+        for count in 1..=4 {
+            let mut seen = [0; 4];
+            for (letter, _) in classes(count) {
+                let births = rule(&format!("B{}{}/S", count, letter));
+                for neighbourhood in neighbourhoods(count.into()).filter(|&n| births.is_born_with(n)) {
+                    assert!(!contains(&seen, neighbourhood), "{}{} overlaps another letter", count, letter);
+                    insert(&mut seen, neighbourhood);
+                }
+            }
+            assert_eq!(seen, totalistic(&[count]), "the letters for {} miss an arrangement", count);
+        }
+    }
+
+    #[test]
+    fn hensel_letters_match_golly() {
+        // Golly's arrangement for each letter, as 3x3 grids read row by row
+        // from the top left down to bit 0, with the cell itself as bit 4
+        let golly: [(&str, &[u16]); 4] = [
+            ("ce", &[1, 2]),
+            ("ceaikn", &[5, 10, 3, 40, 33, 68]),
+            ("ceaiknjqry", &[69, 42, 11, 7, 98, 13, 14, 70, 41, 97]),
+            ("ceaiknjqrtwyz", &[325, 170, 15, 45, 99, 71, 106, 102, 43, 101, 105, 78, 108]),
+        ];
+        let neighbourhood = |grid: u16| ((grid >> 1) & 0xf0 | grid & 0x0f) as u8;
+        // Left to right, to check the letters cover reflections too
+        let mirrored = |grid: u16| (0..3).fold(0, |out, row| out | ((grid >> (row * 3)) & 0b111).reverse_bits() >> 13 << (row * 3));
+
+        for (count, (letters, grids)) in (1..).zip(golly) {
+            for (letter, &grid) in letters.chars().zip(grids) {
+                for arrangement in [neighbourhood(grid), neighbourhood(mirrored(grid))] {
+                    for other in letters.chars() {
+                        let born = rule(&format!("B{}{}/S", count, other)).is_born_with(arrangement);
+                        assert_eq!(born, other == letter, "{:08b} under B{}{}", arrangement, count, other);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[allow(clippy::unusual_byte_groupings)]
+    fn letters_after_a_dash_are_left_out() {
+        assert_eq!(rule("B2-a/S12"), rule("B2ceikn/S12"));
+        assert_eq!(rule("B3/S2-i34q"), rule("B3/S2acekn34q"));
+        // NW and N are adjacent, N and S opposite
+        assert!(!rule("B2-a/S").is_born_with(0b110_00_000));
+        assert!(rule("B2-a/S").is_born_with(0b010_00_010));
+        // Leaving out every letter leaves nothing
+        assert_eq!(rule("B1-ce/S"), rule("B/S"));
+    }
+
+    #[test]
+    fn rules_display_as_they_parse() {
+        for text in ["B2-a/S12", "B3/S2-i34q", "B3/S23", "B36/S23", "B/S", "345/2/4", "B2/S34H", "B2k3-q/S4c"] {
+            let parsed = rule(text);
+            assert_eq!(parsed.to_string(), text);
+            assert_eq!(rule(&parsed.to_string()), parsed);
+        }
+        // Other spellings settle on one form
+        assert_eq!(rule("23/3").to_string(), "B3/S23");
+        assert_eq!(rule("s23/b3").to_string(), "B3/S23");
+        assert_eq!(rule("B2ceikn/S12").to_string(), "B2-a/S12");
+        assert_eq!(rule("B2/S345/C4").to_string(), "345/2/4");
+    }
+
+    #[test]
+    fn every_parse_error_is_reported() {
+        let error = |text: &str| text.parse::<Rule>().unwrap_err();
+        assert_eq!(error("B3S23"), ParseRuleError::Malformed("B3S23".to_string()));
+        assert_eq!(error("B9/S23"), ParseRuleError::InvalidCount('9'));
+        assert_eq!(error("B3/S23/1"), ParseRuleError::InvalidStates("1".to_string()));
+        assert_eq!(error("B2z/S"), ParseRuleError::InvalidLetter { count: 2, letter: 'z' });
+        assert_eq!(error("B0c/S"), ParseRuleError::InvalidLetter { count: 0, letter: 'c' });
+        assert_eq!(error("Ba/S"), ParseRuleError::LetterWithoutCount('a'));
+        assert_eq!(error("B2-/S"), ParseRuleError::MissingLetters(2));
+        assert_eq!(error("R5,C0,M1,S34..58,B34..45,NX"), ParseRuleError::InvalidTerm("NX".to_string()));
+        assert_eq!(error("R501,C0,M1,S34..58,B34..45,NM"), ParseRuleError::InvalidRadius("501".to_string()));
+        assert_eq!(
+            error("B7/S34H"),
+            ParseRuleError::InvalidLatticeCount { count: '7', lattice: Lattice::Hexagonal }
+        );
+    }
+}
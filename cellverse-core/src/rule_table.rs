@@ -0,0 +1,1154 @@
+//! Rules read from Golly `.rule` files.
+//!
+//! A rule file names its rule after `@RULE` and gives its transitions either
+//! as a `@TABLE`, lines such as `0,1,1,2,0,3` tried in order until one
+//! matches, or as a `@TREE`, a decision tree over the states of the cell and
+//! its neighbours. Tables are compiled into the same kind of tree as they are
+//! read, so a cell's next state takes one lookup per neighbour whichever form
+//! the file uses. `@COLORS` and `@ICONS` say how the states are drawn; other
+//! sections are ignored.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// The cells a rule table looks at around each cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TableNeighbourhood {
+    /// The four orthogonal neighbours.
+    VonNeumann,
+    /// All eight neighbours.
+    Moore,
+    /// The six neighbours on the hexagonal lattice.
+    Hexagonal,
+    /// The neighbours left and right on the same row.
+    OneDimensional,
+}
+
+// Neighbour offsets in the order the tree looks at them, as in Golly's rule
+// trees; the cell itself comes last
+const VON_NEUMANN: [(i64, i64); 4] = [(0, -1), (-1, 0), (1, 0), (0, 1)]; // N W E S
+const MOORE: [(i64, i64); 8] = [(-1, -1), (1, -1), (-1, 1), (1, 1), (0, -1), (-1, 0), (1, 0), (0, 1)]; // NW NE SW SE N W E S
+// N E SE S W NW, clockwise, on the skewed rows of `Lattice::Hexagonal`
+const HEXAGONAL: [(i64, i64); 6] = [(0, -1), (1, 0), (1, 1), (0, 1), (-1, 0), (-1, -1)];
+const ONE_DIMENSIONAL: [(i64, i64); 2] = [(-1, 0), (1, 0)]; // W E
+
+// Largest number of states a rule can have
+const MAX_STATES: usize = 255;
+
+// Icons drawn for Golly's built-in `circles` and `diamonds`
+const BUILT_IN_ICON_SIZE: i64 = 15;
+
+impl TableNeighbourhood {
+    /// Offsets from a cell to each of its neighbours, in the order
+    /// [`RuleTable::next`] reads them.
+    pub fn offsets(self) -> &'static [(i64, i64)] {
+        match self {
+            TableNeighbourhood::VonNeumann => &VON_NEUMANN,
+            TableNeighbourhood::Moore => &MOORE,
+            TableNeighbourhood::Hexagonal => &HEXAGONAL,
+            TableNeighbourhood::OneDimensional => &ONE_DIMENSIONAL,
+        }
+    }
+
+    // Where each neighbour of a table line, after the cell itself, goes in
+    // the tree's order. Lines go clockwise from N, or W then E in one
+    // dimension.
+    fn line_order(self) -> &'static [usize] {
+        match self {
+            TableNeighbourhood::VonNeumann => &[0, 2, 3, 1],
+            TableNeighbourhood::Moore => &[4, 1, 6, 3, 7, 2, 5, 0],
+            TableNeighbourhood::Hexagonal => &[0, 1, 2, 3, 4, 5],
+            TableNeighbourhood::OneDimensional => &[0, 1],
+        }
+    }
+
+    fn len(self) -> usize {
+        self.line_order().len()
+    }
+
+    // The neighbour a line's neighbour `index` swaps with in a mirror image
+    fn reflect(self, index: usize) -> usize {
+        let n = self.len();
+        match self {
+            TableNeighbourhood::OneDimensional => n - 1 - index,
+            _ => (n - index) % n,
+        }
+    }
+}
+
+/// A picture of a cell state, drawn in place of the plain colour when zoomed in.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Icon {
+    /// Width and height in pixels.
+    pub size: u32,
+    /// RGBA pixels row by row, transparent where the background shows through.
+    pub pixels: Vec<[u8; 4]>,
+}
+
+impl Icon {
+    // Whether every visible pixel is a shade of grey
+    fn is_grey(&self) -> bool {
+        self.pixels.iter().all(|&[red, green, blue, alpha]| alpha == 0 || (red == green && green == blue))
+    }
+
+    // The average colour of the visible pixels
+    fn average(&self) -> Option<(u8, u8, u8)> {
+        let visible: Vec<_> = self.pixels.iter().filter(|pixel| pixel[3] != 0).collect();
+        if visible.is_empty() {
+            return None;
+        }
+        let mean = |channel: usize| (visible.iter().map(|pixel| u64::from(pixel[channel])).sum::<u64>() / visible.len() as u64) as u8;
+        Some((mean(0), mean(1), mean(2)))
+    }
+}
+
+/// A rule read from a Golly `.rule` file, compiled to a decision tree over
+/// the states of each cell's neighbours, with the colours and icons of its
+/// states.
+///
+/// Cells whose neighbourhood matches no transition keep their state.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RuleTable {
+    name: String,
+    states: u8,
+    neighbourhood: TableNeighbourhood,
+    // Nodes of `states` entries each, the root last. An entry is the node
+    // for the next neighbour's state or, at the last level, the new state.
+    nodes: Vec<u32>,
+    colours: Vec<Option<(u8, u8, u8)>>,
+    icons: Vec<Vec<Icon>>, // Sets of one icon per state from 1 up, by size
+    source: String,
+}
+
+impl RuleTable {
+    /// The name given after `@RULE`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Number of cell states, counting state 0.
+    pub fn states(&self) -> u8 {
+        self.states
+    }
+
+    pub fn neighbourhood(&self) -> TableNeighbourhood {
+        self.neighbourhood
+    }
+
+    /// The colour `@COLORS` gives `state`, or failing that the average
+    /// colour of its icon.
+    pub fn colour(&self, state: u8) -> Option<(u8, u8, u8)> {
+        self.colours.get(usize::from(state)).copied().flatten()
+    }
+
+    /// Whether the file has icons for the states.
+    pub fn has_icons(&self) -> bool {
+        !self.icons.is_empty()
+    }
+
+    /// The icon of `state` closest to `size` pixels: the smallest at least
+    /// that large, or the largest there is.
+    pub fn icon(&self, state: u8, size: u32) -> Option<&Icon> {
+        let set = self.icons.iter().find(|set| set[0].size >= size).or(self.icons.last())?;
+        set.get(usize::from(state).checked_sub(1)?)
+    }
+
+    /// The text of the rule file, e.g. to save it along with a session.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The next state of the cell at `(x, y)`, reading the states of it and
+    /// its neighbours through `state`.
+    pub fn next(&self, x: i64, y: i64, state: impl Fn(i64, i64) -> u8) -> u8 {
+        let states = usize::from(self.states);
+        let mut node = self.nodes.len() / states - 1;
+        for &(dx, dy) in self.neighbourhood.offsets() {
+            node = self.nodes[node * states + usize::from(state(x + dx, y + dy))] as usize;
+        }
+        self.nodes[node * states + usize::from(state(x, y))] as u8
+    }
+}
+
+/// Error returned when a rule file cannot be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseRuleTableError {
+    /// The file lacks a required section: `@RULE`, or both `@TABLE` and `@TREE`.
+    Missing(&'static str),
+    /// A line does not follow the format; `line` is 1-based.
+    Malformed { line: usize, message: String },
+}
+
+impl ParseRuleTableError {
+    fn malformed(line: usize, message: impl Into<String>) -> Self {
+        ParseRuleTableError::Malformed {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ParseRuleTableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseRuleTableError::Missing(section) => write!(f, "the rule file has no {} section", section),
+            ParseRuleTableError::Malformed { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for ParseRuleTableError {}
+
+// The lines of one `@` section, numbered from 1
+struct Section<'a> {
+    name: &'a str,
+    header: &'a str, // The rest of the `@` line
+    lines: Vec<(usize, &'a str)>,
+}
+
+impl FromStr for RuleTable {
+    type Err = ParseRuleTableError;
+
+    /// Reads a Golly `.rule` file.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut sections: Vec<Section> = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            if let Some(tag) = line.trim().strip_prefix('@') {
+                let (name, header) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+                sections.push(Section {
+                    name,
+                    header: header.trim(),
+                    lines: Vec::new(),
+                });
+            } else if let Some(section) = sections.last_mut() {
+                section.lines.push((index + 1, line));
+            }
+        }
+        let section = |name: &str| sections.iter().find(|section| section.name == name);
+
+        let name = section("RULE")
+            .map(|section| section.header)
+            .filter(|name| !name.is_empty())
+            .ok_or(ParseRuleTableError::Missing("@RULE"))?;
+
+        // The first of @TABLE and @TREE wins, as in Golly
+        let transitions = sections
+            .iter()
+            .find(|section| section.name == "TABLE" || section.name == "TREE")
+            .ok_or(ParseRuleTableError::Missing("@TABLE or @TREE"))?;
+        let (states, neighbourhood, nodes) = if transitions.name == "TABLE" {
+            parse_table(&transitions.lines)?
+        } else {
+            parse_tree(&transitions.lines)?
+        };
+
+        let mut colours = vec![None; usize::from(states)];
+        if let Some(section) = section("COLORS") {
+            parse_colours(&section.lines, &mut colours)?;
+        }
+        let mut icons = match section("ICONS") {
+            Some(section) => parse_icons(&section.lines)?,
+            None => Vec::new(),
+        };
+        icons.sort_by_key(|set: &Vec<Icon>| set[0].size);
+        // States beyond the last icon of a set share that icon, as in Golly
+        for set in &mut icons {
+            let last = set[set.len() - 1].clone();
+            set.resize(usize::from(states) - 1, last);
+        }
+
+        // Grey icons take the colour of their state; states without a colour
+        // take that of their coloured icons
+        for set in &mut icons {
+            let grey = set.iter().all(Icon::is_grey);
+            for (icon, colour) in set.iter_mut().zip(colours.iter_mut().skip(1)) {
+                match *colour {
+                    Some((red, green, blue)) if grey => {
+                        for pixel in &mut icon.pixels {
+                            let shade = u16::from(pixel[0]);
+                            let tint = |channel: u8| (u16::from(channel) * shade / 255) as u8;
+                            *pixel = [tint(red), tint(green), tint(blue), pixel[3]];
+                        }
+                    }
+                    None if !grey => *colour = icon.average(),
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            states,
+            neighbourhood,
+            nodes,
+            colours,
+            icons,
+            source: text.to_string(),
+        })
+    }
+}
+
+// A line with any `#` comment removed
+fn strip_comment(line: &str) -> &str {
+    line.split_once('#').map_or(line, |(line, _)| line).trim()
+}
+
+// A set of states, one bit each
+type States = [u64; 4];
+
+// The states in a set, in order
+fn members(set: &States) -> impl Iterator<Item = usize> + '_ {
+    set.iter().enumerate().flat_map(|(word, &bits)| {
+        let mut bits = bits;
+        std::iter::from_fn(move || {
+            let bit = (bits != 0).then(|| bits.trailing_zeros() as usize)?;
+            bits &= bits - 1;
+            Some(word * 64 + bit)
+        })
+    })
+}
+
+// A term of a transition line: a state or a variable, by index
+#[derive(Clone, Copy, PartialEq)]
+enum Term {
+    State(u8),
+    Variable(usize),
+}
+
+// A transition with its terms in the tree's order, the cell itself last
+struct Transition {
+    inputs: Vec<States>,
+    output: u8,
+    open_from: usize, // The terms from this one on match any state
+}
+
+// How a table's transitions apply to the rotations and reflections of the
+// neighbourhood
+enum Symmetries {
+    // Maps from each neighbour of a line to where it moves, one per variant
+    Maps(Vec<Vec<usize>>),
+    // Every ordering of the neighbours
+    Permute,
+}
+
+impl Symmetries {
+    fn parse(symmetries: &str, neighbourhood: TableNeighbourhood) -> Option<Self> {
+        let n = neighbourhood.len();
+        let rotations = |count: usize| -> Option<Vec<Vec<usize>>> {
+            (count >= 1 && n.is_multiple_of(count)).then(|| {
+                (0..count).map(|turn| (0..n).map(|index| (index + turn * n / count) % n).collect()).collect()
+            })
+        };
+        let mut maps = match symmetries {
+            "permute" => return Some(Symmetries::Permute),
+            "none" => rotations(1)?,
+            "reflect" | "reflect_horizontal" => {
+                vec![(0..n).collect(), (0..n).map(|index| neighbourhood.reflect(index)).collect()]
+            }
+            _ => {
+                let rotate = symmetries.strip_prefix("rotate")?;
+                let (count, reflect) = match rotate.strip_suffix("reflect") {
+                    Some(count) => (count, true),
+                    None => (rotate, false),
+                };
+                let mut maps = rotations(count.parse().ok()?)?;
+                if reflect {
+                    let reflected: Vec<Vec<usize>> = maps
+                        .iter()
+                        .map(|map| (0..n).map(|index| map[neighbourhood.reflect(index)]).collect())
+                        .collect();
+                    maps.extend(reflected);
+                }
+                maps
+            }
+        };
+        maps.dedup();
+        Some(Symmetries::Maps(maps))
+    }
+
+    // The distinct variants of a line's terms, the cell first, each in the
+    // tree's order
+    fn variants(&self, line: &[States], neighbourhood: TableNeighbourhood) -> Vec<Vec<States>> {
+        let (cell, neighbours) = (line[0], &line[1..]);
+        let to_tree = |neighbours: &[States]| -> Vec<States> {
+            let mut tree = vec![cell; neighbours.len() + 1];
+            for (&term, &position) in neighbours.iter().zip(neighbourhood.line_order()) {
+                tree[position] = term;
+            }
+            tree
+        };
+        match self {
+            Symmetries::Permute => permutations(neighbours).iter().map(|neighbours| to_tree(neighbours)).collect(),
+            Symmetries::Maps(maps) => {
+                let mut variants: Vec<Vec<States>> = Vec::with_capacity(maps.len());
+                for map in maps {
+                    let mut moved = neighbours.to_vec();
+                    for (&term, &target) in neighbours.iter().zip(map) {
+                        moved[target] = term;
+                    }
+                    let variant = to_tree(&moved);
+                    if !variants.contains(&variant) {
+                        variants.push(variant);
+                    }
+                }
+                variants
+            }
+        }
+    }
+}
+
+// Parse the body of a @TABLE section and compile it into a tree
+fn parse_table(lines: &[(usize, &str)]) -> Result<(u8, TableNeighbourhood, Vec<u32>), ParseRuleTableError> {
+    let mut states = None;
+    let mut neighbourhood = None;
+    let mut symmetries = "none".to_string();
+    let mut variables: Vec<(String, Vec<u8>)> = Vec::new();
+    let mut transitions = Vec::new();
+
+    for &(line_number, line) in lines {
+        let line = strip_comment(line);
+        if line.is_empty() {
+            continue;
+        }
+        let malformed = |message: String| ParseRuleTableError::malformed(line_number, message);
+
+        if let Some((key, value)) = line.split_once(':') {
+            let value = value.trim();
+            match key.trim() {
+                "n_states" => {
+                    states = match value.parse::<usize>() {
+                        Ok(states) if (2..=MAX_STATES).contains(&states) => Some(states as u8),
+                        _ => return Err(malformed(format!("'{}' is not a number of states between 2 and {}", value, MAX_STATES))),
+                    }
+                }
+                "neighborhood" => {
+                    neighbourhood = Some(match value.to_ascii_lowercase().as_str() {
+                        "vonneumann" => TableNeighbourhood::VonNeumann,
+                        "moore" => TableNeighbourhood::Moore,
+                        "hexagonal" => TableNeighbourhood::Hexagonal,
+                        "onedimensional" => TableNeighbourhood::OneDimensional,
+                        _ => return Err(malformed(format!("unknown neighborhood '{}'", value))),
+                    })
+                }
+                "symmetries" => symmetries = value.to_string(),
+                _ => return Err(malformed(format!("unknown setting '{}'", key.trim()))),
+            }
+            continue;
+        }
+
+        let (Some(states), Some(neighbourhood)) = (states, neighbourhood) else {
+            return Err(malformed("n_states and neighborhood must come before variables and transitions".to_string()));
+        };
+
+        if let Some(declaration) = line.strip_prefix("var ") {
+            let (name, values) = declaration
+                .split_once('=')
+                .ok_or_else(|| malformed(format!("'{}' is not a variable such as var a={{0,1}}", line)))?;
+            let values = values.trim();
+            let values = values
+                .strip_prefix('{')
+                .and_then(|values| values.strip_suffix('}'))
+                .ok_or_else(|| malformed(format!("the values of '{}' must be in braces", name.trim())))?;
+            let mut set = Vec::new();
+            for value in values.split(',').map(str::trim) {
+                match parse_term(value, states, &variables) {
+                    Some(Term::State(state)) => set.push(state),
+                    Some(Term::Variable(index)) => set.extend_from_slice(&variables[index].1),
+                    None => return Err(malformed(format!("'{}' is neither a state nor a variable", value))),
+                }
+            }
+            let name = name.trim().to_string();
+            variables.retain(|(existing, _)| *existing != name);
+            variables.push((name, set));
+            continue;
+        }
+
+        // Tables with ten states or fewer may leave out the commas
+        let tokens: Vec<String> = if line.contains(',') {
+            line.split(',').map(|token| token.trim().to_string()).collect()
+        } else if states <= 10 {
+            line.chars().filter(|c| !c.is_whitespace()).map(String::from).collect()
+        } else {
+            line.split_whitespace().map(str::to_string).collect()
+        };
+        let expected = neighbourhood.len() + 2;
+        if tokens.len() != expected {
+            return Err(malformed(format!(
+                "a transition needs {} terms, the cell, its neighbours and the new state, but has {}",
+                expected,
+                tokens.len()
+            )));
+        }
+        let terms = tokens
+            .iter()
+            .map(|token| parse_term(token, states, &variables).ok_or_else(|| malformed(format!("'{}' is neither a state nor a variable", token))))
+            .collect::<Result<Vec<Term>, _>>()?;
+
+        let symmetries = Symmetries::parse(&symmetries, neighbourhood)
+            .ok_or_else(|| malformed(format!("unknown symmetries '{}' for this neighborhood", symmetries)))?;
+        let bound = bind(&terms, &variables).map_err(|name| malformed(format!("the new state '{}' is not among the terms before it", name)))?;
+        let any = set_of(&(0..states).collect::<Vec<u8>>());
+        for (inputs, output) in bound {
+            for inputs in symmetries.variants(&inputs, neighbourhood) {
+                let open_from = inputs.iter().rposition(|&set| set != any).map_or(0, |index| index + 1);
+                transitions.push(Transition { inputs, output, open_from });
+            }
+        }
+    }
+
+    let (Some(states), Some(neighbourhood)) = (states, neighbourhood) else {
+        let line = lines.last().map_or(0, |&(line, _)| line);
+        return Err(ParseRuleTableError::malformed(line, "the table sets no n_states or neighborhood"));
+    };
+    let mut builder = TreeBuilder {
+        transitions: &transitions,
+        states: usize::from(states),
+        inputs: neighbourhood.len() + 1,
+        nodes: Vec::new(),
+        by_candidates: HashMap::new(),
+        by_children: HashMap::new(),
+    };
+    builder.node(0, (0..transitions.len() as u32).collect());
+    Ok((states, neighbourhood, builder.nodes))
+}
+
+// A state number below `states` or the name of a variable
+fn parse_term(token: &str, states: u8, variables: &[(String, Vec<u8>)]) -> Option<Term> {
+    if let Some(index) = variables.iter().position(|(name, _)| name == token) {
+        return Some(Term::Variable(index));
+    }
+    token.parse::<u8>().ok().filter(|&state| state < states).map(Term::State)
+}
+
+// Expand the variables of a line into sets of states, in the line's order,
+// and the new state. A variable that appears more than once, or as the new
+// state, stands for the same state wherever it appears, so the line becomes
+// one transition per value of each such variable. Fails with the name of a
+// variable that only appears as the new state.
+fn bind(terms: &[Term], variables: &[(String, Vec<u8>)]) -> Result<Vec<(Vec<States>, u8)>, String> {
+    let (inputs, output) = terms.split_at(terms.len() - 1);
+    let output = output[0];
+    let uses = |term: Term| inputs.iter().filter(|&&input| input == term).count();
+    if let Term::Variable(index) = output {
+        if uses(output) == 0 {
+            return Err(variables[index].0.clone());
+        }
+    }
+
+    let mut bound: Vec<usize> = inputs
+        .iter()
+        .filter_map(|&term| match term {
+            Term::Variable(index) if uses(term) > 1 || term == output => Some(index),
+            _ => None,
+        })
+        .collect();
+    bound.sort_unstable();
+    bound.dedup();
+
+    // Every combination of values of the bound variables
+    let mut combinations: Vec<Vec<u8>> = vec![Vec::new()];
+    for &index in &bound {
+        combinations = combinations
+            .into_iter()
+            .flat_map(|combination| {
+                variables[index].1.iter().map(move |&value| [&combination[..], &[value]].concat())
+            })
+            .collect();
+    }
+
+    let transitions = combinations
+        .into_iter()
+        .map(|values| {
+            let states = |term: Term| -> Vec<u8> {
+                match term {
+                    Term::State(state) => vec![state],
+                    Term::Variable(index) => match bound.iter().position(|&b| b == index) {
+                        Some(position) => vec![values[position]],
+                        None => variables[index].1.clone(),
+                    },
+                }
+            };
+            let inputs = inputs.iter().map(|&term| set_of(&states(term))).collect();
+            (inputs, states(output)[0])
+        })
+        .collect();
+    Ok(transitions)
+}
+
+fn set_of(states: &[u8]) -> States {
+    states.iter().fold([0; 4], |set, &state| {
+        let mut set = set;
+        set[usize::from(state / 64)] |= 1 << (state % 64);
+        set
+    })
+}
+
+// The distinct orderings of `items`
+fn permutations(items: &[States]) -> Vec<Vec<States>> {
+    // Number equal items alike and step through the orderings of the numbers
+    let mut ids: Vec<usize> = items.iter().map(|item| items.iter().position(|other| other == item).unwrap_or(0)).collect();
+    ids.sort_unstable();
+    let mut orderings = Vec::new();
+    loop {
+        orderings.push(ids.iter().map(|&id| items[id]).collect());
+        // The next ordering in lexicographic order, if any
+        let Some(pivot) = (1..ids.len()).rev().find(|&index| ids[index - 1] < ids[index]) else {
+            return orderings;
+        };
+        let swap = (pivot..ids.len()).rev().find(|&index| ids[index] > ids[pivot - 1]).unwrap_or(pivot);
+        ids.swap(pivot - 1, swap);
+        ids[pivot..].reverse();
+    }
+}
+
+// Builds the decision tree for a table, one level per term, sharing nodes
+// that lead to the same outcomes
+struct TreeBuilder<'a> {
+    transitions: &'a [Transition],
+    states: usize,
+    inputs: usize,
+    nodes: Vec<u32>,
+    by_candidates: HashMap<(usize, Vec<u32>), u32>,
+    by_children: HashMap<(usize, Vec<u32>), u32>,
+}
+
+impl TreeBuilder<'_> {
+    // The node deciding term `input` among the transitions `candidates`,
+    // which match every term before it
+    fn node(&mut self, input: usize, candidates: Vec<u32>) -> u32 {
+        let transitions = self.transitions;
+        let children: Vec<u32> = if input + 1 == self.inputs {
+            // The first transition that matches decides; with none, the cell
+            // keeps its state
+            let mut children = vec![None; self.states];
+            for &index in &candidates {
+                let transition = &transitions[index as usize];
+                for state in members(&transition.inputs[input]) {
+                    children[state].get_or_insert(u32::from(transition.output));
+                }
+            }
+            children.iter().enumerate().map(|(state, child)| child.unwrap_or(state as u32)).collect()
+        } else {
+            let key = (input, candidates);
+            if let Some(&node) = self.by_candidates.get(&key) {
+                return node;
+            }
+
+            // Sort the candidates by the states they allow for this term.
+            // Once a transition matches whatever the later terms are, those
+            // after it can no longer decide anything.
+            let mut matching = vec![Vec::new(); self.states];
+            let mut settled = vec![false; self.states];
+            for &index in &key.1 {
+                let transition = &transitions[index as usize];
+                for state in members(&transition.inputs[input]) {
+                    if !settled[state] {
+                        matching[state].push(index);
+                        settled[state] = transition.open_from <= input + 1;
+                    }
+                }
+            }
+            let children = matching.into_iter().map(|matching| self.node(input + 1, matching)).collect();
+            let node = self.intern(input, children);
+            self.by_candidates.insert(key, node);
+            return node;
+        };
+        self.intern(input, children)
+    }
+
+    // The node with these children, added unless there is one already
+    fn intern(&mut self, input: usize, children: Vec<u32>) -> u32 {
+        let key = (input, children);
+        if let Some(&node) = self.by_children.get(&key) {
+            return node;
+        }
+        let node = (self.nodes.len() / self.states) as u32;
+        self.nodes.extend_from_slice(&key.1);
+        self.by_children.insert(key, node);
+        node
+    }
+}
+
+// Parse the body of a @TREE section: its settings, then one node per line
+// as its level and the entry for each state, leaves at level 1
+fn parse_tree(lines: &[(usize, &str)]) -> Result<(u8, TableNeighbourhood, Vec<u32>), ParseRuleTableError> {
+    let mut states = None;
+    let mut neighbourhood = None;
+    let mut levels = Vec::new();
+    let mut nodes = Vec::new();
+
+    for &(line_number, line) in lines {
+        let line = strip_comment(line);
+        if line.is_empty() {
+            continue;
+        }
+        let malformed = |message: String| ParseRuleTableError::malformed(line_number, message);
+
+        if let Some((key, value)) = line.split_once('=') {
+            let value = value.trim();
+            match key.trim() {
+                "num_states" => {
+                    states = match value.parse::<usize>() {
+                        Ok(states) if (2..=MAX_STATES).contains(&states) => Some(states as u8),
+                        _ => return Err(malformed(format!("'{}' is not a number of states between 2 and {}", value, MAX_STATES))),
+                    }
+                }
+                "num_neighbors" => {
+                    neighbourhood = Some(match value {
+                        "4" => TableNeighbourhood::VonNeumann,
+                        "8" => TableNeighbourhood::Moore,
+                        _ => return Err(malformed(format!("a tree has 4 or 8 neighbors, not '{}'", value))),
+                    })
+                }
+                // The nodes are counted as they are read
+                "num_nodes" => {}
+                _ => return Err(malformed(format!("unknown setting '{}'", key.trim()))),
+            }
+            continue;
+        }
+
+        let Some(states) = states else {
+            return Err(malformed("num_states must come before the nodes".to_string()));
+        };
+        let numbers = line
+            .split_whitespace()
+            .map(|number| number.parse::<u32>().map_err(|_| malformed(format!("'{}' is not a number", number))))
+            .collect::<Result<Vec<u32>, _>>()?;
+        let Some((&level, children)) = numbers.split_first() else {
+            continue;
+        };
+        if children.len() != usize::from(states) {
+            return Err(malformed(format!("a node needs its level and {} entries, one per state", states)));
+        }
+        for &child in children {
+            let valid = if level == 1 {
+                child < u32::from(states)
+            } else {
+                levels.get(child as usize) == Some(&(level - 1))
+            };
+            if !valid {
+                return Err(malformed(format!("{} is not a valid entry for a node at level {}", child, level)));
+            }
+        }
+        levels.push(level);
+        nodes.extend_from_slice(children);
+    }
+
+    let line = lines.last().map_or(0, |&(line, _)| line);
+    let (Some(states), Some(neighbourhood)) = (states, neighbourhood) else {
+        return Err(ParseRuleTableError::malformed(line, "the tree sets no num_states or num_neighbors"));
+    };
+    // The root comes last and looks at every neighbour and the cell
+    if levels.last() != Some(&(neighbourhood.len() as u32 + 1)) {
+        return Err(ParseRuleTableError::malformed(line, "the last node must be the root, at the level above every neighbor"));
+    }
+    Ok((states, neighbourhood, nodes))
+}
+
+// Parse a @COLORS section: `state red green blue` lines, or
+// `red green blue red green blue` for a gradient from state 1 to the last
+fn parse_colours(lines: &[(usize, &str)], colours: &mut [Option<(u8, u8, u8)>]) -> Result<(), ParseRuleTableError> {
+    for &(line_number, line) in lines {
+        let line = strip_comment(line);
+        if line.is_empty() {
+            continue;
+        }
+        let numbers = line
+            .split_whitespace()
+            .map(|number| number.parse::<u8>())
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| ParseRuleTableError::malformed(line_number, format!("'{}' is not a state and a colour", line)))?;
+        match numbers[..] {
+            [state, red, green, blue] => {
+                if let Some(colour) = colours.get_mut(usize::from(state)) {
+                    *colour = Some((red, green, blue));
+                }
+            }
+            [red1, green1, blue1, red2, green2, blue2] => {
+                let last = colours.len() - 1;
+                for (state, colour) in colours.iter_mut().enumerate().skip(1) {
+                    let t = if last > 1 { (state - 1) as f64 / (last - 1) as f64 } else { 0.0 };
+                    let mix = |first: u8, last: u8| (f64::from(first) + (f64::from(last) - f64::from(first)) * t).round() as u8;
+                    *colour = Some((mix(red1, red2), mix(green1, green2), mix(blue1, blue2)));
+                }
+            }
+            _ => return Err(ParseRuleTableError::malformed(line_number, format!("'{}' is not a state and a colour", line))),
+        }
+    }
+    Ok(())
+}
+
+// Parse an @ICONS section: XPM images of one icon per state stacked from
+// the top, or Golly's built-in `circles` and `diamonds`. Black pixels are
+// transparent, as in Golly.
+fn parse_icons(lines: &[(usize, &str)]) -> Result<Vec<Vec<Icon>>, ParseRuleTableError> {
+    // The quoted strings of each image, with the line of each
+    let mut images: Vec<Vec<(usize, &str)>> = Vec::new();
+    let mut sets = Vec::new();
+    for &(line_number, line) in lines {
+        let line = line.trim();
+        match line {
+            "XPM" => images.push(Vec::new()),
+            "circles" | "diamonds" => sets.push(vec![built_in_icon(line)]),
+            _ => {
+                let quoted = line.strip_prefix('"').and_then(|line| line.rsplit_once('"')).map(|(quoted, _)| quoted);
+                if let (Some(quoted), Some(image)) = (quoted, images.last_mut()) {
+                    image.push((line_number, quoted));
+                }
+            }
+        }
+    }
+
+    for image in images {
+        let Some(&(header_line, header)) = image.first() else {
+            continue;
+        };
+        let malformed = |line: usize, message: &str| ParseRuleTableError::malformed(line, message);
+        let numbers = header
+            .split_whitespace()
+            .map(|number| number.parse::<usize>())
+            .collect::<Result<Vec<usize>, _>>()
+            .map_err(|_| malformed(header_line, "an XPM image starts with its width, height, colours and characters per pixel"))?;
+        let [width, height, colour_count, chars] = numbers[..] else {
+            return Err(malformed(header_line, "an XPM image starts with its width, height, colours and characters per pixel"));
+        };
+        if width == 0 || height == 0 || chars == 0 || height % width != 0 || image.len() < 1 + colour_count + height {
+            return Err(malformed(header_line, "an XPM image of icons must hold square icons stacked from the top"));
+        }
+
+        let mut palette = HashMap::new();
+        for &(line, entry) in &image[1..=colour_count] {
+            let (key, rest) = entry.split_at_checked(chars).ok_or_else(|| malformed(line, "an XPM colour is too short"))?;
+            let value = rest.split_whitespace().skip_while(|&word| word != "c").nth(1);
+            let pixel = value.and_then(parse_xpm_colour).ok_or_else(|| malformed(line, "an XPM colour must be #RRGGBB or None"))?;
+            palette.insert(key, pixel);
+        }
+
+        let mut pixels = Vec::with_capacity(width * height);
+        for &(line, row) in &image[1 + colour_count..1 + colour_count + height] {
+            for index in 0..width {
+                let pixel = row
+                    .get(index * chars..(index + 1) * chars)
+                    .and_then(|key| palette.get(key))
+                    .ok_or_else(|| malformed(line, "an XPM row is too short or uses a colour it does not define"))?;
+                pixels.push(*pixel);
+            }
+        }
+        sets.push(
+            pixels
+                .chunks(width * width)
+                .map(|pixels| Icon {
+                    size: width as u32,
+                    pixels: pixels.to_vec(),
+                })
+                .collect(),
+        );
+    }
+    Ok(sets)
+}
+
+// An XPM colour as an RGBA pixel
+fn parse_xpm_colour(colour: &str) -> Option<[u8; 4]> {
+    let rgb = match colour.to_ascii_lowercase().as_str() {
+        "none" => return Some([0; 4]),
+        "black" => [0, 0, 0],
+        "white" => [255, 255, 255],
+        hex => {
+            let hex = hex.strip_prefix('#')?;
+            let digits = hex.len() / 3;
+            if !matches!(digits, 2 | 4) || hex.len() % 3 != 0 {
+                return None;
+            }
+            // Longer forms keep the high byte of each channel
+            let channel = |index: usize| u8::from_str_radix(hex.get(index * digits..index * digits + 2)?, 16).ok();
+            [channel(0)?, channel(1)?, channel(2)?]
+        }
+    };
+    let alpha = if rgb == [0, 0, 0] { 0 } else { 255 };
+    Some([rgb[0], rgb[1], rgb[2], alpha])
+}
+
+// A white circle or diamond for every state, tinted like grey icons
+fn built_in_icon(shape: &str) -> Icon {
+    let centre = BUILT_IN_ICON_SIZE / 2;
+    let pixels = (0..BUILT_IN_ICON_SIZE * BUILT_IN_ICON_SIZE)
+        .map(|index| {
+            let (dx, dy) = (index % BUILT_IN_ICON_SIZE - centre, index / BUILT_IN_ICON_SIZE - centre);
+            let inside = match shape {
+                "circles" => dx * dx + dy * dy <= centre * centre + centre,
+                _ => dx.abs() + dy.abs() <= centre,
+            };
+            if inside { [255; 4] } else { [0; 4] }
+        })
+        .collect();
+    Icon {
+        size: BUILT_IN_ICON_SIZE as u32,
+        pixels,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::rle;
+    use crate::{Engine, Pattern, Rule, Universe};
+
+    // Dead cells next to a 1 are born, or next to an opposite pair of 2s
+    // with 0s between them; 1s become 2s; 2s between opposite 0s and 2s die
+    const TESTER: &str = "\
+@RULE Tester
+@TABLE
+n_states:3
+neighborhood:vonNeumann
+symmetries:rotate4
+var a={0,1,2}
+var b={0,1,2}
+var c={0,1,2}
+var d={0,1,2}
+var x={1,2}
+0,1,a,b,c,1
+0,x,0,x,0,2
+1,a,b,c,d,2
+2,2,0,2,0,0
+";
+
+    fn table(text: &str) -> RuleTable {
+        text.parse().unwrap_or_else(|err| panic!("{}\n{}", text, err))
+    }
+
+    // Every way to give `neighbours` cells one of `states` states
+    fn neighbourhoods(states: u8, neighbours: usize) -> impl Iterator<Item = Vec<u8>> {
+        let states = usize::from(states);
+        (0..states.pow(neighbours as u32)).map(move |mut index| {
+            (0..neighbours)
+                .map(|_| {
+                    let state = (index % states) as u8;
+                    index /= states;
+                    state
+                })
+                .collect()
+        })
+    }
+
+    // The next state of a cell in `state`, its neighbours in the order of
+    // `TableNeighbourhood::offsets`
+    fn next(table: &RuleTable, state: u8, neighbours: &[u8]) -> u8 {
+        let offsets = table.neighbourhood().offsets();
+        table.next(0, 0, |x, y| offsets.iter().position(|&offset| offset == (x, y)).map_or(state, |index| neighbours[index]))
+    }
+
+    // Add a node giving `next` for the neighbourhoods starting with `prefix`
+    // to the lines of a @TREE, without sharing any, returning its number
+    fn tree_node(lines: &mut Vec<String>, states: u8, neighbours: usize, prefix: &mut Vec<u8>, next: &dyn Fn(u8, &[u8]) -> u8) -> usize {
+        let entries: Vec<String> = (0..states)
+            .map(|state| {
+                if prefix.len() == neighbours {
+                    next(state, prefix).to_string()
+                } else {
+                    prefix.push(state);
+                    let child = tree_node(lines, states, neighbours, prefix, next);
+                    prefix.pop();
+                    child.to_string()
+                }
+            })
+            .collect();
+        lines.push(format!("{} {}", neighbours + 1 - prefix.len(), entries.join(" ")));
+        lines.len() - 1
+    }
+
+    #[test]
+    fn tables_compile_to_their_tree() {
+        // `TESTER` written out by hand, its neighbours as N W E S
+        let expected = |cell: u8, neighbours: &[u8]| {
+            let &[n, w, e, s] = neighbours else { unreachable!() };
+            let opposite = |pair: u8| (n, s, e, w) == (pair, pair, 0, 0) || (n, s, e, w) == (0, 0, pair, pair);
+            match cell {
+                0 if neighbours.contains(&1) => 1,
+                0 if opposite(2) => 2,
+                1 => 2,
+                2 if opposite(2) => 0,
+                _ => cell,
+            }
+        };
+        let mut lines = Vec::new();
+        tree_node(&mut lines, 3, 4, &mut Vec::new(), &expected);
+        let tree = table(&format!(
+            "@RULE Tester\n@TREE\nnum_states=3\nnum_neighbors=4\nnum_nodes={}\n{}\n",
+            lines.len(),
+            lines.join("\n")
+        ));
+
+        let compiled = table(TESTER);
+        assert_eq!(compiled.states(), 3);
+        assert_eq!(compiled.neighbourhood(), TableNeighbourhood::VonNeumann);
+        for neighbours in neighbourhoods(3, 4) {
+            for cell in 0..3 {
+                let want = expected(cell, &neighbours);
+                assert_eq!(next(&tree, cell, &neighbours), want, "tree, cell {} among {:?}", cell, neighbours);
+                assert_eq!(next(&compiled, cell, &neighbours), want, "table, cell {} among {:?}", cell, neighbours);
+            }
+        }
+    }
+
+    #[test]
+    fn symmetries_give_every_variant_of_a_transition() {
+        // A line's neighbours, clockwise from N, that no rotation or
+        // reflection maps onto themselves, and the variants of each mode
+        type Case = (&'static str, &'static [u8], &'static [(&'static str, usize)]);
+        let cases: [Case; 4] = [
+            (
+                "vonNeumann",
+                &[1, 2, 0, 0],
+                &[("none", 1), ("rotate4", 4), ("rotate4reflect", 8), ("reflect_horizontal", 2), ("permute", 12)],
+            ),
+            (
+                "Moore",
+                &[1, 1, 0, 1, 0, 0, 0, 0],
+                &[
+                    ("none", 1),
+                    ("rotate4", 4),
+                    ("rotate4reflect", 8),
+                    ("rotate8", 8),
+                    ("rotate8reflect", 16),
+                    ("reflect_horizontal", 2),
+                    ("permute", 56),
+                ],
+            ),
+            (
+                "hexagonal",
+                &[1, 1, 0, 1, 0, 0],
+                &[("none", 1), ("rotate2", 2), ("rotate3", 3), ("rotate6", 6), ("rotate6reflect", 12), ("permute", 20)],
+            ),
+            ("oneDimensional", &[1, 0], &[("none", 1), ("reflect", 2), ("permute", 2)]),
+        ];
+
+        for (neighbourhood, line, modes) in cases {
+            let states = line.iter().max().map_or(2, |&max| (max + 1).max(2));
+            let terms: Vec<String> = line.iter().map(u8::to_string).collect();
+            for &(symmetries, variants) in modes {
+                let table = table(&format!(
+                    "@RULE Symmetric\n@TABLE\nn_states:{}\nneighborhood:{}\nsymmetries:{}\n0,{},1\n",
+                    states,
+                    neighbourhood,
+                    symmetries,
+                    terms.join(",")
+                ));
+                let born = neighbourhoods(states, line.len()).filter(|neighbours| next(&table, 0, neighbours) == 1).count();
+                assert_eq!(born, variants, "{} {}", neighbourhood, symmetries);
+            }
+        }
+
+        // The variants are the turned and mirrored neighbourhoods
+        let born_among = |symmetries: &str, cells: &[(i64, i64)]| {
+            let table = table(&format!(
+                "@RULE Symmetric\n@TABLE\nn_states:2\nneighborhood:Moore\nsymmetries:{}\n0,1,1,0,1,0,0,0,0,1\n",
+                symmetries
+            ));
+            let neighbours: Vec<u8> = MOORE.iter().map(|offset| u8::from(cells.contains(offset))).collect();
+            next(&table, 0, &neighbours) == 1
+        };
+        let (n, ne, e, se, s, sw, w, nw) = ((0, -1), (1, -1), (1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1));
+        assert!(born_among("none", &[n, ne, se]));
+        assert!(born_among("rotate4", &[e, se, sw]));
+        assert!(!born_among("rotate4", &[n, nw, sw]));
+        assert!(born_among("reflect_horizontal", &[n, nw, sw]));
+        assert!(!born_among("reflect_horizontal", &[e, se, sw]));
+        assert!(born_among("rotate8", &[ne, e, s]));
+        assert!(born_among("rotate4reflect", &[w, sw, se]));
+    }
+
+    #[test]
+    fn hexagonal_tables_read_golly_order() {
+        // A line's neighbours go N E SE S W NW on the skewed hexagonal lattice
+        let table = table("@RULE Hex\n@TABLE\nn_states:2\nneighborhood:hexagonal\nsymmetries:none\n0,1,0,1,0,0,0,1\n");
+        let (n, se) = ((0, -1), (1, 1));
+        let neighbours: Vec<u8> = HEXAGONAL.iter().map(|offset| u8::from([n, se].contains(offset))).collect();
+        assert_eq!(next(&table, 0, &neighbours), 1);
+    }
+
+    #[test]
+    fn colour_gradients_run_from_state_one_to_the_last() {
+        let gradient = table(
+            "@RULE Gradient\n@TABLE\nn_states:5\nneighborhood:Moore\nsymmetries:none\n\
+             @COLORS\n255 0 0 0 0 255\n3 0 255 0  # a single state after the gradient\n",
+        );
+        assert_eq!(gradient.colour(0), None);
+        assert_eq!(gradient.colour(1), Some((255, 0, 0)));
+        assert_eq!(gradient.colour(2), Some((170, 0, 85)));
+        assert_eq!(gradient.colour(3), Some((0, 255, 0)));
+        assert_eq!(gradient.colour(4), Some((0, 0, 255)));
+        assert_eq!(gradient.colour(5), None);
+
+        let two_states = table("@RULE Single\n@TABLE\nn_states:2\nneighborhood:Moore\n@COLORS\n10 20 30 40 50 60\n");
+        assert_eq!(two_states.colour(1), Some((10, 20, 30)));
+    }
+
+    #[test]
+    fn xpm_icons_are_read_state_by_state() {
+        let table = table(
+            "@RULE Icons\n@TABLE\nn_states:4\nneighborhood:vonNeumann\nsymmetries:none\n\
+             @COLORS\n2 0 0 255\n\
+             @ICONS\nXPM\n/* width height num_colors chars_per_pixel */\n\"3 6 3 1\"\n\
+             \". c #000000\"\n\"A c #FF0000\"\n\"B c #FFFFFF\"\n\
+             /* state 1 */\n\"A.A\"\n\".A.\"\n\"A.A\"\n/* state 2 */\n\"BBB\"\n\"B.B\"\n\"BBB\"\n",
+        );
+        let (red, white, clear) = ([255, 0, 0, 255], [255; 4], [0; 4]);
+        assert!(table.has_icons());
+        let icon = table.icon(1, 3).expect("an icon for state 1");
+        assert_eq!(icon.size, 3);
+        assert_eq!(icon.pixels, [red, clear, red, clear, red, clear, red, clear, red]);
+        assert_eq!(table.icon(2, 3).map(|icon| icon.pixels[4]), Some(clear));
+        assert_eq!(table.icon(2, 3).map(|icon| icon.pixels[0]), Some(white));
+        // Later states share the last icon, and any size gets the nearest set
+        assert_eq!(table.icon(3, 3), table.icon(2, 3));
+        assert_eq!(table.icon(1, 31), table.icon(1, 3));
+        assert_eq!(table.icon(0, 3), None);
+        // States without a colour take that of their icon
+        assert_eq!(table.colour(1), Some((255, 0, 0)));
+        assert_eq!(table.colour(2), Some((0, 0, 255)));
+        assert_eq!(table.colour(3), Some((255, 255, 255)));
+    }
+
+    #[test]
+    fn grey_icons_take_their_state_colour() {
+        let table = table(
+            "@RULE Tinted\n@TABLE\nn_states:3\nneighborhood:vonNeumann\nsymmetries:none\n\
+             @COLORS\n1 255 0 0\n2 0 255 0\n\
+             @ICONS\nXPM\n\"2 2 2 1\"\n\". c None\"\n\"G c #808080\"\n\"G.\"\n\".G\"\n",
+        );
+        let clear = [0; 4];
+        assert_eq!(table.icon(1, 2).map(|icon| icon.pixels.clone()), Some(vec![[128, 0, 0, 255], clear, clear, [128, 0, 0, 255]]));
+        assert_eq!(table.icon(2, 2).map(|icon| icon.pixels[0]), Some([0, 128, 0, 255]));
+    }
+
+    #[test]
+    fn table_rules_survive_rle_and_their_own_source() {
+        let rule = Rule::from_table(table(TESTER));
+        let mut universe = Universe::new(10, 10);
+        universe.set_rule(rule.clone());
+        for (x, y, state) in [(1, 1, 1), (2, 1, 2), (3, 4, 2), (5, 5, 1)] {
+            universe.set_state(x, y, state);
+        }
+        let saved = Pattern::from_universe(&universe);
+
+        // RLE names the table, which has to be loaded again to use it
+        let read = rle::read(&rle::write(&saved)).expect("the RLE reads back");
+        assert_eq!(read.rule, None);
+        assert_eq!(read.rule_name.as_deref(), Some("Tester"));
+        assert_eq!(read.cells(), saved.cells());
+
+        // A table read back from its source is the same rule, and cells
+        // written without one take on the rule they are stamped under
+        let restored = Rule::from_table(table(rule.table().expect("a rule table").source()));
+        assert_eq!(restored, rule);
+        let mut cells = saved.clone();
+        cells.rule = None;
+        let cells = rle::read(&rle::write(&cells)).expect("the RLE reads back");
+        let mut reopened = Universe::new(10, 10);
+        reopened.set_rule(restored);
+        cells.stamp(&mut reopened, 1, 1);
+        assert_eq!(Pattern::from_universe(&reopened).cells(), saved.cells());
+        assert_eq!(reopened.get_state(3, 4), 2);
+    }
+}
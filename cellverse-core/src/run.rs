@@ -0,0 +1,85 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use crate::Stats;
+
+// Longest population cycle recognised as stable, in samples
+const MAX_STABLE_PERIOD: usize = 30;
+
+// Samples the population must repeat over before a run counts as stable
+const STABLE_WINDOW: usize = 3 * MAX_STABLE_PERIOD;
+
+/// When a run started with [`RunUntil`] should stop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StopCondition {
+    /// At this generation.
+    Generation(u64),
+    /// Once the population stays constant or cycles with a short period.
+    Stable,
+    /// Once fewer than this many cells are alive.
+    PopulationBelow(u64),
+}
+
+impl fmt::Display for StopCondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StopCondition::Generation(generation) => write!(f, "generation {}", generation),
+            StopCondition::Stable => f.write_str("the population stabilises"),
+            StopCondition::PopulationBelow(population) => write!(f, "fewer than {} cells are alive", population),
+        }
+    }
+}
+
+/// Watches the statistics of a run for its stop condition.
+#[derive(Debug, Clone)]
+pub struct RunUntil {
+    condition: StopCondition,
+    populations: VecDeque<u64>, // Most recent populations, newest last
+}
+
+impl RunUntil {
+    pub fn new(condition: StopCondition) -> Self {
+        Self {
+            condition,
+            populations: VecDeque::with_capacity(STABLE_WINDOW),
+        }
+    }
+
+    pub fn condition(&self) -> StopCondition {
+        self.condition
+    }
+
+    /// How many generations may be taken in one go without passing the
+    /// stop condition, out of the `generations` wanted.
+    pub fn limit(&self, stats: Stats, generations: u64) -> u64 {
+        match self.condition {
+            StopCondition::Generation(target) => generations.min(target.saturating_sub(stats.generation)),
+            _ => generations,
+        }
+    }
+
+    /// Feeds the statistics after a step and returns whether the run should stop.
+    pub fn is_done(&mut self, stats: Stats) -> bool {
+        match self.condition {
+            StopCondition::Generation(target) => stats.generation >= target,
+            StopCondition::PopulationBelow(population) => stats.population < population,
+            StopCondition::Stable => {
+                if self.populations.len() == STABLE_WINDOW {
+                    self.populations.pop_front();
+                }
+                self.populations.push_back(stats.population);
+                self.populations.len() == STABLE_WINDOW && self.is_cycling()
+            }
+        }
+    }
+
+    // Whether the recorded populations repeat with some short period
+    fn is_cycling(&self) -> bool {
+        (1..=MAX_STABLE_PERIOD).any(|period| {
+            self.populations
+                .iter()
+                .zip(self.populations.iter().skip(period))
+                .all(|(earlier, later)| earlier == later)
+        })
+    }
+}
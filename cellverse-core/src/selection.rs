@@ -0,0 +1,149 @@
+use rand::Rng;
+
+use crate::{Engine, PasteMode, Pattern, Universe};
+
+/// A rectangle of cells, `left <= x < right` and `top <= y < bottom`, that
+/// editing operations apply to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Selection {
+    pub left: i64,
+    pub top: i64,
+    pub right: i64,
+    pub bottom: i64,
+}
+
+impl Selection {
+    /// The rectangle spanned by two opposite corner cells, both included.
+    pub fn from_corners((x0, y0): (i64, i64), (x1, y1): (i64, i64)) -> Self {
+        Self {
+            left: x0.min(x1),
+            top: y0.min(y1),
+            right: x0.max(x1) + 1,
+            bottom: y0.max(y1) + 1,
+        }
+    }
+
+    pub fn width(&self) -> u64 {
+        (self.right - self.left).max(0) as u64
+    }
+
+    pub fn height(&self) -> u64 {
+        (self.bottom - self.top).max(0) as u64
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width() == 0 || self.height() == 0
+    }
+
+    pub fn contains(&self, x: i64, y: i64) -> bool {
+        (self.left..self.right).contains(&x) && (self.top..self.bottom).contains(&y)
+    }
+
+    /// The part of the selection that lies inside a `width` x `height` grid.
+    pub fn clipped(&self, width: usize, height: usize) -> Self {
+        Self {
+            left: self.left.max(0),
+            top: self.top.max(0),
+            right: self.right.min(width as i64),
+            bottom: self.bottom.min(height as i64),
+        }
+    }
+
+    /// The cells inside the selection that are not dead as a pattern the
+    /// size of the selection, with the universe's rule.
+    pub fn copy(&self, universe: &Universe) -> Pattern {
+        let mut cells = Vec::new();
+        universe.for_each_cell_in(self.left, self.top, self.right, self.bottom, &mut |x, y, state| {
+            cells.push((x - self.left, y - self.top, state));
+        });
+        let mut pattern = Pattern::from_placed_cells(cells);
+        pattern.set_size(self.width(), self.height());
+        pattern.rule = Some(universe.rule());
+        pattern
+    }
+
+    /// Kills every cell inside the selection.
+    pub fn clear(&self, universe: &mut Universe) {
+        for (x, y) in self.occupied_cells(universe, true) {
+            universe.set_cell(x, y, false);
+        }
+    }
+
+    /// Kills every cell outside the selection.
+    pub fn clear_outside(&self, universe: &mut Universe) {
+        for (x, y) in self.occupied_cells(universe, false) {
+            universe.set_cell(x, y, false);
+        }
+    }
+
+    /// Replaces the cells inside the selection with random ones, each alive
+    /// with probability `density`.
+    pub fn randomize(&self, universe: &mut Universe, density: f64) {
+        let mut rng = rand::rng();
+        let density = density.clamp(0.0, 1.0);
+        for y in self.top..self.bottom {
+            for x in self.left..self.right {
+                universe.set_cell(x, y, rng.random_bool(density));
+            }
+        }
+    }
+
+    /// The smallest selection holding the same cells that are not dead, or
+    /// `None` if there are none.
+    pub fn shrunk_to_fit(&self, universe: &Universe) -> Option<Self> {
+        let mut bounds: Option<Self> = None;
+        universe.for_each_cell_in(self.left, self.top, self.right, self.bottom, &mut |x, y, _| {
+            let cell = Self::from_corners((x, y), (x, y));
+            bounds = Some(match bounds {
+                None => cell,
+                Some(bounds) => Self {
+                    left: bounds.left.min(cell.left),
+                    top: bounds.top.min(cell.top),
+                    right: bounds.right.max(cell.right),
+                    bottom: bounds.bottom.max(cell.bottom),
+                },
+            });
+        });
+        bounds
+    }
+
+    /// Turns the contents a quarter turn clockwise about the selection's
+    /// centre and returns the turned selection.
+    pub fn rotate_clockwise(&self, universe: &mut Universe) -> Self {
+        let pattern = self.copy(universe).rotated_clockwise();
+        self.clear(universe);
+
+        let left = self.left + (self.width() as i64 - pattern.width() as i64) / 2;
+        let top = self.top + (self.height() as i64 - pattern.height() as i64) / 2;
+        pattern.paste(universe, left, top, PasteMode::Or);
+        Self {
+            left,
+            top,
+            right: left + pattern.width() as i64,
+            bottom: top + pattern.height() as i64,
+        }
+    }
+
+    /// Mirrors the contents left to right.
+    pub fn flip_horizontally(&self, universe: &mut Universe) {
+        let pattern = self.copy(universe).flipped_horizontally();
+        pattern.paste(universe, self.left, self.top, PasteMode::Copy);
+    }
+
+    /// Mirrors the contents top to bottom.
+    pub fn flip_vertically(&self, universe: &mut Universe) {
+        let pattern = self.copy(universe).flipped_vertically();
+        pattern.paste(universe, self.left, self.top, PasteMode::Copy);
+    }
+
+    // Cells that are not dead inside the selection, or outside it
+    fn occupied_cells(&self, universe: &Universe, inside: bool) -> Vec<(i64, i64)> {
+        let mut cells = Vec::new();
+        if inside {
+            universe.for_each_cell_in(self.left, self.top, self.right, self.bottom, &mut |x, y, _| cells.push((x, y)));
+        } else {
+            cells.extend(universe.cells().into_iter().map(|(x, y, _)| (x, y)).filter(|&(x, y)| !self.contains(x, y)));
+        }
+        cells
+    }
+}
@@ -0,0 +1,385 @@
+//! Sparse storage for the unbounded plane.
+//!
+//! Cells live in 64x64 tiles keyed by tile coordinates. A tile is created
+//! when a cell inside it comes alive and freed as soon as it is empty, so
+//! memory follows the population rather than the extent of the pattern.
+//! The few dying cells of Generations rules are kept apart, by cell, as are
+//! the cells of rule tables in states 2 and up.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::bitgrid::RuleMasks;
+use crate::{LargerThanLife, Lattice, Rule, RuleTable};
+
+const TILE_SIZE: i64 = 64;
+
+// One bit per cell, one word per row
+type Tile = [u64; TILE_SIZE as usize];
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SparseGrid {
+    tiles: HashMap<(i64, i64), Box<Tile>>,
+    dying: HashMap<(i64, i64), u8>, // Cells in states 2 and up
+}
+
+impl SparseGrid {
+    pub(crate) fn state(&self, x: i64, y: i64) -> u8 {
+        if self.get(x, y) {
+            1
+        } else {
+            self.dying.get(&(x, y)).copied().unwrap_or(0)
+        }
+    }
+
+    pub(crate) fn set_state(&mut self, x: i64, y: i64, state: u8) {
+        self.set(x, y, state == 1);
+        if state >= 2 {
+            self.dying.insert((x, y), state);
+        } else {
+            self.dying.remove(&(x, y));
+        }
+    }
+
+    fn get(&self, x: i64, y: i64) -> bool {
+        let (key, column, row) = split(x, y);
+        self.tiles
+            .get(&key)
+            .is_some_and(|tile| tile[row] & (1 << column) != 0)
+    }
+
+    fn set(&mut self, x: i64, y: i64, alive: bool) {
+        let (key, column, row) = split(x, y);
+        if alive {
+            self.tiles.entry(key).or_insert_with(|| Box::new([0; TILE_SIZE as usize]))[row] |= 1 << column;
+        } else if let Some(tile) = self.tiles.get_mut(&key) {
+            tile[row] &= !(1 << column);
+            if tile.iter().all(|&word| word == 0) {
+                self.tiles.remove(&key);
+            }
+        }
+    }
+
+    pub(crate) fn population(&self) -> u64 {
+        self.tiles
+            .values()
+            .flat_map(|tile| tile.iter())
+            .map(|word| word.count_ones() as u64)
+            .sum()
+    }
+
+    // Approximate heap memory used by the tiles, in bytes
+    pub(crate) fn memory_size(&self) -> usize {
+        self.tiles.len() * (std::mem::size_of::<Tile>() + std::mem::size_of::<((i64, i64), Box<Tile>)>())
+            + self.dying.len() * std::mem::size_of::<((i64, i64), u8)>()
+    }
+
+    pub(crate) fn for_each_live(&self, f: &mut dyn FnMut(i64, i64)) {
+        for (&(tile_x, tile_y), tile) in &self.tiles {
+            for (row, &word) in tile.iter().enumerate() {
+                let mut bits = word;
+                while bits != 0 {
+                    let column = bits.trailing_zeros() as i64;
+                    f(tile_x * TILE_SIZE + column, tile_y * TILE_SIZE + row as i64);
+                    bits &= bits - 1;
+                }
+            }
+        }
+    }
+
+    // Like `for_each_live`, but only visits tiles overlapping the rectangle
+    pub(crate) fn for_each_live_in(&self, left: i64, top: i64, right: i64, bottom: i64, f: &mut dyn FnMut(i64, i64)) {
+        if left >= right || top >= bottom {
+            return;
+        }
+        let (first, _, _) = split(left, top);
+        let (last, _, _) = split(right - 1, bottom - 1);
+
+        let mut visit = |(tile_x, tile_y): (i64, i64), tile: &Tile| {
+            for (row, &word) in tile.iter().enumerate() {
+                let y = tile_y * TILE_SIZE + row as i64;
+                if y < top || y >= bottom {
+                    continue;
+                }
+                let mut bits = word;
+                while bits != 0 {
+                    let x = tile_x * TILE_SIZE + bits.trailing_zeros() as i64;
+                    if (left..right).contains(&x) {
+                        f(x, y);
+                    }
+                    bits &= bits - 1;
+                }
+            }
+        };
+
+        // Look small windows up tile by tile; scan the map for large ones
+        let span = (last.0 - first.0 + 1).saturating_mul(last.1 - first.1 + 1);
+        if span <= self.tiles.len() as i64 {
+            for tile_y in first.1..=last.1 {
+                for tile_x in first.0..=last.0 {
+                    if let Some(tile) = self.tiles.get(&(tile_x, tile_y)) {
+                        visit((tile_x, tile_y), tile);
+                    }
+                }
+            }
+        } else {
+            for (&key, tile) in &self.tiles {
+                if (first.0..=last.0).contains(&key.0) && (first.1..=last.1).contains(&key.1) {
+                    visit(key, tile);
+                }
+            }
+        }
+    }
+
+    // Dying cells in the rectangle, with their states
+    pub(crate) fn for_each_dying_in(&self, left: i64, top: i64, right: i64, bottom: i64, f: &mut dyn FnMut(i64, i64, u8)) {
+        for (&(x, y), &state) in &self.dying {
+            if (left..right).contains(&x) && (top..bottom).contains(&y) {
+                f(x, y, state);
+            }
+        }
+    }
+
+    /// Advances one generation, returning the number of births and deaths.
+    /// Empty space is never born, so rules containing `B0` behave as if it
+    /// were absent.
+    pub(crate) fn step(&mut self, rule: &Rule) -> (u64, u64) {
+        if let Some(table) = rule.table() {
+            return self.step_table(table);
+        }
+        if let Some(range) = rule.range() {
+            return self.step_range(rule, range);
+        }
+        if rule.lattice() != Lattice::Square {
+            return self.step_lattice(rule, rule.lattice());
+        }
+        let masks = RuleMasks::new(rule).without_b0();
+
+        // Dying cells cannot be born into, so mask them out tile by tile
+        let mut dying_tiles: HashMap<(i64, i64), Tile> = HashMap::new();
+        for &(x, y) in self.dying.keys() {
+            let (key, column, row) = split(x, y);
+            dying_tiles.entry(key).or_insert([0; TILE_SIZE as usize])[row] |= 1 << column;
+        }
+
+        // Only tiles that are alive or touch a live tile can change
+        let candidates: HashSet<(i64, i64)> = self
+            .tiles
+            .keys()
+            .flat_map(|&(tile_x, tile_y)| {
+                (-1..=1).flat_map(move |dy| (-1..=1).map(move |dx| (tile_x + dx, tile_y + dy)))
+            })
+            .collect();
+
+        // Dying cells age by one state, until they are dead
+        let mut dying: HashMap<(i64, i64), u8> = self
+            .dying
+            .iter()
+            .filter(|&(_, &state)| state < rule.states() - 1)
+            .map(|(&cell, &state)| (cell, state + 1))
+            .collect();
+
+        let mut births = 0;
+        let mut deaths = 0;
+        let mut next = HashMap::with_capacity(self.tiles.len());
+        for key in candidates {
+            let (tile, tile_births, tile_deaths) = self.step_tile(key, &masks, dying_tiles.get(&key));
+            births += tile_births;
+            deaths += tile_deaths;
+
+            // Cells that stopped living start dying
+            if rule.states() > 2 {
+                if let Some(old) = self.tiles.get(&key) {
+                    for (row, (&before, &after)) in old.iter().zip(tile.iter()).enumerate() {
+                        let mut bits = before & !after;
+                        while bits != 0 {
+                            let column = bits.trailing_zeros() as i64;
+                            dying.insert((key.0 * TILE_SIZE + column, key.1 * TILE_SIZE + row as i64), 2);
+                            bits &= bits - 1;
+                        }
+                    }
+                }
+            }
+            if tile.iter().any(|&word| word != 0) {
+                next.insert(key, Box::new(tile));
+            }
+        }
+
+        self.tiles = next;
+        self.dying = dying;
+        (births, deaths)
+    }
+
+    // Advance under a Larger than Life rule. Each tile that can change is
+    // counted from a window reaching `radius` cells into its neighbours.
+    fn step_range(&mut self, rule: &Rule, range: &LargerThanLife) -> (u64, u64) {
+        let radius = i64::from(range.radius);
+        let reach = (radius + TILE_SIZE - 1) / TILE_SIZE;
+
+        // Tiles in reach of a live cell, and those holding dying cells
+        let mut candidates: HashSet<(i64, i64)> = self
+            .tiles
+            .keys()
+            .flat_map(|&(tile_x, tile_y)| {
+                (-reach..=reach).flat_map(move |dy| (-reach..=reach).map(move |dx| (tile_x + dx, tile_y + dy)))
+            })
+            .collect();
+        candidates.extend(self.dying.keys().map(|&(x, y)| split(x, y).0));
+
+        let side = (TILE_SIZE + 2 * radius) as usize;
+        let mut births = 0;
+        let mut deaths = 0;
+        let mut next = SparseGrid::default();
+        for (tile_x, tile_y) in candidates {
+            let (left, top) = (tile_x * TILE_SIZE, tile_y * TILE_SIZE);
+            let mut alive = vec![0; side * side];
+            self.for_each_live_in(left - radius, top - radius, left + TILE_SIZE + radius, top + TILE_SIZE + radius, &mut |x, y| {
+                alive[(y - top + radius) as usize * side + (x - left + radius) as usize] = 1;
+            });
+            let counts = range.counts(&alive, side, side);
+
+            for (index, &count) in counts.iter().enumerate() {
+                let (x, y) = (left + index as i64 % TILE_SIZE, top + index as i64 / TILE_SIZE);
+                let state = self.state(x, y);
+                // Empty space is never born, as in `step`
+                let new_state = if state == 0 && count == 0 { 0 } else { rule.next_by_count(state, count) };
+                match (state == 1, new_state == 1) {
+                    (true, false) => deaths += 1,
+                    (false, true) => births += 1,
+                    _ => {}
+                }
+                if new_state != 0 {
+                    next.set_state(x, y, new_state);
+                }
+            }
+        }
+
+        *self = next;
+        (births, deaths)
+    }
+
+    // Advance under a rule for the hexagonal or triangular lattice, cell by
+    // cell: every live cell adds itself to the counts of its neighbours
+    fn step_lattice(&mut self, rule: &Rule, lattice: Lattice) -> (u64, u64) {
+        let mut counts: HashMap<(i64, i64), u32> = HashMap::new();
+        self.for_each_live(&mut |x, y| {
+            counts.entry((x, y)).or_insert(0);
+            for &(dx, dy) in lattice.neighbour_offsets(x, y) {
+                *counts.entry((x + dx, y + dy)).or_insert(0) += 1;
+            }
+        });
+        for &cell in self.dying.keys() {
+            counts.entry(cell).or_insert(0);
+        }
+
+        let mut births = 0;
+        let mut deaths = 0;
+        let mut next = SparseGrid::default();
+        for ((x, y), count) in counts {
+            let state = self.state(x, y);
+            let new_state = rule.next_by_count(state, count);
+            match (state == 1, new_state == 1) {
+                (true, false) => deaths += 1,
+                (false, true) => births += 1,
+                _ => {}
+            }
+            if new_state != 0 {
+                next.set_state(x, y, new_state);
+            }
+        }
+
+        *self = next;
+        (births, deaths)
+    }
+
+    // Advance under a rule table, cell by cell: only the cells in a state
+    // other than 0 and their neighbours can change
+    fn step_table(&mut self, table: &RuleTable) -> (u64, u64) {
+        let mut candidates = HashSet::new();
+        let mut add = |x: i64, y: i64| {
+            candidates.insert((x, y));
+            for &(dx, dy) in table.neighbourhood().offsets() {
+                candidates.insert((x + dx, y + dy));
+            }
+        };
+        self.for_each_live(&mut |x, y| add(x, y));
+        for &(x, y) in self.dying.keys() {
+            add(x, y);
+        }
+
+        let mut births = 0;
+        let mut deaths = 0;
+        let mut next = SparseGrid::default();
+        for (x, y) in candidates {
+            let state = self.state(x, y);
+            let new_state = table.next(x, y, |x, y| self.state(x, y));
+            match (state == 1, new_state == 1) {
+                (true, false) => deaths += 1,
+                (false, true) => births += 1,
+                _ => {}
+            }
+            if new_state != 0 {
+                next.set_state(x, y, new_state);
+            }
+        }
+
+        *self = next;
+        (births, deaths)
+    }
+
+    fn step_tile(&self, (tile_x, tile_y): (i64, i64), masks: &RuleMasks, dying: Option<&Tile>) -> (Tile, u64, u64) {
+        // The tile and its eight neighbours, indexed [dy + 1][dx + 1]
+        let around: [[Option<&Tile>; 3]; 3] = std::array::from_fn(|dy| {
+            std::array::from_fn(|dx| {
+                self.tiles
+                    .get(&(tile_x + dx as i64 - 1, tile_y + dy as i64 - 1))
+                    .map(|tile| &**tile)
+            })
+        });
+
+        // Row `row` (-1 to 64) of the tile column `dx`, reaching into the
+        // tiles above and below at the edges
+        let word = |dx: usize, row: i64| -> u64 {
+            let (dy, row) = match row {
+                -1 => (0, TILE_SIZE - 1),
+                TILE_SIZE => (2, 0),
+                _ => (1, row),
+            };
+            around[dy][dx].map_or(0, |tile| tile[row as usize])
+        };
+
+        // The row's cells together with their west and east neighbours
+        let shifted = |row: i64| -> (u64, u64, u64) {
+            let (west, centre, east) = (word(0, row), word(1, row), word(2, row));
+            ((centre << 1) | (west >> 63), centre, (centre >> 1) | (east << 63))
+        };
+
+        let mut next = [0; TILE_SIZE as usize];
+        let mut births = 0;
+        let mut deaths = 0;
+        for (row, out) in next.iter_mut().enumerate() {
+            let row = row as i64;
+            let (above_w, above, above_e) = shifted(row - 1);
+            let (current_w, current, current_e) = shifted(row);
+            let (below_w, below, below_e) = shifted(row + 1);
+
+            let (born, survive) = masks.apply([above_w, above, above_e, current_w, current_e, below_w, below, below_e]);
+            let alive = current;
+            let dying = dying.map_or(0, |dying| dying[row as usize]);
+            *out = (alive & survive) | (!alive & !dying & born);
+
+            births += (*out & !alive).count_ones() as u64;
+            deaths += (alive & !*out).count_ones() as u64;
+        }
+
+        (next, births, deaths)
+    }
+}
+
+// Split a cell coordinate into its tile key and position inside the tile
+fn split(x: i64, y: i64) -> ((i64, i64), usize, usize) {
+    (
+        (x.div_euclid(TILE_SIZE), y.div_euclid(TILE_SIZE)),
+        x.rem_euclid(TILE_SIZE) as usize,
+        y.rem_euclid(TILE_SIZE) as usize,
+    )
+}
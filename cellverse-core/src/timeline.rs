@@ -0,0 +1,150 @@
+//! Keyframes of a run, for scrubbing back and forth through its generations.
+//!
+//! A copy of the universe is kept every so many generations. Any generation
+//! up to the furthest one reached can then be recreated by stepping forward
+//! from the nearest keyframe before it. When the keyframes outgrow their
+//! memory limit every other one is dropped, so the timeline keeps covering
+//! the whole run, just more coarsely.
+
+use std::collections::BTreeMap;
+
+use crate::Universe;
+
+/// Generations between keyframes unless told otherwise.
+pub const DEFAULT_KEYFRAME_INTERVAL: u64 = 50;
+
+/// Memory the keyframes may use unless told otherwise, in bytes.
+pub const DEFAULT_MEMORY_LIMIT: usize = 128 * 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct Timeline {
+    keyframes: BTreeMap<u64, Universe>, // Keyed by generation
+    interval: u64,
+    latest: u64, // Furthest generation reached
+    memory_limit: usize,
+    memory_used: usize,
+}
+
+impl Timeline {
+    pub fn new(interval: u64, memory_limit: usize) -> Self {
+        Self {
+            keyframes: BTreeMap::new(),
+            interval: interval.max(1),
+            latest: 0,
+            memory_limit,
+            memory_used: 0,
+        }
+    }
+
+    /// Generations between keyframes; this doubles each time the keyframes
+    /// are thinned out to fit the memory limit.
+    pub fn interval(&self) -> u64 {
+        self.interval
+    }
+
+    /// The furthest generation reached, the end of the timeline.
+    pub fn latest(&self) -> u64 {
+        self.latest
+    }
+
+    /// Generations that have a keyframe, in order.
+    pub fn keyframes(&self) -> impl Iterator<Item = u64> + '_ {
+        self.keyframes.keys().copied()
+    }
+
+    /// The last keyframe at or before `generation`.
+    pub fn keyframe_before(&self, generation: u64) -> Option<u64> {
+        self.keyframes.range(..=generation).next_back().map(|(&key, _)| key)
+    }
+
+    /// Approximate memory held by the keyframes, in bytes.
+    pub fn memory_used(&self) -> usize {
+        self.memory_used
+    }
+
+    /// Notes the universe after it advanced, keeping it as a keyframe if the
+    /// last one before it is at least an interval back.
+    pub fn record(&mut self, universe: &Universe) {
+        let generation = universe.stats().generation;
+        self.reach(generation);
+
+        if self.keyframe_before(generation).is_none_or(|key| generation - key >= self.interval) {
+            self.insert(universe);
+        }
+    }
+
+    /// Extends the timeline to `generation` without a keyframe, for runs
+    /// that get there without the universe, such as HashLife's.
+    pub fn reach(&mut self, generation: u64) {
+        self.latest = self.latest.max(generation);
+    }
+
+    /// Starts the timeline over from `universe`, e.g. after it was edited:
+    /// every generation from its own on is forgotten.
+    pub fn reset_from(&mut self, universe: &Universe) {
+        let generation = universe.stats().generation;
+        for (_, keyframe) in self.keyframes.split_off(&generation) {
+            self.memory_used -= memory_size(&keyframe);
+        }
+        self.latest = generation;
+        self.insert(universe);
+    }
+
+    /// Recreates `generation`, stepping forward from the nearest keyframe, or
+    /// from `current` if that is closer. Returns `None` for generations past
+    /// the end of the timeline or before its first keyframe.
+    pub fn seek(&self, generation: u64, current: &Universe) -> Option<Universe> {
+        if generation > self.latest {
+            return None;
+        }
+
+        let keyframe = self.keyframes.range(..=generation).next_back();
+        let current_generation = current.stats().generation;
+        let mut universe = match keyframe {
+            Some((&key, _)) if key <= current_generation && current_generation <= generation => current.clone(),
+            Some((_, keyframe)) => keyframe.clone(),
+            None if current_generation <= generation => current.clone(),
+            None => return None,
+        };
+        while universe.stats().generation < generation {
+            universe.step();
+        }
+        Some(universe)
+    }
+
+    /// Forgets every keyframe.
+    pub fn clear(&mut self) {
+        self.keyframes.clear();
+        self.latest = 0;
+        self.memory_used = 0;
+    }
+
+    fn insert(&mut self, universe: &Universe) {
+        let generation = universe.stats().generation;
+        self.memory_used += memory_size(universe);
+        if let Some(replaced) = self.keyframes.insert(generation, universe.clone()) {
+            self.memory_used -= memory_size(&replaced);
+        }
+
+        // Thin out to every other keyframe, always keeping the first
+        while self.memory_used > self.memory_limit && self.keyframes.len() > 1 {
+            let dropped: Vec<u64> = self.keyframes.keys().copied().skip(1).step_by(2).collect();
+            for generation in dropped {
+                if let Some(keyframe) = self.keyframes.remove(&generation) {
+                    self.memory_used -= memory_size(&keyframe);
+                }
+            }
+            self.interval = self.interval.saturating_mul(2);
+        }
+    }
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Self::new(DEFAULT_KEYFRAME_INTERVAL, DEFAULT_MEMORY_LIMIT)
+    }
+}
+
+fn memory_size(universe: &Universe) -> usize {
+    std::mem::size_of::<Universe>() + universe.memory_size()
+}
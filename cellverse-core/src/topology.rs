@@ -0,0 +1,115 @@
+use std::fmt;
+
+/// How the edges of the universe behave.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Topology {
+    /// Edges wrap around to the opposite side.
+    #[default]
+    Torus,
+    /// A bounded plane surrounded by permanently dead cells.
+    Plane,
+    /// Left and right edges wrap; top and bottom are dead.
+    HorizontalCylinder,
+    /// Top and bottom edges wrap; left and right are dead.
+    VerticalCylinder,
+    /// Left and right edges wrap; top and bottom wrap with the x axis flipped.
+    KleinBottle,
+    /// Both pairs of edges wrap with the other axis flipped (the real
+    /// projective plane).
+    CrossSurface,
+    /// An infinite plane. The width and height only describe the home area
+    /// that is shown and randomized; patterns can travel indefinitely.
+    Unbounded,
+}
+
+impl Topology {
+    pub const ALL: [Topology; 7] = [
+        Topology::Torus,
+        Topology::Plane,
+        Topology::HorizontalCylinder,
+        Topology::VerticalCylinder,
+        Topology::KleinBottle,
+        Topology::CrossSurface,
+        Topology::Unbounded,
+    ];
+
+    /// Whether the left and right edges are joined.
+    pub fn wraps_x(self) -> bool {
+        matches!(
+            self,
+            Topology::Torus | Topology::HorizontalCylinder | Topology::KleinBottle | Topology::CrossSurface
+        )
+    }
+
+    /// Whether the top and bottom edges are joined.
+    pub fn wraps_y(self) -> bool {
+        matches!(
+            self,
+            Topology::Torus | Topology::VerticalCylinder | Topology::KleinBottle | Topology::CrossSurface
+        )
+    }
+
+    // Crossing the left/right seam mirrors y
+    fn twists_x(self) -> bool {
+        self == Topology::CrossSurface
+    }
+
+    // Crossing the top/bottom seam mirrors x
+    fn twists_y(self) -> bool {
+        matches!(self, Topology::KleinBottle | Topology::CrossSurface)
+    }
+
+    /// Whether a cell's distance from the edges along the x axis matters,
+    /// i.e. the axis is bounded by dead cells or joined with a twist.
+    pub fn x_edges_matter(self) -> bool {
+        !self.wraps_x() || self.twists_y()
+    }
+
+    /// Whether a cell's distance from the edges along the y axis matters.
+    pub fn y_edges_matter(self) -> bool {
+        !self.wraps_y() || self.twists_x()
+    }
+
+    /// Maps a possibly out-of-range coordinate onto a bounded `width` x
+    /// `height` grid, or `None` if it falls on a dead boundary.
+    pub fn wrap(self, x: i64, y: i64, width: usize, height: usize) -> Option<(usize, usize)> {
+        let (width, height) = (width as i64, height as i64);
+        let (mut x, mut y) = (x, y);
+
+        if !(0..width).contains(&x) {
+            if !self.wraps_x() {
+                return None;
+            }
+            x = x.rem_euclid(width);
+            if self.twists_x() {
+                y = height - 1 - y;
+            }
+        }
+
+        if !(0..height).contains(&y) {
+            if !self.wraps_y() {
+                return None;
+            }
+            y = y.rem_euclid(height);
+            if self.twists_y() {
+                x = width - 1 - x;
+            }
+        }
+
+        Some((x as usize, y as usize))
+    }
+}
+
+impl fmt::Display for Topology {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Topology::Torus => "Torus",
+            Topology::Plane => "Plane",
+            Topology::HorizontalCylinder => "Cylinder (horizontal)",
+            Topology::VerticalCylinder => "Cylinder (vertical)",
+            Topology::KleinBottle => "Klein bottle",
+            Topology::CrossSurface => "Cross-surface",
+            Topology::Unbounded => "Unbounded",
+        })
+    }
+}
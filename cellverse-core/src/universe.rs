@@ -0,0 +1,557 @@
+use rand::Rng;
+
+use crate::sparse::SparseGrid;
+use crate::{LargerThanLife, Lattice, Rule, Topology};
+
+// Initial grid dimensions that will grow over time
+pub const INITIAL_GRID_WIDTH: usize = 150;  // Initial width
+pub const INITIAL_GRID_HEIGHT: usize = 100; // Initial height
+pub const MAX_GRID_WIDTH: usize = 5000;      // Maximum width the grid can grow to
+pub const MAX_GRID_HEIGHT: usize = 3000;     // Maximum height the grid can grow to
+pub const GROWTH_INTERVAL: u64 = 50;        // How many updates before growing the grid
+pub const GROWTH_AMOUNT: usize = 1;         // How many cells to add in each direction when growing
+
+/// Running counters describing a universe.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Number of generations simulated since the last clear.
+    pub generation: u64,
+    /// Number of cells currently alive.
+    pub population: u64,
+    /// Total cells born, including cells switched on by hand.
+    pub births: u64,
+    /// Total cells died, including cells switched off by hand.
+    pub deaths: u64,
+}
+
+// Cell storage: a dense grid of cell states for bounded topologies, sparse
+// tiles for the plane
+#[derive(Debug, Clone)]
+enum Cells {
+    Dense(Vec<Vec<u8>>),
+    Sparse(SparseGrid),
+}
+
+/// A grid of cells evolving under a [`Rule`].
+#[derive(Debug, Clone)]
+pub struct Universe {
+    cells: Cells,
+    width: usize,
+    height: usize,
+    topology: Topology,
+    update_counter: u64, // Count updates for growth timing
+    auto_grow: bool, // Whether to auto-grow the universe
+    rule: Rule,
+    stats: Stats,
+}
+
+impl Universe {
+    /// Creates an empty universe of the given size.
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            cells: Cells::Dense(vec![vec![0; width]; height]),
+            width,
+            height,
+            topology: Topology::Torus,
+            update_counter: 0,
+            auto_grow: true,
+            rule: Rule::default(),
+            stats: Stats::default(),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    /// Restores the generation, birth and death counters, e.g. when
+    /// reloading a saved universe. The population is recounted from the cells.
+    pub fn set_stats(&mut self, stats: Stats) {
+        self.stats = stats;
+        self.update_population();
+    }
+
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    /// Switches topology. Moving from the unbounded plane to a bounded
+    /// topology keeps only the cells inside the home area.
+    pub fn set_topology(&mut self, topology: Topology) {
+        if topology == self.topology {
+            return;
+        }
+
+        let cells = self.cells();
+
+        self.topology = topology;
+        self.cells = match topology {
+            Topology::Unbounded => Cells::Sparse(SparseGrid::default()),
+            _ => Cells::Dense(vec![vec![0; self.width]; self.height]),
+        };
+        for (x, y, state) in cells {
+            self.put(x, y, state);
+        }
+        self.update_population();
+    }
+
+    pub fn rule(&self) -> Rule {
+        self.rule.clone()
+    }
+
+    /// Changes the rule used by subsequent steps. Dying cells in states the
+    /// new rule does not have are cleared.
+    pub fn set_rule(&mut self, rule: Rule) {
+        let states = rule.states();
+        self.rule = rule;
+        for (x, y, state) in self.cells() {
+            if state >= states {
+                self.put(x, y, 0);
+            }
+        }
+    }
+
+    pub fn auto_grow(&self) -> bool {
+        self.auto_grow
+    }
+
+    /// Enables or disables growing the universe every `GROWTH_INTERVAL` steps.
+    pub fn set_auto_grow(&mut self, auto_grow: bool) {
+        self.auto_grow = auto_grow;
+    }
+
+    /// Returns whether the cell at `(x, y)` is alive. Cells outside a bounded grid are dead.
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        self.cell(x as i64, y as i64)
+    }
+
+    /// Sets the cell at `(x, y)`, counting the change as a birth or death.
+    /// Coordinates outside a bounded grid are ignored.
+    pub fn set(&mut self, x: usize, y: usize, alive: bool) {
+        self.set_at(x as i64, y as i64, alive);
+    }
+
+    pub fn toggle(&mut self, x: usize, y: usize) {
+        let alive = self.get(x, y);
+        self.set(x, y, !alive);
+    }
+
+    /// Returns the state of the cell at `(x, y)`: 0 when dead, 1 when alive
+    /// and 2 and up while dying under a Generations rule.
+    pub fn state(&self, x: i64, y: i64) -> u8 {
+        match &self.cells {
+            Cells::Dense(grid) if self.contains(x, y) => grid[y as usize][x as usize],
+            Cells::Dense(_) => 0,
+            Cells::Sparse(plane) => plane.state(x, y),
+        }
+    }
+
+    /// Calls `f` with the coordinates of every live cell.
+    pub fn for_each_live(&self, f: &mut dyn FnMut(i64, i64)) {
+        match &self.cells {
+            Cells::Dense(grid) => {
+                for (y, row) in grid.iter().enumerate() {
+                    for (x, &cell) in row.iter().enumerate() {
+                        if cell == 1 {
+                            f(x as i64, y as i64);
+                        }
+                    }
+                }
+            }
+            Cells::Sparse(plane) => plane.for_each_live(f),
+        }
+    }
+
+    /// Calls `f` with the coordinates of every live cell in the rectangle
+    /// `left <= x < right`, `top <= y < bottom`, without visiting the rest of
+    /// the universe.
+    pub fn for_each_live_in(&self, left: i64, top: i64, right: i64, bottom: i64, f: &mut dyn FnMut(i64, i64)) {
+        match &self.cells {
+            Cells::Dense(grid) => {
+                let (left, right) = (left.max(0) as usize, right.clamp(0, self.width as i64) as usize);
+                let (top, bottom) = (top.max(0) as usize, bottom.clamp(0, self.height as i64) as usize);
+                for (y, row) in grid.iter().enumerate().take(bottom).skip(top) {
+                    for (x, &cell) in row.iter().enumerate().take(right).skip(left) {
+                        if cell == 1 {
+                            f(x as i64, y as i64);
+                        }
+                    }
+                }
+            }
+            Cells::Sparse(plane) => plane.for_each_live_in(left, top, right, bottom, f),
+        }
+    }
+
+    /// Like [`Universe::for_each_live_in`], but also visits dying cells,
+    /// calling `f` with the state of each cell that is not dead.
+    pub fn for_each_cell_in(&self, left: i64, top: i64, right: i64, bottom: i64, f: &mut dyn FnMut(i64, i64, u8)) {
+        match &self.cells {
+            Cells::Dense(grid) => {
+                let (left, right) = (left.max(0) as usize, right.clamp(0, self.width as i64) as usize);
+                let (top, bottom) = (top.max(0) as usize, bottom.clamp(0, self.height as i64) as usize);
+                for (y, row) in grid.iter().enumerate().take(bottom).skip(top) {
+                    for (x, &cell) in row.iter().enumerate().take(right).skip(left) {
+                        if cell != 0 {
+                            f(x as i64, y as i64, cell);
+                        }
+                    }
+                }
+            }
+            Cells::Sparse(plane) => {
+                plane.for_each_live_in(left, top, right, bottom, &mut |x, y| f(x, y, 1));
+                plane.for_each_dying_in(left, top, right, bottom, f);
+            }
+        }
+    }
+
+    // Every cell that is not dead, with its state
+    pub(crate) fn cells(&self) -> Vec<(i64, i64, u8)> {
+        let mut cells = Vec::new();
+        self.for_each_cell_in(i64::MIN, i64::MIN, i64::MAX, i64::MAX, &mut |x, y, state| cells.push((x, y, state)));
+        cells
+    }
+
+    pub(crate) fn cell(&self, x: i64, y: i64) -> bool {
+        self.state(x, y) == 1
+    }
+
+    // Set a cell with statistics bookkeeping
+    pub(crate) fn set_at(&mut self, x: i64, y: i64, alive: bool) {
+        self.set_state(x, y, u8::from(alive));
+    }
+
+    /// Puts the cell at `(x, y)` into `state`. Only cells coming alive or
+    /// leaving the live state count as births and deaths, so killing a
+    /// dying cell clears it without counting another death. States the rule
+    /// does not have and coordinates outside a bounded grid are ignored.
+    pub fn set_state(&mut self, x: i64, y: i64, state: u8) {
+        let was_alive = self.cell(x, y);
+        if state >= self.rule.states() || self.state(x, y) == state || !self.put(x, y, state) {
+            return;
+        }
+
+        // Update statistics
+        if state == 1 {
+            self.stats.population += 1;
+            self.stats.births += 1;
+        } else if was_alive {
+            self.stats.population -= 1;
+            self.stats.deaths += 1;
+        }
+    }
+
+    // Write a cell state without touching the statistics. Returns false if
+    // the cell lies outside a bounded grid.
+    fn put(&mut self, x: i64, y: i64, state: u8) -> bool {
+        let inside = self.contains(x, y);
+        match &mut self.cells {
+            Cells::Dense(grid) if inside => grid[y as usize][x as usize] = state,
+            Cells::Dense(_) => return false,
+            Cells::Sparse(plane) => plane.set_state(x, y, state),
+        }
+        true
+    }
+
+    // Put a cell into `state` without touching the statistics, returning the
+    // state it was in
+    pub(crate) fn swap_state(&mut self, x: i64, y: i64, state: u8) -> u8 {
+        let old = self.state(x, y);
+        self.put(x, y, state);
+        old
+    }
+
+    pub(crate) fn stats_mut(&mut self) -> &mut Stats {
+        &mut self.stats
+    }
+
+    // Whether both universes have the same size, topology and rule, so that
+    // they differ at most in their cells and counters
+    pub(crate) fn same_shape(&self, other: &Universe) -> bool {
+        (self.width, self.height, self.topology, &self.rule) == (other.width, other.height, other.topology, &other.rule)
+    }
+
+    // Approximate heap memory used by the cells, in bytes
+    pub(crate) fn memory_size(&self) -> usize {
+        match &self.cells {
+            Cells::Dense(grid) => grid.len() * (self.width + std::mem::size_of::<Vec<u8>>()),
+            Cells::Sparse(plane) => plane.memory_size(),
+        }
+    }
+
+    fn contains(&self, x: i64, y: i64) -> bool {
+        (0..self.width as i64).contains(&x) && (0..self.height as i64).contains(&y)
+    }
+
+    // Recount the living cells from scratch
+    fn update_population(&mut self) {
+        self.stats.population = match &self.cells {
+            Cells::Dense(grid) => grid.iter().flatten().filter(|&&cell| cell == 1).count() as u64,
+            Cells::Sparse(plane) => plane.population(),
+        };
+    }
+
+    pub fn randomize(&mut self) {
+        // Clear the grid first, keeping the counters
+        self.clear_cells();
+
+        // About 20% chance for a cell to be alive (less dense for better patterns)
+        self.fill_random(0..self.width, 0..self.height, 0.2);
+    }
+
+    /// Clears the grid and fills its middle third with random cells.
+    pub fn randomize_center(&mut self) {
+        // Clear the grid first, keeping the counters
+        self.clear_cells();
+
+        // Determine the center region (about 1/4 of the total area)
+        let start_x = self.width / 3;
+        let end_x = self.width * 2 / 3;
+        let start_y = self.height / 3;
+        let end_y = self.height * 2 / 3;
+
+        // About 30% chance for a cell to be alive in the center region
+        self.fill_random(start_x..end_x, start_y..end_y, 0.3);
+    }
+
+    fn fill_random(&mut self, xs: std::ops::Range<usize>, ys: std::ops::Range<usize>, density: f64) {
+        let mut rng = rand::rng();
+        for y in ys {
+            for x in xs.clone() {
+                self.put(x as i64, y as i64, u8::from(rng.random_bool(density)));
+            }
+        }
+        self.update_population();
+    }
+
+    /// Kills every cell and resets all counters.
+    pub fn clear(&mut self) {
+        self.clear_cells();
+
+        // Reset counters
+        self.update_counter = 0;
+        self.stats = Stats::default();
+    }
+
+    // Kill every cell without touching the counters
+    fn clear_cells(&mut self) {
+        match &mut self.cells {
+            Cells::Dense(grid) => {
+                for row in grid {
+                    row.fill(0);
+                }
+            }
+            Cells::Sparse(plane) => *plane = SparseGrid::default(),
+        }
+    }
+
+    /// Resizes the grid to `width` x `height`, keeping the existing cells centred.
+    /// Cells that no longer fit are discarded. On the unbounded plane only the
+    /// home area changes.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        if let Cells::Dense(grid) = &mut self.cells {
+            let mut new_grid = vec![vec![0; width]; height];
+
+            // Copy existing data to the center of the new grid. Triangles
+            // only keep their neighbours when moved by even amounts.
+            let even = matches!(self.rule.lattice(), Lattice::Triangular | Lattice::TriangularEdges);
+            let (src_x, dst_x, copy_w) = centered_span(self.width, width, even);
+            let (src_y, dst_y, copy_h) = centered_span(self.height, height, even);
+
+            for y in 0..copy_h {
+                new_grid[dst_y + y][dst_x..dst_x + copy_w]
+                    .copy_from_slice(&grid[src_y + y][src_x..src_x + copy_w]);
+            }
+
+            *grid = new_grid;
+        }
+
+        self.width = width;
+        self.height = height;
+        self.update_population();
+    }
+
+    // Swap in a new set of cells computed by another engine, counting cells
+    // that switched on or off as births and deaths
+    pub(crate) fn replace_cells(&mut self, cells: impl IntoIterator<Item = (i64, i64, u8)>, generation: u64) {
+        let mut old = Vec::new();
+        for (x, y, state) in self.cells() {
+            self.put(x, y, 0);
+            if state == 1 {
+                old.push((x, y));
+            }
+        }
+
+        let mut population = 0;
+        for (x, y, state) in cells {
+            if self.put(x, y, state) && state == 1 {
+                population += 1;
+            }
+        }
+        let survivors = old.iter().filter(|&&(x, y)| self.cell(x, y)).count() as u64;
+
+        self.stats.births += population - survivors;
+        self.stats.deaths += old.len() as u64 - survivors;
+        self.stats.generation = generation;
+        self.stats.population = population;
+    }
+
+    /// Grows the universe by `GROWTH_AMOUNT` cells, up to the maximum size.
+    /// Axes with dead or twisted edges grow on both sides so the pattern stays
+    /// centred between them. Returns whether the universe actually grew.
+    pub fn grow(&mut self) -> bool {
+        // Only grow if we're below the maximum size, and there is no edge to move on the plane
+        if self.topology == Topology::Unbounded
+            || self.width >= MAX_GRID_WIDTH || self.height >= MAX_GRID_HEIGHT {
+            return false;
+        }
+
+        // Calculate new dimensions
+        let growth = |edges_matter: bool| if edges_matter { 2 * GROWTH_AMOUNT } else { GROWTH_AMOUNT };
+        let new_width = (self.width + growth(self.topology.x_edges_matter())).min(MAX_GRID_WIDTH);
+        let new_height = (self.height + growth(self.topology.y_edges_matter())).min(MAX_GRID_HEIGHT);
+        self.resize(new_width, new_height);
+        true
+    }
+
+    /// Advances the universe by one generation.
+    pub fn step(&mut self) {
+        // Check if it's time to grow the universe
+        self.update_counter += 1;
+        self.stats.generation += 1;
+
+        if self.auto_grow && self.update_counter.is_multiple_of(GROWTH_INTERVAL) {
+            self.grow();
+        }
+
+        let (births, deaths) = match &mut self.cells {
+            Cells::Sparse(plane) => plane.step(&self.rule),
+            Cells::Dense(_) => self.step_dense(),
+        };
+
+        self.stats.births += births;
+        self.stats.deaths += deaths;
+        self.update_population();
+    }
+
+    fn step_dense(&mut self) -> (u64, u64) {
+        let Cells::Dense(grid) = &self.cells else {
+            return (0, 0);
+        };
+
+        // Larger than Life rules count over a wider area, for all cells at once
+        let counts = self.rule.range().map(|range| range_counts(grid, self.topology, range));
+        let lattice = self.rule.lattice();
+        // Rule tables read the state of every neighbour
+        let table = self.rule.table();
+        let (width, height) = (self.width, self.height);
+        let state_at = |x: i64, y: i64| self.topology.wrap(x, y, width, height).map_or(0, |(x, y)| grid[y][x]);
+
+        let mut new_grid = vec![vec![0; self.width]; self.height];
+        let mut births = 0;
+        let mut deaths = 0;
+
+        for (y, new_row) in new_grid.iter_mut().enumerate() {
+            for (x, new_cell) in new_row.iter_mut().enumerate() {
+                let state = grid[y][x];
+                *new_cell = match (&counts, table) {
+                    (Some(counts), _) => self.rule.next_by_count(state, counts[y * self.width + x]),
+                    (None, Some(table)) => table.next(x as i64, y as i64, state_at),
+                    (None, None) if lattice == Lattice::Square => self.rule.next(state, neighbourhood(grid, self.topology, x, y)),
+                    (None, None) => self.rule.next_by_count(state, lattice_count(grid, self.topology, lattice, x, y)),
+                };
+
+                // Births and deaths count cells coming alive and leaving life
+                match (state == 1, *new_cell == 1) {
+                    (true, false) => deaths += 1,
+                    (false, true) => births += 1,
+                    _ => {}
+                }
+            }
+        }
+
+        self.cells = Cells::Dense(new_grid);
+        (births, deaths)
+    }
+}
+
+// The live neighbours of a cell as a neighbourhood byte, NW in the high bit
+// and SE in the low bit as in `Rule`
+fn neighbourhood(grid: &[Vec<u8>], topology: Topology, x: usize, y: usize) -> u8 {
+    let (width, height) = (grid[0].len(), grid.len());
+    let mut neighbourhood = 0;
+
+    for dy in -1..=1 {
+        for dx in -1..=1 {
+            if dx == 0 && dy == 0 {
+                continue; // Skip the cell itself
+            }
+
+            // Neighbours across an edge depend on how the edges are joined
+            let neighbor = topology.wrap(x as i64 + dx, y as i64 + dy, width, height);
+            let alive = neighbor.is_some_and(|(nx, ny)| grid[ny][nx] == 1);
+            neighbourhood = (neighbourhood << 1) | u8::from(alive);
+        }
+    }
+
+    neighbourhood
+}
+
+// The live neighbours of a cell on the hexagonal or triangular lattice
+fn lattice_count(grid: &[Vec<u8>], topology: Topology, lattice: Lattice, x: usize, y: usize) -> u32 {
+    let (width, height) = (grid[0].len(), grid.len());
+    let (x, y) = (x as i64, y as i64);
+    lattice
+        .neighbour_offsets(x, y)
+        .iter()
+        .filter(|&&(dx, dy)| {
+            let neighbor = topology.wrap(x + dx, y + dy, width, height);
+            neighbor.is_some_and(|(nx, ny)| grid[ny][nx] == 1)
+        })
+        .count() as u32
+}
+
+// The live cells around every cell within the range of a Larger than Life
+// rule, row by row
+fn range_counts(grid: &[Vec<u8>], topology: Topology, range: &LargerThanLife) -> Vec<u32> {
+    let (width, height) = (grid[0].len(), grid.len());
+    let radius = i64::from(range.radius);
+
+    // Pad the grid with what lies beyond its edges, which depends on how
+    // they are joined
+    let (padded_width, padded_height) = (width + 2 * radius as usize, height + 2 * radius as usize);
+    let mut alive = vec![0; padded_width * padded_height];
+    for (py, row) in alive.chunks_mut(padded_width).enumerate() {
+        for (px, cell) in row.iter_mut().enumerate() {
+            let neighbor = topology.wrap(px as i64 - radius, py as i64 - radius, width, height);
+            *cell = u8::from(neighbor.is_some_and(|(x, y)| grid[y][x] == 1));
+        }
+    }
+
+    range.counts(&alive, padded_width, padded_height)
+}
+
+impl Default for Universe {
+    fn default() -> Self {
+        Self::new(INITIAL_GRID_WIDTH, INITIAL_GRID_HEIGHT)
+    }
+}
+
+// Work out which part of an axis of length `old` survives a resize to `new`
+// when the content stays centred: (source start, destination start, length).
+// With `even`, the content moves by an even number of cells.
+fn centered_span(old: usize, new: usize, even: bool) -> (usize, usize, usize) {
+    let offset = |margin: usize| if even { (margin / 2) & !1 } else { margin / 2 };
+    if new >= old {
+        (0, offset(new - old), old)
+    } else {
+        (offset(old - new), 0, new)
+    }
+}
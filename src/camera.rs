@@ -0,0 +1,104 @@
+// The view onto the universe: which cell sits at the top-left corner of the
+// drawing area and how many pixels a cell spans.
+
+/// Smallest zoom; each pixel then averages 16x16 cells.
+pub const MIN_ZOOM: f64 = 1.0 / 16.0;
+
+/// Largest zoom, in pixels per cell.
+pub const MAX_ZOOM: f64 = 64.0;
+
+// Cells of empty space kept around a pattern when fitting it into view
+const FIT_MARGIN: f64 = 2.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    left: f64, // Cell coordinate at the left edge of the view
+    top: f64,  // Cell coordinate at the top edge of the view
+    zoom: f64, // Pixels per cell
+}
+
+impl Camera {
+    pub fn new(zoom: f64) -> Self {
+        Self {
+            left: 0.0,
+            top: 0.0,
+            zoom: zoom.clamp(MIN_ZOOM, MAX_ZOOM),
+        }
+    }
+
+    /// A camera with the cell coordinates `(left, top)` at the top-left
+    /// corner of the view.
+    pub fn at(left: f64, top: f64, zoom: f64) -> Self {
+        Self {
+            left,
+            top,
+            ..Self::new(zoom)
+        }
+    }
+
+    pub fn left(&self) -> f64 {
+        self.left
+    }
+
+    pub fn top(&self) -> f64 {
+        self.top
+    }
+
+    pub fn zoom(&self) -> f64 {
+        self.zoom
+    }
+
+    /// Converts a widget position into fractional cell coordinates.
+    pub fn screen_to_cell(&self, x: f64, y: f64) -> (f64, f64) {
+        (self.left + x / self.zoom, self.top + y / self.zoom)
+    }
+
+    /// The cell under a widget position.
+    pub fn cell_at(&self, x: f64, y: f64) -> (i64, i64) {
+        let (x, y) = self.screen_to_cell(x, y);
+        (x.floor() as i64, y.floor() as i64)
+    }
+
+    /// Converts cell coordinates into a widget position.
+    pub fn cell_to_screen(&self, x: f64, y: f64) -> (f64, f64) {
+        ((x - self.left) * self.zoom, (y - self.top) * self.zoom)
+    }
+
+    /// The cells that are at least partly visible in a view of `width` x
+    /// `height` pixels, as `(left, top, right, bottom)` with exclusive ends.
+    pub fn visible_cells(&self, width: f64, height: f64) -> (i64, i64, i64, i64) {
+        let (left, top) = self.cell_at(0.0, 0.0);
+        let (right, bottom) = self.screen_to_cell(width, height);
+        (left, top, right.ceil() as i64, bottom.ceil() as i64)
+    }
+
+    /// Moves the view by a distance in pixels, dragging the cells along.
+    pub fn pan(&mut self, dx: f64, dy: f64) {
+        self.left -= dx / self.zoom;
+        self.top -= dy / self.zoom;
+    }
+
+    /// Multiplies the zoom by `factor`, keeping the cell under the widget
+    /// position `(x, y)` in place.
+    pub fn zoom_at(&mut self, factor: f64, x: f64, y: f64) {
+        let (cell_x, cell_y) = self.screen_to_cell(x, y);
+        self.zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        self.left = cell_x - x / self.zoom;
+        self.top = cell_y - y / self.zoom;
+    }
+
+    /// Centres a view of `width` x `height` pixels on the cell coordinates `(x, y)`.
+    pub fn center_on(&mut self, x: f64, y: f64, width: f64, height: f64) {
+        self.left = x - width / 2.0 / self.zoom;
+        self.top = y - height / 2.0 / self.zoom;
+    }
+
+    /// Zooms and centres a view of `view_width` x `view_height` pixels so
+    /// that the given rectangle of cells fills it.
+    pub fn fit(&mut self, left: f64, top: f64, width: f64, height: f64, view_width: f64, view_height: f64) {
+        let zoom_x = view_width / (width + 2.0 * FIT_MARGIN);
+        let zoom_y = view_height / (height + 2.0 * FIT_MARGIN);
+        self.zoom = zoom_x.min(zoom_y).clamp(MIN_ZOOM, MAX_ZOOM);
+        self.center_on(left + width / 2.0, top + height / 2.0, view_width, view_height);
+    }
+}
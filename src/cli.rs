@@ -0,0 +1,282 @@
+// The headless `run` command, for scripts and servers without a display:
+//
+//     cellverse run pattern.rle --generations 10000 --rule B3/S23 --out result.rle --stats stats.csv
+//
+// It steps through the same `Stepper` as the window, but never initializes
+// GTK. The final statistics are printed to stdout as one JSON object, and
+// `--stats` writes them as CSV along the way. Errors go to stderr with exit
+// code 2 for bad arguments and 1 for failures while running.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+use cellverse_core::{
+    Engine, Rule, Stats, Topology, Universe, INITIAL_GRID_HEIGHT, INITIAL_GRID_WIDTH, MAX_GRID_HEIGHT, MAX_GRID_WIDTH,
+};
+
+use crate::stepper::{Backend, Stepper};
+use crate::PatternFile;
+
+const EXIT_SUCCESS: i32 = 0;
+const EXIT_FAILURE: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+
+const USAGE: &str = "\
+Usage: cellverse run PATTERN [OPTIONS]
+
+Simulates a pattern file (RLE, Macrocell, plaintext or Life 1.05/1.06)
+without opening a window, then prints the final statistics as JSON.
+
+Options:
+  --generations N    Generations to simulate (default 0)
+  --rule RULE        Rule in B/S notation, overriding the pattern's own
+  --topology NAME    torus, plane, horizontal-cylinder, vertical-cylinder,
+                     klein-bottle, cross-surface or unbounded (default torus)
+  --size WxH         Universe size, grown to fit the pattern (default 150x100,
+                     at most 5000x3000)
+  --engine NAME      grid, bit-packed or hashlife (default grid)
+  --no-grow          Keep the universe from growing every 50 generations
+  --out FILE         Write the final universe, in the format of its extension
+  --stats FILE       Write generation, population, births and deaths as CSV
+  --stats-every N    Generations between CSV rows (default 1)
+  -h, --help         Show this help
+
+Exit codes: 0 on success, 1 if the run failed, 2 for invalid arguments.";
+
+// Topologies by their command-line names
+const TOPOLOGY_NAMES: [(&str, Topology); 7] = [
+    ("torus", Topology::Torus),
+    ("plane", Topology::Plane),
+    ("horizontal-cylinder", Topology::HorizontalCylinder),
+    ("vertical-cylinder", Topology::VerticalCylinder),
+    ("klein-bottle", Topology::KleinBottle),
+    ("cross-surface", Topology::CrossSurface),
+    ("unbounded", Topology::Unbounded),
+];
+
+struct Options {
+    pattern: PathBuf,
+    generations: u64,
+    rule: Option<Rule>,
+    topology: Topology,
+    size: (usize, usize),
+    backend: Backend,
+    auto_grow: bool,
+    out: Option<PathBuf>,
+    stats: Option<PathBuf>,
+    stats_every: u64,
+}
+
+/// Runs the command with the arguments following `run`, returning the
+/// process exit code.
+pub fn run(args: &[String]) -> i32 {
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        return EXIT_SUCCESS;
+    }
+    let options = match parse_args(args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("cellverse run: {}\n\n{}", err, USAGE);
+            return EXIT_USAGE;
+        }
+    };
+    match simulate(&options) {
+        Ok((universe, stats)) => {
+            println!(
+                "{{\"generation\":{},\"population\":{},\"births\":{},\"deaths\":{},\"width\":{},\"height\":{}}}",
+                stats.generation,
+                stats.population,
+                stats.births,
+                stats.deaths,
+                universe.width(),
+                universe.height()
+            );
+            EXIT_SUCCESS
+        }
+        Err(err) => {
+            eprintln!("cellverse run: {}", err);
+            EXIT_FAILURE
+        }
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        pattern: PathBuf::new(),
+        generations: 0,
+        rule: None,
+        topology: Topology::Torus,
+        size: (INITIAL_GRID_WIDTH, INITIAL_GRID_HEIGHT),
+        backend: Backend::Grid,
+        auto_grow: true,
+        out: None,
+        stats: None,
+        stats_every: 1,
+    };
+    let mut pattern = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            if pattern.replace(PathBuf::from(arg)).is_some() {
+                return Err(format!("unexpected argument \"{}\"", arg));
+            }
+            continue;
+        }
+        if arg == "--no-grow" {
+            options.auto_grow = false;
+            continue;
+        }
+
+        let value = args.next().ok_or_else(|| format!("{} needs a value", arg))?;
+        let invalid = || format!("invalid value \"{}\" for {}", value, arg);
+        match arg.as_str() {
+            "--generations" => options.generations = value.parse().map_err(|_| invalid())?,
+            "--rule" => options.rule = Some(value.parse().map_err(|err| format!("{}: {}", arg, err))?),
+            "--topology" => {
+                options.topology = TOPOLOGY_NAMES
+                    .iter()
+                    .find(|(name, _)| name == value)
+                    .map(|&(_, topology)| topology)
+                    .ok_or_else(invalid)?;
+            }
+            "--size" => {
+                let (width, height) = value.split_once('x').ok_or_else(invalid)?;
+                let width: usize = width.parse().map_err(|_| invalid())?;
+                let height: usize = height.parse().map_err(|_| invalid())?;
+                if width == 0 || height == 0 {
+                    return Err(invalid());
+                }
+                if width > MAX_GRID_WIDTH || height > MAX_GRID_HEIGHT {
+                    return Err(format!(
+                        "{}: {}x{} is larger than the biggest universe ({}x{})",
+                        arg, width, height, MAX_GRID_WIDTH, MAX_GRID_HEIGHT
+                    ));
+                }
+                options.size = (width, height);
+            }
+            "--engine" => {
+                options.backend = Backend::ALL
+                    .into_iter()
+                    .find(|backend| backend.label().eq_ignore_ascii_case(value))
+                    .ok_or_else(invalid)?;
+            }
+            "--out" => options.out = Some(PathBuf::from(value)),
+            "--stats" => options.stats = Some(PathBuf::from(value)),
+            "--stats-every" => {
+                options.stats_every = value.parse().map_err(|_| invalid())?;
+                if options.stats_every == 0 {
+                    return Err(invalid());
+                }
+            }
+            _ => return Err(format!("unknown option \"{}\"", arg)),
+        }
+    }
+
+    options.pattern = pattern.ok_or("no pattern file given")?;
+    Ok(options)
+}
+
+// Load the pattern, run it and write the requested files, returning the
+// final universe and its counters. A HashLife run is counted and written
+// from its tree, without filling in the universe's cells.
+fn simulate(options: &Options) -> Result<(Universe, Stats), String> {
+    let mut stepper = Stepper::default();
+    stepper.backend = options.backend;
+    let mut universe = load_universe(options, &mut stepper)?;
+
+    let mut stats_file = match &options.stats {
+        Some(path) => {
+            let file = File::create(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+            let mut file = BufWriter::new(file);
+            writeln!(file, "generation,population,births,deaths").map_err(|err| err.to_string())?;
+            write_stats(&mut file, stepper.stats(&universe))?;
+            Some(file)
+        }
+        None => None,
+    };
+
+    // Without a CSV to fill in, the whole run can be handed to the engine at once
+    let chunk = if stats_file.is_some() { options.stats_every } else { u64::MAX };
+    let mut remaining = options.generations;
+    while remaining > 0 {
+        let generations = remaining.min(chunk);
+        stepper.step(&mut universe, generations);
+        remaining -= generations;
+        if let Some(file) = stats_file.as_mut() {
+            write_stats(file, stepper.stats(&universe))?;
+        }
+    }
+    if let Some(mut file) = stats_file {
+        file.flush().map_err(|err| err.to_string())?;
+    }
+
+    if let Some(path) = &options.out {
+        crate::write_pattern(path, &universe, stepper.tree()).map_err(|err| format!("{}: {}", path.display(), err))?;
+    }
+    let stats = stepper.stats(&universe);
+    Ok((universe, stats))
+}
+
+// A universe holding the pattern in its centre, grown to fit it if bounded.
+// A Macrocell file run by HashLife on the unbounded plane is handed to the
+// engine as the quadtree it is.
+fn load_universe(options: &Options, stepper: &mut Stepper) -> Result<Universe, String> {
+    let file = crate::read_pattern(&options.pattern).map_err(|err| format!("{}: {}", options.pattern.display(), err))?;
+    let pattern = match file {
+        PatternFile::Tree(macrocell) if stepper.backend == Backend::HashLife && options.topology == Topology::Unbounded => {
+            let mut hashlife = macrocell.hashlife;
+            let rule = pattern_rule(options, hashlife.rule(), macrocell.rule_name.as_deref())?;
+            hashlife.set_rule(rule.clone());
+            let (width, height) = options.size;
+            let mut universe = Universe::new(width, height);
+            universe.set_topology(Topology::Unbounded);
+            universe.set_rule(rule);
+            stepper.load_tree(hashlife);
+            return Ok(universe);
+        }
+        file => file.into_pattern(),
+    };
+    let (width, height) = (pattern.width() as usize, pattern.height() as usize);
+    let (mut grid_width, mut grid_height) = options.size;
+    if options.topology != Topology::Unbounded {
+        if width > MAX_GRID_WIDTH || height > MAX_GRID_HEIGHT {
+            return Err(format!(
+                "the pattern is {}x{} cells, larger than the biggest bounded universe ({}x{}); use --topology unbounded",
+                width, height, MAX_GRID_WIDTH, MAX_GRID_HEIGHT
+            ));
+        }
+        grid_width = grid_width.max(width);
+        grid_height = grid_height.max(height);
+    }
+
+    let mut universe = Universe::new(grid_width, grid_height);
+    universe.set_topology(options.topology);
+    universe.set_auto_grow(options.auto_grow);
+    let rule = pattern_rule(options, pattern.rule.clone().unwrap_or_default(), pattern.rule_name.as_deref())?;
+    universe.set_rule(rule);
+    let x = (grid_width as i64 - width as i64) / 2;
+    let y = (grid_height as i64 - height as i64) / 2;
+    pattern.stamp(&mut universe, x, y);
+    Ok(universe)
+}
+
+// The rule to run: `--rule` if given, else the file's. A file naming a rule
+// `Rule` cannot parse, such as a rule table, needs `--rule`.
+fn pattern_rule(options: &Options, file_rule: Rule, name: Option<&str>) -> Result<Rule, String> {
+    match (&options.rule, name) {
+        (Some(rule), _) => Ok(rule.clone()),
+        (None, Some(name)) => Err(format!(
+            "{}: the pattern uses the rule \"{}\", which cannot be loaded here; give a rule with --rule",
+            options.pattern.display(),
+            name
+        )),
+        (None, None) => Ok(file_rule),
+    }
+}
+
+fn write_stats(file: &mut impl Write, stats: Stats) -> Result<(), String> {
+    writeln!(file, "{},{},{},{}", stats.generation, stats.population, stats.births, stats.deaths).map_err(|err| err.to_string())
+}
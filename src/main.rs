@@ -1,331 +1,662 @@
+mod camera;
+mod cli;
+mod renderer;
+mod session;
+mod stepper;
+
 use gtk4 as gtk;
 use gtk::prelude::*;
 use gtk::{
     Application, ApplicationWindow, Box as GtkBox, Button, DrawingArea, 
     Orientation, Scale, Frame,
 };
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::path::Path;
 use std::rc::Rc;
-use std::time::Duration;
-use rand::Rng;
-
-// Initial grid dimensions that will grow over time
-const CELL_SIZE: i32 = 8;  // Cell size in pixels
-const INITIAL_GRID_WIDTH: usize = 150;  // Initial width
-const INITIAL_GRID_HEIGHT: usize = 100; // Initial height
-const MAX_GRID_WIDTH: usize = 5000;      // Maximum width the grid can grow to
-const MAX_GRID_HEIGHT: usize = 3000;     // Maximum height the grid can grow to
-const GROWTH_INTERVAL: u64 = 50;        // How many updates before growing the grid
-const GROWTH_AMOUNT: usize = 1;         // How many cells to add in each direction when growing
-
-// Game state
+use std::time::{Duration, Instant};
+
+use camera::Camera;
+use renderer::Renderer;
+use session::Session;
+use stepper::{Backend, Stepper};
+
+use cellverse_core::draw::{self, Shape};
+use cellverse_core::formats::{self, macrocell::{self, Macrocell}, rle};
+use cellverse_core::library::Category;
+use cellverse_core::{Engine, Format, HashLife, History, Lattice, Library, PasteMode, Pattern, Rule, RuleTable, RunUntil, Selection, Stats, StopCondition, Timeline, Topology, Universe, INITIAL_GRID_HEIGHT, INITIAL_GRID_WIDTH, MAX_GRID_HEIGHT, MAX_GRID_WIDTH};
+
+const INITIAL_ZOOM: f64 = 8.0; // Pixels per cell when the window opens
+const WHEEL_ZOOM_FACTOR: f64 = 1.25; // Zoom change per mouse wheel step
+const CHECKPOINT_INTERVAL: u64 = 100; // Updates between undo checkpoints while running
+const INITIAL_UNDO_MEMORY_MB: f64 = 256.0;
+const MAX_SCRUB_STEPS: u64 = 10_000; // Longest re-simulation from a keyframe when scrubbing the timeline
+const RUN_FRAME_TIME: Duration = Duration::from_millis(15); // Time spent simulating between redraws of a run-until
+const RULE_TOOLTIP: &str = "Life-like rule in B/S notation, e.g. B3/S23, B36/S23 or B2/S, \
+                            with Hensel letters for non-totalistic rules, e.g. B2-a/S12, \
+                            Generations rule in S/B/C notation, e.g. /2/3 or 345/2/4, \
+                            ending in H, L or LE for the hexagonal, triangular or triangular edge lattice, e.g. B2/S34H, \
+                            or Larger than Life rule, e.g. R5,C0,M1,S34..58,B34..45,NM \
+                            (NM Moore, NN von Neumann or NC circular neighbourhood); \
+                            rule tables are loaded with Rule File…";
+
+// What dragging with the primary mouse button does
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Tool {
+    Pencil,
+    Eraser,
+    Shape(Shape),
+    Fill,
+    Select,
+}
+
+impl Tool {
+    const ALL: [Tool; 8] = [
+        Tool::Pencil,
+        Tool::Eraser,
+        Tool::Shape(Shape::Line),
+        Tool::Shape(Shape::Rectangle),
+        Tool::Shape(Shape::FilledRectangle),
+        Tool::Shape(Shape::Ellipse),
+        Tool::Fill,
+        Tool::Select,
+    ];
+
+    fn label(self) -> String {
+        match self {
+            Tool::Pencil => "Pencil".to_string(),
+            Tool::Eraser => "Eraser".to_string(),
+            Tool::Shape(shape) => shape.to_string(),
+            Tool::Fill => "Flood fill".to_string(),
+            Tool::Select => "Select".to_string(),
+        }
+    }
+
+    fn tooltip(self) -> &'static str {
+        match self {
+            Tool::Pencil => "Drag to draw live cells; starting on a live cell draws dead ones",
+            Tool::Eraser => "Drag to kill cells",
+            Tool::Shape(_) => "Drag from corner to corner to draw live cells",
+            Tool::Fill => "Click to flip the connected area of cells in the same state",
+            Tool::Select => "Drag to select a rectangle of cells",
+        }
+    }
+}
+
+// An operation on the selected cells
+#[derive(Clone, Copy, PartialEq)]
+enum SelectionAction {
+    Copy,
+    Cut,
+    Paste,
+    Rotate,
+    FlipHorizontally,
+    FlipVertically,
+    Clear,
+    ClearOutside,
+    Randomize,
+    Shrink,
+}
+
+impl SelectionAction {
+    const ALL: [SelectionAction; 10] = [
+        SelectionAction::Copy,
+        SelectionAction::Cut,
+        SelectionAction::Paste,
+        SelectionAction::Rotate,
+        SelectionAction::FlipHorizontally,
+        SelectionAction::FlipVertically,
+        SelectionAction::Clear,
+        SelectionAction::ClearOutside,
+        SelectionAction::Randomize,
+        SelectionAction::Shrink,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            SelectionAction::Copy => "Copy",
+            SelectionAction::Cut => "Cut",
+            SelectionAction::Paste => "Paste",
+            SelectionAction::Rotate => "Rotate",
+            SelectionAction::FlipHorizontally => "Flip H",
+            SelectionAction::FlipVertically => "Flip V",
+            SelectionAction::Clear => "Clear",
+            SelectionAction::ClearOutside => "Clear Outside",
+            SelectionAction::Randomize => "Random Fill",
+            SelectionAction::Shrink => "Shrink to Fit",
+        }
+    }
+
+    fn tooltip(self) -> &'static str {
+        match self {
+            SelectionAction::Copy => "Copy the selection to the clipboard as RLE (Ctrl+C)",
+            SelectionAction::Cut => "Copy the selection to the clipboard and clear it (Ctrl+X)",
+            SelectionAction::Paste => "Place a pattern from the clipboard at the mouse (Ctrl+V)",
+            SelectionAction::Rotate => "Turn the selection a quarter turn clockwise",
+            SelectionAction::FlipHorizontally => "Mirror the selection left to right",
+            SelectionAction::FlipVertically => "Mirror the selection top to bottom",
+            SelectionAction::Clear => "Kill the cells in the selection (Delete)",
+            SelectionAction::ClearOutside => "Kill the cells outside the selection",
+            SelectionAction::Randomize => "Fill the selection with random cells at the chosen density",
+            SelectionAction::Shrink => "Shrink the selection to the live cells inside it",
+        }
+    }
+}
+
+// A drag in progress with the pencil, eraser or a shape tool
+struct Stroke {
+    start: (i64, i64), // Cell where the drag began
+    last: (i64, i64),  // Cell under the mouse
+    state: u8,         // State the stroke paints
+}
+
+// A pattern following the mouse until it is stamped into the universe
+struct Placement {
+    pattern: Pattern,
+    mode: PasteMode,
+    cursor: Option<(i64, i64)>, // Cell under the mouse, while it is over the drawing area
+}
+
+impl Placement {
+    // Top-left corner of the pattern, centred on the cursor
+    fn origin(&self) -> Option<(i64, i64)> {
+        let (x, y) = self.cursor?;
+        Some((x - self.pattern.width() as i64 / 2, y - self.pattern.height() as i64 / 2))
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "Placing {} ({}): click to stamp, R rotate, H/V flip, M paste mode, Esc or right-click to stop",
+            self.pattern.name.as_deref().unwrap_or("pattern"),
+            self.mode
+        )
+    }
+}
+
+// Game state: the simulated universe plus the playback settings of the window
 struct GameState {
-    grid: Vec<Vec<bool>>,
-    grid_width: usize,
-    grid_height: usize,
+    universe: Universe,
+    stepper: Stepper,
+    step_exponent: u8, // HashLife advances 2^step_exponent generations per update
     running: bool,
     speed: u64, // in milliseconds
-    update_counter: u64, // Count updates for growth timing
-    auto_grow: bool, // Whether to auto-grow the universe
-    generation_count: u64, // Track number of generations
-    living_cells: usize, // Track number of living cells
-    cell_births: u64, // Track cell births
-    cell_deaths: u64, // Track cell deaths
     timeout_id: Option<gtk::glib::SourceId>, // Store current timeout ID
+    placement: Option<Placement>,
+    camera: Camera,
+    pointer: Option<(f64, f64)>, // Mouse position over the drawing area, in pixels
+    renderer: Renderer,
+    tool: Tool,
+    draw_state: u8, // State the pencil and shapes paint, for rules with more than two
+    stroke: Option<Stroke>,
+    selection: Option<Selection>,
+    history: History,
+    updates_since_checkpoint: Option<u64>, // None until the current run has a checkpoint
+    run_until: Option<RunUntil>, // Run started with Step N or Run Until
+    run_until_id: Option<gtk::glib::SourceId>,
+    timeline: Timeline,
 }
 
 impl GameState {
     fn new() -> Self {
-        // Initialize with an empty grid
-        let grid = vec![vec![false; INITIAL_GRID_WIDTH]; INITIAL_GRID_HEIGHT];
-
-        Self { 
-            grid,
-            grid_width: INITIAL_GRID_WIDTH,
-            grid_height: INITIAL_GRID_HEIGHT,
+        let universe = Universe::new(INITIAL_GRID_WIDTH, INITIAL_GRID_HEIGHT);
+        let mut timeline = Timeline::default();
+        timeline.reset_from(&universe);
+        Self {
+            universe,
+            stepper: Stepper::default(),
+            step_exponent: 0,
             running: false,
             speed: 100,
-            update_counter: 0,
-            auto_grow: true,
-            generation_count: 0,
-            living_cells: 0,
-            cell_births: 0,
-            cell_deaths: 0,
             timeout_id: None,
+            placement: None,
+            camera: Camera::new(INITIAL_ZOOM),
+            pointer: None,
+            renderer: Renderer::default(),
+            tool: Tool::Pencil,
+            draw_state: 1,
+            stroke: None,
+            selection: None,
+            history: History::new((INITIAL_UNDO_MEMORY_MB * 1024.0 * 1024.0) as usize),
+            updates_since_checkpoint: None,
+            run_until: None,
+            run_until_id: None,
+            timeline,
         }
     }
 
-    // Calculate and update statistics
-    fn update_statistics(&mut self) {
-        let mut count = 0;
-        for row in &self.grid {
-            for &cell in row {
-                if cell {
-                    count += 1;
-                }
-            }
-        }
-        self.living_cells = count;
+    // Drop the engine copies so that they are rebuilt from the grid on the next update
+    fn invalidate_engines(&mut self) {
+        self.stepper.invalidate();
     }
 
-    fn add_glider(&mut self, x: usize, y: usize) {
-        if x + 2 < self.grid_width && y + 2 < self.grid_height {
-            self.grid[y][x+1] = true;
-            self.grid[y+1][x+2] = true;
-            self.grid[y+2][x] = true;
-            self.grid[y+2][x+1] = true;
-            self.grid[y+2][x+2] = true;
-        }
-        self.update_statistics();
+    // Fill in the universe's cells from a HashLife tree that ran ahead of
+    // it, before they are edited or read
+    fn sync_engines(&mut self) {
+        self.stepper.sync(&mut self.universe);
     }
 
-    // Add a cool pattern: Gosper's Glider Gun
-    fn add_glider_gun(&mut self, x: usize, y: usize) {
-        if x + 36 < self.grid_width && y + 9 < self.grid_height {
-            // Left block
-            self.grid[y+4][x+0] = true;
-            self.grid[y+4][x+1] = true;
-            self.grid[y+5][x+0] = true;
-            self.grid[y+5][x+1] = true;
-
-            // Left gun
-            self.grid[y+2][x+12] = true;
-            self.grid[y+2][x+13] = true;
-            self.grid[y+3][x+11] = true;
-            self.grid[y+3][x+15] = true;
-            self.grid[y+4][x+10] = true;
-            self.grid[y+4][x+16] = true;
-            self.grid[y+5][x+10] = true;
-            self.grid[y+5][x+14] = true;
-            self.grid[y+5][x+16] = true;
-            self.grid[y+5][x+17] = true;
-            self.grid[y+6][x+10] = true;
-            self.grid[y+6][x+16] = true;
-            self.grid[y+7][x+11] = true;
-            self.grid[y+7][x+15] = true;
-            self.grid[y+8][x+12] = true;
-            self.grid[y+8][x+13] = true;
-
-            // Right block
-            self.grid[y+2][x+24] = true;
-            self.grid[y+2][x+25] = true;
-            self.grid[y+3][x+24] = true;
-            self.grid[y+3][x+25] = true;
-        }
-        self.update_statistics();
+    // The counters of the universe, or of the tree running ahead of it
+    fn stats(&self) -> Stats {
+        self.stepper.stats(&self.universe)
     }
 
-    // Add a spaceship
-    fn add_spaceship(&mut self, x: usize, y: usize) {
-        if x + 4 < self.grid_width && y + 3 < self.grid_height {
-            self.grid[y][x+1] = true;
-            self.grid[y][x+4] = true;
-            self.grid[y+1][x] = true;
-            self.grid[y+2][x] = true;
-            self.grid[y+2][x+4] = true;
-            self.grid[y+3][x] = true;
-            self.grid[y+3][x+1] = true;
-            self.grid[y+3][x+2] = true;
-            self.grid[y+3][x+3] = true;
-        }
-        self.update_statistics();
+    // The cell under a widget position, on the lattice of the current rule
+    fn cell_at(&self, x: f64, y: f64) -> (i64, i64) {
+        let (x, y) = self.camera.screen_to_cell(x, y);
+        self.universe.rule().lattice().cell_at(x, y)
     }
 
-    // Add a pulsar pattern (period 3 oscillator)
-    fn add_pulsar(&mut self, x: usize, y: usize) {
-        if x + 12 < self.grid_width && y + 12 < self.grid_height {
-            // Outer vertical lines
-            for dy in [0, 5, 7, 12] {
-                for dx in [2, 3, 4, 8, 9, 10] {
-                    self.grid[y+dy][x+dx] = true;
-                }
-            }
+    // Access the universe for editing. The edit becomes an undo step on the
+    // next `commit_edit`
+    fn universe_mut(&mut self) -> &mut Universe {
+        self.sync_engines();
+        self.invalidate_engines();
+        self.renderer.invalidate();
+        self.history.begin_edit(&self.universe);
+        &mut self.universe
+    }
 
-            // Outer horizontal lines
-            for dx in [0, 5, 7, 12] {
-                for dy in [2, 3, 4, 8, 9, 10] {
-                    self.grid[y+dy][x+dx] = true;
-                }
-            }
+    // Access the universe to edit only the cells in `left..right` x `top..bottom`,
+    // which are then the only ones redrawn
+    fn edit_cells(&mut self, left: i64, top: i64, right: i64, bottom: i64) -> &mut Universe {
+        self.sync_engines();
+        self.invalidate_engines();
+        self.renderer.invalidate_cells(left, top, right, bottom);
+        self.history.begin_edit(&self.universe);
+        &mut self.universe
+    }
+
+    // Record the edits made since the last commit as one undo step
+    fn commit_edit(&mut self) {
+        if self.history.end_edit(&self.universe) {
+            // A later run starts from here
+            self.updates_since_checkpoint = None;
+            self.timeline.reset_from(&self.universe);
         }
-        self.update_statistics();
     }
 
-    // Add an R-pentomino (chaotic pattern that evolves for a long time)
-    fn add_r_pentomino(&mut self, x: usize, y: usize) {
-        if x + 2 < self.grid_width && y + 2 < self.grid_height {
-            self.grid[y][x+1] = true;
-            self.grid[y][x+2] = true;
-            self.grid[y+1][x] = true;
-            self.grid[y+1][x+1] = true;
-            self.grid[y+2][x+1] = true;
+    // Make a complete edit to the universe
+    fn edit<T>(&mut self, f: impl FnOnce(&mut Universe) -> T) -> T {
+        let result = f(self.universe_mut());
+        self.commit_edit();
+        result
+    }
+
+    // Undo the last edit or run, or redo the last undone one
+    fn step_history(&mut self, redo: bool) {
+        self.stroke = None;
+        // Redo comes back to the cells as they are, not as the grid last saw them
+        self.sync_engines();
+        let changed = if redo {
+            self.history.redo(&mut self.universe)
+        } else {
+            self.history.undo(&mut self.universe)
+        };
+        if changed {
+            self.invalidate_engines();
+            self.renderer.invalidate();
+            self.updates_since_checkpoint = None;
+            self.timeline.reset_from(&self.universe);
         }
-        self.update_statistics();
     }
 
-    fn randomize(&mut self) {
-        let mut rng = rand::thread_rng();
-        for y in 0..self.grid_height {
-            for x in 0..self.grid_width {
-                // About 20% chance for a cell to be alive (less dense for better patterns)
-                self.grid[y][x] = rng.gen_bool(0.2);
-            }
+    // The session as it stands, for saving
+    fn session(&mut self) -> Session {
+        self.sync_engines();
+        Session {
+            universe: self.universe.clone(),
+            speed: self.speed,
+            camera: self.camera,
+            selection: self.selection,
         }
-        self.update_statistics();
     }
 
-    // Create random pattern in center region only
-    fn randomize_center(&mut self) {
-        let mut rng = rand::thread_rng();
+    // Pick up a saved session. Its universe starts a fresh history and timeline
+    fn restore_session(&mut self, session: Session) {
+        self.universe = session.universe;
+        self.speed = session.speed;
+        self.camera = session.camera;
+        self.selection = session.selection;
+        self.placement = None;
+        self.stroke = None;
+        self.invalidate_engines();
+        self.renderer.invalidate();
+        self.history.clear();
+        self.updates_since_checkpoint = None;
+        self.timeline.clear();
+        self.timeline.reset_from(&self.universe);
+    }
 
-        // Clear the grid first
-        self.clear();
+    // Show an earlier or later generation of the run from the timeline.
+    // Generations too far past a keyframe to re-simulate snap back to it.
+    fn seek(&mut self, generation: u64) {
+        let Some(keyframe) = self.timeline.keyframe_before(generation) else {
+            return;
+        };
+        self.sync_engines();
+        let current = self.universe.stats().generation;
+        let nearest = if (keyframe..=generation).contains(&current) { current } else { keyframe };
+        let generation = if generation - nearest > MAX_SCRUB_STEPS { nearest } else { generation };
+
+        if let Some(mut universe) = self.timeline.seek(generation, &self.universe) {
+            universe.set_auto_grow(self.universe.auto_grow());
+            self.universe = universe;
+            self.invalidate_engines();
+            self.renderer.invalidate();
+            self.updates_since_checkpoint = None;
+        }
+    }
 
-        // Determine the center region (about 1/4 of the total area)
-        let start_x = self.grid_width / 3;
-        let end_x = self.grid_width * 2 / 3;
-        let start_y = self.grid_height / 3;
-        let end_y = self.grid_height * 2 / 3;
+    // Set `cells` to `state`, counting the changes as births or deaths
+    fn paint(&mut self, cells: &[(i64, i64)], state: u8) {
+        let Some(&(x, y)) = cells.first() else {
+            return;
+        };
+        let (left, top, right, bottom) = cells.iter().fold((x, y, x, y), |(left, top, right, bottom), &(x, y)| {
+            (left.min(x), top.min(y), right.max(x), bottom.max(y))
+        });
+        let universe = self.edit_cells(left, top, right + 1, bottom + 1);
+        for &(x, y) in cells {
+            universe.set_state(x, y, state);
+        }
+    }
 
-        // Fill only the center region
-        for y in start_y..end_y {
-            for x in start_x..end_x {
-                // About 30% chance for a cell to be alive in the center region
-                self.grid[y][x] = rng.gen_bool(0.3);
+    // Start using the current tool at `cell`
+    fn begin_stroke(&mut self, cell: (i64, i64), view_width: f64, view_height: f64) {
+        self.sync_engines();
+        let (x, y) = cell;
+        // The rule may have fewer states than the picker offers
+        let draw_state = self.draw_state.min(self.universe.rule().states() - 1);
+        let state = match self.tool {
+            Tool::Pencil if self.universe.get_state(x, y) == draw_state => 0,
+            Tool::Pencil | Tool::Shape(_) | Tool::Select => draw_state,
+            Tool::Eraser => 0,
+            Tool::Fill => {
+                // The plane has no edges, so filling stops at the edges of the view
+                let bounds = match self.universe.topology() {
+                    Topology::Unbounded => renderer::visible_cells(&self.camera, self.universe.rule().lattice(), view_width, view_height),
+                    _ => (0, 0, self.universe.width() as i64, self.universe.height() as i64),
+                };
+                draw::flood_fill(self.universe_mut(), x, y, bounds);
+                self.commit_edit();
+                return;
             }
+        };
+
+        match self.tool {
+            Tool::Pencil | Tool::Eraser => self.paint(&[cell], state),
+            Tool::Select => self.select(Selection::from_corners(cell, cell)),
+            _ => {}
         }
+        self.stroke = Some(Stroke { start: cell, last: cell, state });
+    }
 
-        self.update_statistics();
+    // Continue the stroke to `cell`; freehand tools paint along the way
+    fn continue_stroke(&mut self, cell: (i64, i64)) {
+        let Some(stroke) = self.stroke.as_mut() else {
+            return;
+        };
+        let (last, state) = (stroke.last, stroke.state);
+        stroke.last = cell;
+        let start = stroke.start;
+        match self.tool {
+            // Join up with the previous cell so fast drags leave no gaps
+            Tool::Pencil | Tool::Eraser if cell != last => self.paint(&Shape::Line.cells(last, cell), state),
+            Tool::Select => self.select(Selection::from_corners(start, cell)),
+            _ => {}
+        }
     }
 
-    fn clear(&mut self) {
-        for y in 0..self.grid_height {
-            for x in 0..self.grid_width {
-                self.grid[y][x] = false;
+    // Select a rectangle, keeping to the grid on bounded topologies
+    fn select(&mut self, selection: Selection) {
+        let selection = match self.universe.topology() {
+            Topology::Unbounded => selection,
+            _ => selection.clipped(self.universe.width(), self.universe.height()),
+        };
+        self.selection = (!selection.is_empty()).then_some(selection);
+    }
+
+    // Apply an editing operation to the selection, other than the clipboard ones
+    fn edit_selection(&mut self, action: SelectionAction, density: f64) {
+        let Some(selection) = self.selection else {
+            return;
+        };
+        let Selection { left, top, right, bottom } = selection;
+        self.sync_engines();
+        match action {
+            SelectionAction::Rotate => {
+                let rotated = selection.rotate_clockwise(self.universe_mut());
+                self.select(rotated);
             }
+            SelectionAction::FlipHorizontally => selection.flip_horizontally(self.edit_cells(left, top, right, bottom)),
+            SelectionAction::FlipVertically => selection.flip_vertically(self.edit_cells(left, top, right, bottom)),
+            SelectionAction::Clear | SelectionAction::Cut => selection.clear(self.edit_cells(left, top, right, bottom)),
+            SelectionAction::ClearOutside => selection.clear_outside(self.universe_mut()),
+            SelectionAction::Randomize => selection.randomize(self.edit_cells(left, top, right, bottom), density),
+            SelectionAction::Shrink => self.selection = selection.shrunk_to_fit(&self.universe),
+            SelectionAction::Copy | SelectionAction::Paste => {}
         }
+        self.commit_edit();
+    }
 
-        // Reset counters
-        self.update_counter = 0;
-        self.generation_count = 0;
-        self.cell_births = 0;
-        self.cell_deaths = 0;
-        self.living_cells = 0;
+    // Finish the stroke, drawing the shape being dragged out
+    fn end_stroke(&mut self) {
+        let Some(stroke) = self.stroke.take() else {
+            return;
+        };
+        if let Tool::Shape(shape) = self.tool {
+            self.paint(&shape.cells(stroke.start, stroke.last), stroke.state);
+        }
+        self.commit_edit();
     }
 
-    fn toggle_cell(&mut self, x: usize, y: usize) {
-        if x < self.grid_width && y < self.grid_height {
-            self.grid[y][x] = !self.grid[y][x];
+    // Replace the universe's contents with `pattern`, adopting its rule
+    fn load_pattern(&mut self, pattern: &Pattern) -> Result<(), String> {
+        let rule = self.pattern_rule(pattern.rule.as_ref(), pattern.rule_name.as_deref())?;
+        self.fit_pattern(pattern)?;
+        let universe = self.universe_mut();
+        universe.clear();
+        if let Some(rule) = rule {
+            universe.set_rule(rule);
+        }
+        let x = (universe.width() as i64 - pattern.width() as i64) / 2;
+        let y = (universe.height() as i64 - pattern.height() as i64) / 2;
+        pattern.stamp(universe, x, y);
+        self.commit_edit();
+        Ok(())
+    }
 
-            // Update statistics
-            if self.grid[y][x] {
-                self.living_cells += 1;
-                self.cell_births += 1;
-            } else {
-                self.living_cells -= 1;
-                self.cell_deaths += 1;
-            }
+    // Replace the universe with a Macrocell quadtree. It goes on the unbounded
+    // plane and runs on HashLife as it is, without being stamped cell by cell.
+    // Like a run, it is undone back to a checkpoint of the universe before it.
+    fn load_tree(&mut self, macrocell: Macrocell) -> Result<(), String> {
+        let Macrocell { mut hashlife, rule_name } = macrocell;
+        let file_rule = hashlife.rule();
+        let rule = self.pattern_rule(Some(&file_rule).filter(|_| rule_name.is_none()), rule_name.as_deref())?;
+        self.sync_engines();
+        self.history.checkpoint(&self.universe);
+        self.universe.clear();
+        self.universe.set_topology(Topology::Unbounded);
+        if let Some(rule) = rule {
+            self.universe.set_rule(rule);
         }
+        hashlife.set_rule(self.universe.rule());
+        let generation = hashlife.stats().generation;
+        self.stepper.load_tree(hashlife);
+        self.renderer.invalidate();
+        // The checkpoint covers the run that follows, which would otherwise
+        // have to copy the tree out for one of its own
+        self.updates_since_checkpoint = Some(0);
+        self.timeline.clear();
+        self.timeline.reach(generation);
+        Ok(())
     }
 
-    fn grow_universe(&mut self) {
-        // Only grow if we're below the maximum size
-        if self.grid_width >= MAX_GRID_WIDTH || self.grid_height >= MAX_GRID_HEIGHT {
-            return;
+    // The rule to load a pattern with: its own, or the current rule table if
+    // the file names that. `None` keeps the current rule.
+    fn pattern_rule(&self, rule: Option<&Rule>, name: Option<&str>) -> Result<Option<Rule>, String> {
+        match (rule, name) {
+            (Some(rule), _) => Ok(Some(rule.clone())),
+            (None, Some(name)) => {
+                let current = self.universe.rule();
+                match current.table() {
+                    Some(table) if table.name().eq_ignore_ascii_case(name) => Ok(None),
+                    _ => Err(format!(
+                        "The pattern uses the rule \"{}\", which is not loaded. Load its rule file with Rule File…, then open the pattern again.",
+                        name
+                    )),
+                }
+            }
+            (None, None) => Ok(None),
         }
+    }
 
-        // Calculate new dimensions
-        let new_width = (self.grid_width + GROWTH_AMOUNT).min(MAX_GRID_WIDTH);
-        let new_height = (self.grid_height + GROWTH_AMOUNT).min(MAX_GRID_HEIGHT);
+    // Attach `pattern` to the mouse, making sure a bounded grid can hold it
+    fn begin_placement(&mut self, pattern: Pattern) -> Result<(), String> {
+        self.fit_pattern(&pattern)?;
+        self.commit_edit();
+        let cursor = self.placement.take().and_then(|placement| placement.cursor);
+        self.placement = Some(Placement {
+            pattern,
+            mode: PasteMode::default(),
+            cursor,
+        });
+        Ok(())
+    }
 
-        // Create a new grid with expanded dimensions
-        let mut new_grid = vec![vec![false; new_width]; new_height];
+    // Zoom and centre the camera on the live cells, or on the whole grid if
+    // there are none
+    fn fit_view(&mut self, view_width: f64, view_height: f64) {
+        let mut bounds: Option<(i64, i64, i64, i64)> = None;
+        match self.stepper.tree() {
+            Some(tree) => bounds = tree.bounds(),
+            None => self.universe.for_each_live(&mut |x, y| {
+                bounds = Some(match bounds {
+                    None => (x, y, x, y),
+                    Some((left, top, right, bottom)) => (left.min(x), top.min(y), right.max(x), bottom.max(y)),
+                });
+            }),
+        }
 
-        // Copy existing data to the center of the new grid
-        let x_offset = (new_width - self.grid_width) / 2;
-        let y_offset = (new_height - self.grid_height) / 2;
+        let (left, top, right, bottom) = match bounds {
+            Some(bounds) => bounds,
+            None if self.universe.topology() == Topology::Unbounded => (0, 0, 0, 0),
+            None => (0, 0, self.universe.width() as i64 - 1, self.universe.height() as i64 - 1),
+        };
+        // Sheared hexagons spread the rows sideways
+        let lattice = self.universe.rule().lattice();
+        let (top, bottom) = (top as f64, bottom as f64 + 1.0);
+        let corners = [(left as f64, top), (left as f64, bottom), (right as f64 + 1.0, top), (right as f64 + 1.0, bottom)];
+        let columns = corners.map(|(x, y)| lattice.shear(x, y));
+        let left = columns.iter().copied().fold(f64::INFINITY, f64::min);
+        let right = columns.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        self.camera.fit(left, top, right - left, bottom - top, view_width, view_height);
+    }
 
-        for y in 0..self.grid_height {
-            for x in 0..self.grid_width {
-                new_grid[y + y_offset][x + x_offset] = self.grid[y][x];
-            }
+    // Stamp the pattern being placed at the cursor
+    fn stamp_placement(&mut self) {
+        let Some(placement) = self.placement.take() else {
+            return;
+        };
+        if let Some((x, y)) = placement.origin() {
+            let pattern = &placement.pattern;
+            let universe = self.edit_cells(x, y, x + pattern.width() as i64, y + pattern.height() as i64);
+            pattern.paste(universe, x, y, placement.mode);
+            self.commit_edit();
         }
+        self.placement = Some(placement);
+    }
 
-        // Update grid
-        self.grid = new_grid;
-        self.grid_width = new_width;
-        self.grid_height = new_height;
+    // Grow a bounded grid so that `pattern` fits in it
+    fn fit_pattern(&mut self, pattern: &Pattern) -> Result<(), String> {
+        let (width, height) = (pattern.width() as usize, pattern.height() as usize);
+        let bounded = self.universe.topology() != Topology::Unbounded;
+        if bounded && (width > MAX_GRID_WIDTH || height > MAX_GRID_HEIGHT) {
+            return Err(format!(
+                "The pattern is {}x{} cells, larger than the biggest bounded universe ({}x{}). \
+                 Switch to the unbounded topology to use it.",
+                width, height, MAX_GRID_WIDTH, MAX_GRID_HEIGHT
+            ));
+        }
 
-        // Report growth
-        println!("Universe expanded to {}x{}", self.grid_width, self.grid_height);
+        if bounded && (width > self.universe.width() || height > self.universe.height()) {
+            let new_width = self.universe.width().max(width);
+            let new_height = self.universe.height().max(height);
+            self.universe_mut().resize(new_width, new_height);
+        }
+        Ok(())
     }
 
     fn update(&mut self) {
         if !self.running {
             return;
         }
+        self.advance(self.generations_per_update());
+    }
 
-        // Check if it's time to grow the universe
-        self.update_counter += 1;
-        self.generation_count += 1;
-
-        if self.auto_grow && self.update_counter % GROWTH_INTERVAL == 0 {
-            self.grow_universe();
-        }
-
-        let mut new_grid = vec![vec![false; self.grid_width]; self.grid_height];
-        let mut births = 0;
-        let mut deaths = 0;
-
-        for y in 0..self.grid_height {
-            for x in 0..self.grid_width {
-                let alive_neighbors = self.count_alive_neighbors(x, y);
-                let cell_alive = self.grid[y][x];
-
-                new_grid[y][x] = match (cell_alive, alive_neighbors) {
-                    (true, 0..=1) => {
-                        deaths += 1;
-                        false  // Underpopulation
-                    },
-                    (true, 2..=3) => true,   // Survival
-                    (true, _) => {
-                        deaths += 1;
-                        false  // Overpopulation
-                    },
-                    (false, 3) => {
-                        births += 1;
-                        true   // Reproduction
-                    },
-                    (state, _) => state,     // No change
-                };
-            }
+    // Generations one update of the game loop advances
+    fn generations_per_update(&self) -> u64 {
+        match self.stepper.backend {
+            // HashLife jumps ahead, unless the rule keeps it on the grid engine
+            Backend::HashLife if self.stepper.runs(&self.universe) => 1 << self.step_exponent,
+            _ => 1,
         }
-
-        self.cell_births += births;
-        self.cell_deaths += deaths;
-        self.grid = new_grid;
-        self.update_statistics();
     }
 
-    fn count_alive_neighbors(&self, x: usize, y: usize) -> usize {
-        let mut count = 0;
+    // Take one update towards the goal of the current run-until, returning
+    // whether it has been reached
+    fn run_step(&mut self) -> bool {
+        let stats = self.stats();
+        let per_update = self.generations_per_update();
+        let Some(run) = self.run_until.as_mut() else {
+            return true;
+        };
+        if run.is_done(stats) {
+            return true;
+        }
 
-        for dy in -1..=1 {
-            for dx in -1..=1 {
-                if dx == 0 && dy == 0 {
-                    continue; // Skip the cell itself
-                }
+        // HashLife jumps straight to a target generation
+        let wanted = match (self.stepper.backend, run.condition()) {
+            (Backend::HashLife, StopCondition::Generation(_)) if self.stepper.runs(&self.universe) => u64::MAX,
+            _ => per_update,
+        };
+        let generations = run.limit(stats, wanted);
+        if generations == 0 {
+            return true;
+        }
+        self.advance(generations);
+        false
+    }
 
-                let nx = (x as isize + dx).rem_euclid(self.grid_width as isize) as usize;
-                let ny = (y as isize + dy).rem_euclid(self.grid_height as isize) as usize;
+    // Advance the universe by `generations` on the selected engine
+    fn advance(&mut self, generations: u64) {
+        self.renderer.invalidate();
+
+        // Keep checkpoints along the run so that it can be undone in stages.
+        // A HashLife tree running ahead would have to be copied out for each
+        // one, so its runs keep just the checkpoint they started from.
+        let due = self.updates_since_checkpoint.is_none_or(|updates| updates >= CHECKPOINT_INTERVAL);
+        if due && self.stepper.tree().is_none() {
+            self.history.checkpoint(&self.universe);
+            self.updates_since_checkpoint = Some(0);
+        }
+        self.updates_since_checkpoint = self.updates_since_checkpoint.map(|updates| updates + 1);
 
-                if self.grid[ny][nx] {
-                    count += 1;
-                }
-            }
+        self.step_engine(generations);
+        // Keyframes are copies of the universe, which the tree leaves behind
+        match self.stepper.tree() {
+            Some(tree) => self.timeline.reach(tree.stats().generation),
+            None => self.timeline.record(&self.universe),
         }
+    }
+
+    fn step_engine(&mut self, generations: u64) {
+        let old_size = (self.universe.width(), self.universe.height());
+        self.stepper.step(&mut self.universe, generations);
 
-        count
+        // Report growth
+        if (self.universe.width(), self.universe.height()) != old_size {
+            println!("Universe expanded to {}x{}", self.universe.width(), self.universe.height());
+        }
     }
 }
 
@@ -338,6 +669,9 @@ struct UiState {
     size_label: gtk::Label,
     birth_death_label: gtk::Label,
     coord_label: gtk::Label,
+    placement_label: gtk::Label,
+    timeline_scale: Scale,
+    step_spin: gtk::SpinButton,
 }
 
 impl UiState {
@@ -350,15 +684,88 @@ impl UiState {
             size_label: gtk::Label::new(Some(&format!("Universe: {}x{}", INITIAL_GRID_WIDTH, INITIAL_GRID_HEIGHT))),
             birth_death_label: gtk::Label::new(Some("Births: 0  Deaths: 0")),
             coord_label: gtk::Label::new(Some("Coordinates: -,-")),
+            placement_label: gtk::Label::new(None),
+            timeline_scale: Scale::with_range(Orientation::Horizontal, 0.0, 1.0, 1.0),
+            step_spin: gtk::SpinButton::with_range(0.0, 40.0, 1.0),
         }
     }
 
     fn update_statistics(&self) {
         let state = self.game_state.borrow();
-        self.generation_label.set_text(&format!("Generation: {}", state.generation_count));
-        self.cells_label.set_text(&format!("Living Cells: {}", state.living_cells));
-        self.size_label.set_text(&format!("Universe: {}x{}", state.grid_width, state.grid_height));
-        self.birth_death_label.set_text(&format!("Births: {}  Deaths: {}", state.cell_births, state.cell_deaths));
+        let stats = state.stats();
+        self.generation_label.set_text(&format!("Generation: {}", stats.generation));
+        self.cells_label.set_text(&format!("Living Cells: {}", stats.population));
+        match state.universe.topology() {
+            Topology::Unbounded => self.size_label.set_text("Universe: unbounded"),
+            _ => self.size_label.set_text(&format!("Universe: {}x{}", state.universe.width(), state.universe.height())),
+        }
+        self.birth_death_label.set_text(&format!("Births: {}  Deaths: {}", stats.births, stats.deaths));
+        // The step size is greyed out while the rule or topology keeps HashLife on the grid engine
+        let hashlife = state.stepper.backend == Backend::HashLife;
+        self.step_spin.set_sensitive(hashlife && state.stepper.runs(&state.universe));
+        let latest = state.timeline.latest();
+        drop(state);
+
+        // Setting the slider runs its handler, which ignores the generation already shown
+        self.timeline_scale.set_range(0.0, latest.max(1) as f64);
+        self.timeline_scale.set_value(stats.generation as f64);
+    }
+
+    fn update_placement_hint(&self) {
+        let state = self.game_state.borrow();
+        let hint = match (&state.placement, &state.selection) {
+            (Some(placement), _) => placement.describe(),
+            (None, Some(selection)) => format!(
+                "Selection: {}x{} at {},{}",
+                selection.width(),
+                selection.height(),
+                selection.left,
+                selection.top
+            ),
+            (None, None) => String::new(),
+        };
+        self.placement_label.set_text(&hint);
+    }
+
+    // Run a selection action, going through the system clipboard for copy,
+    // cut and paste
+    fn apply_selection_action(&self, action: SelectionAction, density: f64) {
+        let clipboard = self.drawing_area.clipboard();
+        match action {
+            SelectionAction::Copy | SelectionAction::Cut => {
+                let mut state = self.game_state.borrow_mut();
+                state.sync_engines();
+                if let Some(selection) = state.selection {
+                    clipboard.set_text(&rle::write(&selection.copy(&state.universe)));
+                }
+            }
+            SelectionAction::Paste => {
+                let ui = self.clone();
+                clipboard.read_text_async(None::<&gtk::gio::Cancellable>, move |result| {
+                    let result = match result {
+                        Ok(Some(text)) => formats::read(&text).map_err(|err| err.to_string()),
+                        Ok(None) => Err("The clipboard holds no text.".to_string()),
+                        Err(err) => Err(err.to_string()),
+                    };
+                    let result = result.and_then(|pattern| ui.game_state.borrow_mut().begin_placement(pattern));
+
+                    ui.update_placement_hint();
+                    ui.update_statistics();
+                    ui.drawing_area.queue_draw();
+                    if let Err(err) = result {
+                        let parent = ui.drawing_area.root().and_downcast::<gtk::Window>();
+                        show_error(parent.as_ref(), "Could not paste a pattern", &err);
+                    }
+                });
+                return;
+            }
+            _ => {}
+        }
+
+        self.game_state.borrow_mut().edit_selection(action, density);
+        self.update_placement_hint();
+        self.update_statistics();
+        self.drawing_area.queue_draw();
     }
 
     fn setup_game_loop(&self, speed: u64) -> gtk::glib::SourceId {
@@ -394,11 +801,171 @@ impl Clone for UiState {
             size_label: self.size_label.clone(),
             birth_death_label: self.birth_death_label.clone(),
             coord_label: self.coord_label.clone(),
+            placement_label: self.placement_label.clone(),
+            timeline_scale: self.timeline_scale.clone(),
+            step_spin: self.step_spin.clone(),
         }
     }
 }
 
+// Show `lattice` in the lattice selector
+fn select_lattice(dropdown: &gtk::DropDown, lattice: Lattice) {
+    if let Some(index) = Lattice::ALL.iter().position(|&item| item == lattice) {
+        dropdown.set_selected(index as u32);
+    }
+}
+
+// A pattern file as read. Macrocell files stay the quadtree they store.
+enum PatternFile {
+    Cells(Pattern),
+    Tree(Macrocell),
+}
+
+impl PatternFile {
+    // The file's cells, flattening a quadtree
+    fn into_pattern(self) -> Pattern {
+        match self {
+            PatternFile::Cells(pattern) => pattern,
+            PatternFile::Tree(macrocell) => macrocell.to_pattern(),
+        }
+    }
+}
+
+// Add the outline of cell `(x, y)` to the path, as a pixel when zoomed out so
+// that previews stay visible
+fn trace_cell(cr: &gtk::cairo::Context, camera: &Camera, lattice: Lattice, x: i64, y: i64) {
+    if camera.zoom() < 1.0 {
+        let (sx, sy) = camera.cell_to_screen(lattice.shear(x as f64, y as f64), y as f64);
+        cr.rectangle(sx, sy, 1.0, 1.0);
+    } else {
+        renderer::trace(cr, camera, &lattice.outline(x, y));
+    }
+}
+
+// Read a pattern file in any supported format, describing any failure for the user
+fn read_pattern(path: &Path) -> Result<PatternFile, String> {
+    let text = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    match Format::detect(&text) {
+        Format::Macrocell => macrocell::read(&text).map(PatternFile::Tree),
+        _ => formats::read(&text).map(PatternFile::Cells),
+    }
+    .map_err(|err| err.to_string())
+}
+
+// Write the universe in the format matching the file extension, RLE by
+// default. A HashLife tree that ran ahead of the universe is written instead,
+// straight from the quadtree for Macrocell.
+fn write_pattern(path: &Path, universe: &Universe, tree: Option<&HashLife>) -> std::io::Result<()> {
+    let format = path
+        .extension()
+        .and_then(|extension| Format::from_extension(&extension.to_string_lossy()))
+        .unwrap_or(Format::Rle);
+    let text = match tree {
+        // The tree only holds states its rule has
+        Some(tree) if format == Format::Macrocell && tree.rule().states() > 2 => macrocell::write_multistate(tree),
+        Some(tree) if format == Format::Macrocell => macrocell::write(tree),
+        _ => {
+            let mut pattern = tree.map_or_else(|| Pattern::from_universe(universe), Pattern::from_hashlife);
+            pattern.name = path.file_stem().map(|stem| stem.to_string_lossy().into_owned());
+            formats::write(&pattern, format)
+        }
+    };
+    std::fs::write(path, text)
+}
+
+// Ask the user for a pattern file to open or save, calling `on_accept` with the chosen path
+fn choose_pattern_file(
+    parent: Option<gtk::Window>,
+    action: gtk::FileChooserAction,
+    on_accept: impl Fn(&Path) + 'static,
+) {
+    let title = match action {
+        gtk::FileChooserAction::Save => "Save Pattern",
+        _ => "Open Pattern",
+    };
+    let filter = gtk::FileFilter::new();
+    filter.set_name(Some("Patterns (RLE, Macrocell, Plaintext, Life 1.05/1.06)"));
+    for pattern in ["*.rle", "*.mc", "*.cells", "*.lif", "*.life"] {
+        filter.add_pattern(pattern);
+    }
+    choose_file(parent, action, title, &filter, "pattern.rle", on_accept);
+}
+
+// Ask the user for a session file to open or save, calling `on_accept` with the chosen path
+fn choose_session_file(
+    parent: Option<gtk::Window>,
+    action: gtk::FileChooserAction,
+    on_accept: impl Fn(&Path) + 'static,
+) {
+    let title = match action {
+        gtk::FileChooserAction::Save => "Save Session",
+        _ => "Open Session",
+    };
+    let filter = gtk::FileFilter::new();
+    filter.set_name(Some("Cellverse sessions"));
+    filter.add_pattern("*.cellverse");
+    choose_file(parent, action, title, &filter, "session.cellverse", on_accept);
+}
+
+// Ask the user for a Golly rule file to open, calling `on_accept` with the chosen path
+fn choose_rule_file(parent: Option<gtk::Window>, on_accept: impl Fn(&Path) + 'static) {
+    let filter = gtk::FileFilter::new();
+    filter.set_name(Some("Golly rules"));
+    filter.add_pattern("*.rule");
+    choose_file(parent, gtk::FileChooserAction::Open, "Open Rule", &filter, "", on_accept);
+}
+
+fn choose_file(
+    parent: Option<gtk::Window>,
+    action: gtk::FileChooserAction,
+    title: &str,
+    filter: &gtk::FileFilter,
+    default_name: &str,
+    on_accept: impl Fn(&Path) + 'static,
+) {
+    let accept_label = match action {
+        gtk::FileChooserAction::Save => "_Save",
+        _ => "_Open",
+    };
+    let dialog = gtk::FileChooserNative::new(Some(title), parent.as_ref(), action, Some(accept_label), Some("_Cancel"));
+    dialog.add_filter(filter);
+    if action == gtk::FileChooserAction::Save {
+        dialog.set_current_name(default_name);
+    }
+
+    // GTK does not own native dialogs, so hold a reference until it responds
+    let keep_alive = RefCell::new(Some(dialog.clone()));
+    dialog.connect_response(move |dialog, response| {
+        if response == gtk::ResponseType::Accept {
+            if let Some(path) = dialog.file().and_then(|file| file.path()) {
+                on_accept(&path);
+            }
+        }
+        keep_alive.borrow_mut().take();
+    });
+    dialog.show();
+}
+
+fn show_error(parent: Option<&gtk::Window>, message: &str, detail: &str) {
+    let dialog = gtk::MessageDialog::new(
+        parent,
+        gtk::DialogFlags::MODAL | gtk::DialogFlags::DESTROY_WITH_PARENT,
+        gtk::MessageType::Error,
+        gtk::ButtonsType::Close,
+        message,
+    );
+    dialog.set_secondary_text(Some(detail));
+    dialog.connect_response(|dialog, _| dialog.destroy());
+    dialog.show();
+}
+
 fn main() {
+    // `run` simulates without a display, for scripts and servers
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().is_some_and(|command| command == "run") {
+        std::process::exit(cli::run(&args[1..]));
+    }
+
     let application = Application::new(
         Some("com.example.GameOfLife"),
         Default::default(),
@@ -415,17 +982,11 @@ fn build_ui(app: &Application) {
     // Create UI state
     let ui = Rc::new(UiState::new(game_state.clone()));
 
-    // Configure drawing area
-    ui.drawing_area.set_content_width(MAX_GRID_WIDTH as i32 * CELL_SIZE);
-    ui.drawing_area.set_content_height(MAX_GRID_HEIGHT as i32 * CELL_SIZE);
-
-    // Create scroll window for the drawing area
-    let scroll_window = gtk::ScrolledWindow::new();
-    scroll_window.set_child(Some(&ui.drawing_area));
-    scroll_window.set_min_content_width(1000);
-    scroll_window.set_min_content_height(600);
-    scroll_window.set_hexpand(true);
-    scroll_window.set_vexpand(true);
+    // Configure drawing area; the camera decides which part of the universe it shows
+    ui.drawing_area.set_content_width(1000);
+    ui.drawing_area.set_content_height(600);
+    ui.drawing_area.set_hexpand(true);
+    ui.drawing_area.set_vexpand(true);
 
     // Create control buttons
     let play_button = Button::with_label("Play");
@@ -433,13 +994,21 @@ fn build_ui(app: &Application) {
     let random_button = Button::with_label("Random");
     let center_random_button = Button::with_label("Center Random");
     let grow_button = Button::with_label("Grow Universe");
-
-    // Create pattern buttons
-    let glider_button = Button::with_label("Glider");
-    let spaceship_button = Button::with_label("Spaceship");
-    let glider_gun_button = Button::with_label("Glider Gun");
-    let pulsar_button = Button::with_label("Pulsar");
-    let r_pentomino_button = Button::with_label("R-Pentomino");
+    let fit_button = Button::with_label("Fit Pattern");
+    let origin_button = Button::with_label("Go to Origin");
+    let open_button = Button::with_label("Open…");
+    let save_button = Button::with_label("Save…");
+    let open_session_button = Button::with_label("Open Session…");
+    open_session_button.set_tooltip_text(Some("Restore a saved universe along with its counters, speed, view and selection"));
+    let save_session_button = Button::with_label("Save Session…");
+    save_session_button.set_tooltip_text(Some("Save the universe along with its counters, speed, view and selection"));
+    let autosave_check = gtk::CheckButton::with_label("Restore on start");
+    autosave_check.set_tooltip_text(Some("Save the session when the window closes and reopen it next time"));
+    autosave_check.set_active(session::autosave_path().exists());
+    let undo_button = Button::with_label("Undo");
+    undo_button.set_tooltip_text(Some("Undo the last edit or run (Ctrl+Z)"));
+    let redo_button = Button::with_label("Redo");
+    redo_button.set_tooltip_text(Some("Redo the last undone step (Ctrl+Shift+Z)"));
 
     // Create speed controls
     let speed_label = gtk::Label::new(Some("Speed:"));
@@ -453,6 +1022,63 @@ fn build_ui(app: &Application) {
     let auto_grow_check = gtk::CheckButton::with_label("Auto-grow");
     auto_grow_check.set_active(true);
 
+    // Create topology selector
+    let topology_label = gtk::Label::new(Some("Topology:"));
+    let topology_names: Vec<String> = Topology::ALL.iter().map(|topology| topology.to_string()).collect();
+    let topology_names: Vec<&str> = topology_names.iter().map(String::as_str).collect();
+    let topology_dropdown = gtk::DropDown::from_strings(&topology_names);
+    topology_dropdown.set_tooltip_text(Some("How the edges of the universe are joined"));
+
+    // Create lattice selector
+    let lattice_label = gtk::Label::new(Some("Lattice:"));
+    let lattice_names: Vec<String> = Lattice::ALL.iter().map(|lattice| lattice.to_string()).collect();
+    let lattice_names: Vec<&str> = lattice_names.iter().map(String::as_str).collect();
+    let lattice_dropdown = gtk::DropDown::from_strings(&lattice_names);
+    lattice_dropdown.set_tooltip_text(Some("Shape of the cells; the rule keeps the neighbour counts the new lattice has"));
+
+    // Create rule entry
+    let rule_label = gtk::Label::new(Some("Rule:"));
+    let rule_entry = gtk::Entry::new();
+    rule_entry.set_text(&Rule::default().to_string());
+    rule_entry.set_width_chars(14);
+    rule_entry.set_tooltip_text(Some(RULE_TOOLTIP));
+    let rule_file_button = Button::with_label("Rule File…");
+    rule_file_button.set_tooltip_text(Some("Load a Golly .rule file with a rule table or tree, colours and icons"));
+
+    // Create stepping controls
+    let step_button = Button::with_label("Step");
+    step_button.set_tooltip_text(Some("Advance one generation (S)"));
+    let step_n_button = Button::with_label("Step N");
+    step_n_button.set_tooltip_text(Some("Advance N generations, or 2^N (N)"));
+    let step_n_label = gtk::Label::new(Some("N:"));
+    let step_n_spin = gtk::SpinButton::with_range(1.0, 1_000_000.0, 1.0);
+    step_n_spin.set_value(10.0);
+    let step_power_check = gtk::CheckButton::with_label("2^N");
+    step_power_check.set_tooltip_text(Some("Treat N as a power of two (up to 2^62)"));
+    let run_until_label = gtk::Label::new(Some("Run until:"));
+    let run_until_dropdown = gtk::DropDown::from_strings(&["Generation", "Population stabilises", "Population below"]);
+    let run_until_spin = gtk::SpinButton::with_range(0.0, 1e15, 1.0);
+    run_until_spin.set_value(1000.0);
+    run_until_spin.set_tooltip_text(Some("Generation to stop at, or population to drop below"));
+    let run_until_button = Button::with_label("Run");
+    run_until_button.set_tooltip_text(Some("Run without the timer until the condition holds (U)"));
+
+    // Create undo memory limit
+    let undo_memory_label = gtk::Label::new(Some("Undo MB:"));
+    let undo_memory_spin = gtk::SpinButton::with_range(0.0, 4096.0, 16.0);
+    undo_memory_spin.set_value(INITIAL_UNDO_MEMORY_MB);
+    undo_memory_spin.set_tooltip_text(Some("Memory kept for undo; the oldest steps are dropped beyond it"));
+
+    // Create engine selector and HashLife step size
+    let engine_label = gtk::Label::new(Some("Engine:"));
+    let engine_names: Vec<&str> = Backend::ALL.iter().map(|backend| backend.label()).collect();
+    let engine_dropdown = gtk::DropDown::from_strings(&engine_names);
+    engine_dropdown.set_tooltip_text(Some("Auto-grow only applies to the Grid engine, Bit-packed only to two-state square rules on the torus and HashLife only to the unbounded topology. Larger than Life and triangular rules always run on Grid. Macrocell files open on HashLife"));
+    let step_label = gtk::Label::new(Some("Step 2^"));
+    let step_spin = ui.step_spin.clone();
+    step_spin.set_tooltip_text(Some("Generations advanced per HashLife update, as a power of two"));
+    step_spin.set_sensitive(false);
+
     // Create button boxes
     let control_box = GtkBox::new(Orientation::Horizontal, 5);
     control_box.append(&play_button);
@@ -460,23 +1086,132 @@ fn build_ui(app: &Application) {
     control_box.append(&random_button);
     control_box.append(&center_random_button);
     control_box.append(&grow_button);
+    control_box.append(&fit_button);
+    control_box.append(&origin_button);
+    control_box.append(&open_button);
+    control_box.append(&save_button);
+    control_box.append(&open_session_button);
+    control_box.append(&save_session_button);
+    control_box.append(&autosave_check);
+    control_box.append(&undo_button);
+    control_box.append(&redo_button);
     control_box.append(&speed_label);
     control_box.append(&speed_scale);
     control_box.append(&auto_grow_check);
-
-    // Create patterns box
-    let patterns_frame = Frame::new(Some("Patterns"));
-    let patterns_box = GtkBox::new(Orientation::Horizontal, 5);
-    patterns_box.set_margin_start(5);
-    patterns_box.set_margin_end(5);
-    patterns_box.set_margin_top(5);
-    patterns_box.set_margin_bottom(5);
-    patterns_box.append(&glider_button);
-    patterns_box.append(&spaceship_button);
-    patterns_box.append(&glider_gun_button);
-    patterns_box.append(&pulsar_button);
-    patterns_box.append(&r_pentomino_button);
-    patterns_frame.set_child(Some(&patterns_box));
+    control_box.append(&topology_label);
+    control_box.append(&topology_dropdown);
+    control_box.append(&lattice_label);
+    control_box.append(&lattice_dropdown);
+    control_box.append(&rule_label);
+    control_box.append(&rule_entry);
+    control_box.append(&rule_file_button);
+    control_box.append(&engine_label);
+    control_box.append(&engine_dropdown);
+    control_box.append(&step_label);
+    control_box.append(&step_spin);
+    control_box.append(&undo_memory_label);
+    control_box.append(&undo_memory_spin);
+
+    // Create tool palette
+    let tools_frame = Frame::new(Some("Tools"));
+    let tools_box = GtkBox::new(Orientation::Vertical, 2);
+    tools_box.set_margin_start(5);
+    tools_box.set_margin_end(5);
+    tools_box.set_margin_top(5);
+    tools_box.set_margin_bottom(5);
+    let mut first_tool_button: Option<gtk::ToggleButton> = None;
+    for tool in Tool::ALL {
+        let button = gtk::ToggleButton::with_label(&tool.label());
+        button.set_tooltip_text(Some(tool.tooltip()));
+        button.set_group(first_tool_button.as_ref());
+        button.set_active(tool == Tool::Pencil);
+        let tool_state = game_state.clone();
+        button.connect_toggled(move |button| {
+            if button.is_active() {
+                tool_state.borrow_mut().tool = tool;
+            }
+        });
+        tools_box.append(&button);
+        first_tool_button.get_or_insert(button);
+    }
+    let draw_state_box = GtkBox::new(Orientation::Horizontal, 5);
+    let draw_state_spin = gtk::SpinButton::with_range(1.0, 255.0, 1.0);
+    draw_state_spin.set_tooltip_text(Some(
+        "State the pencil and shapes paint, for rules with more than two states; rules with fewer get their last state",
+    ));
+    draw_state_box.append(&gtk::Label::new(Some("State:")));
+    draw_state_box.append(&draw_state_spin);
+    tools_box.append(&draw_state_box);
+    tools_frame.set_child(Some(&tools_box));
+
+    // Create selection panel
+    let selection_frame = Frame::new(Some("Selection"));
+    let selection_grid = gtk::Grid::new();
+    selection_grid.set_row_spacing(2);
+    selection_grid.set_column_spacing(2);
+    selection_grid.set_margin_start(5);
+    selection_grid.set_margin_end(5);
+    selection_grid.set_margin_top(5);
+    selection_grid.set_margin_bottom(5);
+    let density_spin = gtk::SpinButton::with_range(1.0, 100.0, 1.0);
+    density_spin.set_value(25.0);
+    density_spin.set_tooltip_text(Some("Percentage of live cells for Random Fill"));
+    let selection_buttons: Vec<(SelectionAction, Button)> = SelectionAction::ALL
+        .iter()
+        .map(|&action| {
+            let button = Button::with_label(action.label());
+            button.set_tooltip_text(Some(action.tooltip()));
+            (action, button)
+        })
+        .collect();
+    for (index, (_, button)) in selection_buttons.iter().enumerate() {
+        selection_grid.attach(button, (index % 2) as i32, (index / 2) as i32, 1, 1);
+    }
+    let density_row = (selection_buttons.len() as i32 + 1) / 2;
+    selection_grid.attach(&gtk::Label::new(Some("Density %:")), 0, density_row, 1, 1);
+    selection_grid.attach(&density_spin, 1, density_row, 1, 1);
+    selection_frame.set_child(Some(&selection_grid));
+
+    let step_box = GtkBox::new(Orientation::Horizontal, 5);
+    step_box.append(&step_button);
+    step_box.append(&step_n_button);
+    step_box.append(&step_n_label);
+    step_box.append(&step_n_spin);
+    step_box.append(&step_power_check);
+    step_box.append(&run_until_label);
+    step_box.append(&run_until_dropdown);
+    step_box.append(&run_until_spin);
+    step_box.append(&run_until_button);
+
+    // Create pattern library panel
+    let library = Rc::new(Library::bundled());
+    let search_entry = gtk::SearchEntry::new();
+    search_entry.set_placeholder_text(Some("Search patterns"));
+    let category_names: Vec<String> = std::iter::once("All categories".to_string())
+        .chain(Category::ALL.iter().map(|category| category.to_string()))
+        .collect();
+    let category_names: Vec<&str> = category_names.iter().map(String::as_str).collect();
+    let category_dropdown = gtk::DropDown::from_strings(&category_names);
+    let pattern_list = gtk::ListBox::new();
+    pattern_list.set_selection_mode(gtk::SelectionMode::Single);
+    let pattern_scroll = gtk::ScrolledWindow::new();
+    pattern_scroll.set_child(Some(&pattern_list));
+    pattern_scroll.set_min_content_width(220);
+    pattern_scroll.set_vexpand(true);
+    let place_button = Button::with_label("Place");
+    place_button.set_tooltip_text(Some("Attach the selected pattern to the mouse to stamp it (or double-click it)"));
+
+    let library_frame = Frame::new(Some("Library"));
+    let library_box = GtkBox::new(Orientation::Vertical, 5);
+    library_box.set_margin_start(5);
+    library_box.set_margin_end(5);
+    library_box.set_margin_top(5);
+    library_box.set_margin_bottom(5);
+    library_box.append(&search_entry);
+    library_box.append(&category_dropdown);
+    library_box.append(&pattern_scroll);
+    library_box.append(&place_button);
+    library_frame.set_child(Some(&library_box));
 
     // Create stats box
     let stats_frame = Frame::new(Some("Statistics"));
@@ -497,14 +1232,30 @@ fn build_ui(app: &Application) {
 
     stats_box.append(&stats_row1);
     stats_box.append(&stats_row2);
+    ui.placement_label.set_xalign(0.0);
+    stats_box.append(&ui.placement_label);
     stats_frame.set_child(Some(&stats_box));
 
     // Create main box
     let main_box = GtkBox::new(Orientation::Vertical, 5);
     main_box.append(&control_box);
-    main_box.append(&patterns_frame);
+    main_box.append(&step_box);
     main_box.append(&stats_frame);
-    main_box.append(&scroll_window);
+    let content_box = GtkBox::new(Orientation::Horizontal, 5);
+    ui.timeline_scale.set_draw_value(true);
+    ui.timeline_scale.set_digits(0);
+    ui.timeline_scale.set_value_pos(gtk::PositionType::Left);
+    ui.timeline_scale.set_tooltip_text(Some("Drag to revisit earlier generations of the run"));
+    let view_box = GtkBox::new(Orientation::Vertical, 5);
+    view_box.append(&ui.drawing_area);
+    view_box.append(&ui.timeline_scale);
+    content_box.append(&view_box);
+    let side_box = GtkBox::new(Orientation::Vertical, 5);
+    side_box.append(&tools_frame);
+    side_box.append(&selection_frame);
+    side_box.append(&library_frame);
+    content_box.append(&side_box);
+    main_box.append(&content_box);
     main_box.set_margin_start(5);
     main_box.set_margin_end(5);
     main_box.set_margin_top(5);
@@ -514,76 +1265,71 @@ fn build_ui(app: &Application) {
     let game_state_ref = game_state.clone();
     let draw_ui = ui.clone();
     draw_ui.drawing_area.set_draw_func(move |_, cr, width, height| {
-        let state = game_state_ref.borrow();
-
-        // Clear background
-        cr.set_source_rgb(0.1, 0.1, 0.1);
-        cr.paint().unwrap();
-
-        // Calculate the visible grid portion
-        let visible_width = (width / CELL_SIZE).min(state.grid_width as i32) as usize;
-        let visible_height = (height / CELL_SIZE).min(state.grid_height as i32) as usize;
-
-        // Draw grid lines (only for smaller grid sizes or they become too dense)
-        if CELL_SIZE >= 5 {
-            cr.set_source_rgb(0.2, 0.2, 0.2);
-            cr.set_line_width(0.5);
-
-            // Draw vertical grid lines
-            for x in 0..=visible_width {
-                cr.move_to(f64::from(x as i32 * CELL_SIZE), 0.0);
-                cr.line_to(f64::from(x as i32 * CELL_SIZE), f64::from(visible_height as i32 * CELL_SIZE));
-            }
+        let mut state = game_state_ref.borrow_mut();
+        let GameState { universe, stepper, camera, renderer, .. } = &mut *state;
+        renderer.draw(cr, universe, stepper.tree(), camera, width, height);
+        let lattice = universe.rule().lattice();
+        let camera = *camera;
+
+        // Draw the pattern being placed as a translucent ghost with its bounding box
+        if let Some((left, top, placement)) = state
+            .placement
+            .as_ref()
+            .and_then(|placement| placement.origin().map(|(left, top)| (left, top, placement)))
+        {
+            let pattern = &placement.pattern;
+            let area = (left, top, left + pattern.width() as i64, top + pattern.height() as i64);
+            cr.set_source_rgba(0.3, 0.6, 1.0, 0.8);
+            cr.set_line_width(1.0);
+            renderer::trace_area(cr, &camera, lattice, area);
+            cr.stroke().unwrap();
 
-            // Draw horizontal grid lines
-            for y in 0..=visible_height {
-                cr.move_to(0.0, f64::from(y as i32 * CELL_SIZE));
-                cr.line_to(f64::from(visible_width as i32 * CELL_SIZE), f64::from(y as i32 * CELL_SIZE));
+            cr.set_source_rgba(0.3, 0.6, 1.0, 0.5);
+            for &(dx, dy, _) in pattern.cells() {
+                trace_cell(cr, &camera, lattice, left + dx, top + dy);
             }
+            cr.fill().unwrap();
+        }
 
+        // Draw the selection as a shaded, dashed rectangle
+        if let Some(selection) = state.selection {
+            let area = (selection.left, selection.top, selection.right, selection.bottom);
+            renderer::trace_area(cr, &camera, lattice, area);
+            cr.set_source_rgba(0.9, 0.8, 0.2, 0.15);
+            cr.fill_preserve().unwrap();
+            cr.set_source_rgba(0.9, 0.8, 0.2, 0.9);
+            cr.set_line_width(1.0);
+            cr.set_dash(&[4.0, 4.0], 0.0);
             cr.stroke().unwrap();
+            cr.set_dash(&[], 0.0);
         }
 
-        // Draw cells
-        cr.set_source_rgb(0.8, 0.8, 0.8);
-        for y in 0..visible_height {
-            for x in 0..visible_width {
-                if x < state.grid_width && y < state.grid_height && state.grid[y][x] {
-                    if CELL_SIZE >= 5 {
-                        // With grid lines, leave a small gap
-                        cr.rectangle(
-                            f64::from(x as i32 * CELL_SIZE + 1), 
-                            f64::from(y as i32 * CELL_SIZE + 1),
-                            f64::from(CELL_SIZE - 1),
-                            f64::from(CELL_SIZE - 1),
-                        );
-                    } else {
-                        // Without grid lines, fill the whole cell
-                        cr.rectangle(
-                            f64::from(x as i32 * CELL_SIZE), 
-                            f64::from(y as i32 * CELL_SIZE),
-                            f64::from(CELL_SIZE),
-                            f64::from(CELL_SIZE),
-                        );
-                    }
-                    cr.fill().unwrap();
-                }
+        // Draw the shape being dragged out the same way
+        if let (Some(stroke), Tool::Shape(shape)) = (&state.stroke, state.tool) {
+            cr.set_source_rgba(0.3, 0.6, 1.0, 0.5);
+            for (x, y) in shape.cells(stroke.start, stroke.last) {
+                trace_cell(cr, &camera, lattice, x, y);
             }
+            cr.fill().unwrap();
         }
     });
 
     // Set up mouse click handler
     let click_ui = ui.clone();
     let click_gesture = gtk::GestureClick::new();
-    click_gesture.connect_pressed(move |_gesture, _n_press, x, y| {
-        let cell_x = (x / CELL_SIZE as f64) as usize;
-        let cell_y = (y / CELL_SIZE as f64) as usize;
-
+    click_gesture.set_button(0);
+    click_gesture.connect_pressed(move |gesture, _n_press, _, _| {
         let mut state = click_ui.game_state.borrow_mut();
-        if cell_x < state.grid_width && cell_y < state.grid_height {
-            state.toggle_cell(cell_x, cell_y);
+        if state.placement.is_some() {
+            // While placing, the primary button stamps and any other button stops placing
+            if gesture.current_button() == gtk::gdk::BUTTON_PRIMARY {
+                state.stamp_placement();
+            } else {
+                state.placement = None;
+            }
             drop(state);
 
+            click_ui.update_placement_hint();
             click_ui.update_statistics();
             click_ui.drawing_area.queue_draw();
         }
@@ -594,12 +1340,115 @@ fn build_ui(app: &Application) {
     let motion_ui = ui.clone();
     let motion_controller = gtk::EventControllerMotion::new();
     motion_controller.connect_motion(move |_, x, y| {
-        let cell_x = (x / CELL_SIZE as f64) as usize;
-        let cell_y = (y / CELL_SIZE as f64) as usize;
+        let mut state = motion_ui.game_state.borrow_mut();
+        state.pointer = Some((x, y));
+        let (cell_x, cell_y) = state.cell_at(x, y);
         motion_ui.coord_label.set_text(&format!("Coordinates: {},{}", cell_x, cell_y));
+
+        if let Some(placement) = state.placement.as_mut() {
+            placement.cursor = Some((cell_x, cell_y));
+            motion_ui.drawing_area.queue_draw();
+        }
+    });
+    let leave_ui = ui.clone();
+    motion_controller.connect_leave(move |_| {
+        let mut state = leave_ui.game_state.borrow_mut();
+        state.pointer = None;
+        if let Some(placement) = state.placement.as_mut() {
+            placement.cursor = None;
+            leave_ui.drawing_area.queue_draw();
+        }
     });
     ui.drawing_area.add_controller(motion_controller);
 
+    // Set up mouse wheel zoom around the pointer
+    let zoom_ui = ui.clone();
+    let scroll_controller = gtk::EventControllerScroll::new(gtk::EventControllerScrollFlags::VERTICAL);
+    scroll_controller.connect_scroll(move |_, _, dy| {
+        let area = &zoom_ui.drawing_area;
+        let mut state = zoom_ui.game_state.borrow_mut();
+        let (x, y) = state
+            .pointer
+            .unwrap_or((f64::from(area.width()) / 2.0, f64::from(area.height()) / 2.0));
+        state.camera.zoom_at(WHEEL_ZOOM_FACTOR.powf(-dy), x, y);
+        drop(state);
+
+        area.queue_draw();
+        glib::Propagation::Stop
+    });
+    ui.drawing_area.add_controller(scroll_controller);
+
+    // Set up drawing with the selected tool by dragging with the primary button
+    let tool_gesture = gtk::GestureDrag::new();
+    tool_gesture.set_button(gtk::gdk::BUTTON_PRIMARY);
+    let begin_tool_ui = ui.clone();
+    tool_gesture.connect_drag_begin(move |_, x, y| {
+        let area = &begin_tool_ui.drawing_area;
+        let mut state = begin_tool_ui.game_state.borrow_mut();
+        // While placing a pattern the primary button stamps it instead
+        if state.placement.is_some() {
+            return;
+        }
+        let cell = state.cell_at(x, y);
+        state.begin_stroke(cell, f64::from(area.width()), f64::from(area.height()));
+        drop(state);
+
+        begin_tool_ui.update_placement_hint();
+        begin_tool_ui.update_statistics();
+        area.queue_draw();
+    });
+    let update_tool_ui = ui.clone();
+    tool_gesture.connect_drag_update(move |gesture, dx, dy| {
+        let Some((x, y)) = gesture.start_point() else {
+            return;
+        };
+        let mut state = update_tool_ui.game_state.borrow_mut();
+        let cell = state.cell_at(x + dx, y + dy);
+        state.continue_stroke(cell);
+        drop(state);
+
+        update_tool_ui.update_placement_hint();
+        update_tool_ui.update_statistics();
+        update_tool_ui.drawing_area.queue_draw();
+    });
+    let end_tool_ui = ui.clone();
+    tool_gesture.connect_drag_end(move |_, _, _| {
+        end_tool_ui.game_state.borrow_mut().end_stroke();
+        end_tool_ui.update_statistics();
+        end_tool_ui.drawing_area.queue_draw();
+    });
+    ui.drawing_area.add_controller(tool_gesture);
+
+    // Set up the selection buttons
+    for (action, button) in selection_buttons {
+        let selection_ui = ui.clone();
+        let selection_density = density_spin.clone();
+        button.connect_clicked(move |_| {
+            selection_ui.apply_selection_action(action, selection_density.value() / 100.0);
+        });
+    }
+
+    // Set up panning by dragging with the middle or right mouse button
+    let pan_ui = ui.clone();
+    let pan_start: Rc<Cell<Option<Camera>>> = Rc::new(Cell::new(None));
+    let pan_gesture = gtk::GestureDrag::new();
+    pan_gesture.set_button(0);
+    let begin_pan_ui = pan_ui.clone();
+    let begin_pan_start = pan_start.clone();
+    pan_gesture.connect_drag_begin(move |gesture, _, _| {
+        let button = gesture.current_button();
+        let panning = button == gtk::gdk::BUTTON_MIDDLE || button == gtk::gdk::BUTTON_SECONDARY;
+        begin_pan_start.set(panning.then(|| begin_pan_ui.game_state.borrow().camera));
+    });
+    pan_gesture.connect_drag_update(move |_, dx, dy| {
+        if let Some(mut camera) = pan_start.get() {
+            camera.pan(dx, dy);
+            pan_ui.game_state.borrow_mut().camera = camera;
+            pan_ui.drawing_area.queue_draw();
+        }
+    });
+    ui.drawing_area.add_controller(pan_gesture);
+
     // Set up play button
     let play_ui = ui.clone();
     play_button.connect_clicked(move |button| {
@@ -631,10 +1480,124 @@ fn build_ui(app: &Application) {
         }
     });
 
+    // Set up runs without the timer: they simulate for a frame's time per
+    // main loop iteration until the stop condition holds or Play takes over
+    let run_ui = ui.clone();
+    let run_play_button = play_button.clone();
+    let run_button = run_until_button.clone();
+    let start_run = Rc::new(move |condition: StopCondition| {
+        if run_ui.game_state.borrow().running {
+            run_play_button.emit_clicked();
+        }
+
+        let mut state = run_ui.game_state.borrow_mut();
+        if let Some(id) = state.run_until_id.take() {
+            id.remove();
+        }
+        state.run_until = Some(RunUntil::new(condition));
+        run_button.set_label("Stop");
+
+        let loop_ui = run_ui.clone();
+        let loop_button = run_button.clone();
+        let id = glib::idle_add_local(move || {
+            let mut state = loop_ui.game_state.borrow_mut();
+            let started = Instant::now();
+            let done = loop {
+                if state.running || state.run_step() {
+                    break true;
+                }
+                if started.elapsed() >= RUN_FRAME_TIME {
+                    break false;
+                }
+            };
+            if done {
+                state.run_until = None;
+                state.run_until_id = None;
+                loop_button.set_label("Run");
+            }
+            drop(state);
+
+            loop_ui.update_statistics();
+            loop_ui.drawing_area.queue_draw();
+            if done {
+                glib::ControlFlow::Break
+            } else {
+                glib::ControlFlow::Continue
+            }
+        });
+        state.run_until_id = Some(id);
+    });
+
+    // Set up Step
+    let step_ui = ui.clone();
+    let step_once = Rc::new(move || {
+        step_ui.game_state.borrow_mut().advance(1);
+        step_ui.update_statistics();
+        step_ui.drawing_area.queue_draw();
+    });
+    let button_step_once = step_once.clone();
+    step_button.connect_clicked(move |_| button_step_once());
+
+    // Set up Step N as a run until the generation N steps ahead
+    let step_n_ui = ui.clone();
+    let step_n_run = start_run.clone();
+    let step_n_spin_ref = step_n_spin.clone();
+    let step_n_power = step_power_check.clone();
+    let step_n = Rc::new(move || {
+        let n = step_n_spin_ref.value() as u64;
+        let generations = if step_n_power.is_active() { 1 << n.min(62) } else { n };
+        let generation = step_n_ui.game_state.borrow().stats().generation;
+        step_n_run(StopCondition::Generation(generation.saturating_add(generations)));
+    });
+    let button_step_n = step_n.clone();
+    step_n_button.connect_clicked(move |_| button_step_n());
+
+    // Set up Run Until, which stops the run in progress when pressed again
+    let until_ui = ui.clone();
+    let until_dropdown = run_until_dropdown.clone();
+    let until_spin = run_until_spin.clone();
+    let until_button = run_until_button.clone();
+    let toggle_run_until = Rc::new(move || {
+        let mut state = until_ui.game_state.borrow_mut();
+        if let Some(id) = state.run_until_id.take() {
+            id.remove();
+            state.run_until = None;
+            until_button.set_label("Run");
+            return;
+        }
+        drop(state);
+
+        let value = until_spin.value() as u64;
+        start_run(match until_dropdown.selected() {
+            0 => StopCondition::Generation(value),
+            1 => StopCondition::Stable,
+            _ => StopCondition::PopulationBelow(value),
+        });
+    });
+    let button_run_until = toggle_run_until.clone();
+    run_until_button.connect_clicked(move |_| button_run_until());
+    let stable_spin = run_until_spin.clone();
+    run_until_dropdown.connect_selected_notify(move |dropdown| {
+        stable_spin.set_sensitive(dropdown.selected() != 1);
+    });
+
+    // Set up the timeline slider
+    let timeline_ui = ui.clone();
+    ui.timeline_scale.connect_value_changed(move |scale| {
+        let generation = scale.value().round() as u64;
+        if generation == timeline_ui.game_state.borrow().stats().generation {
+            return;
+        }
+        timeline_ui.game_state.borrow_mut().seek(generation);
+
+        timeline_ui.update_statistics();
+        timeline_ui.drawing_area.queue_draw();
+    });
+
     // Set up clear button
     let clear_ui = ui.clone();
     clear_button.connect_clicked(move |_| {
-        clear_ui.game_state.borrow_mut().clear();
+        clear_ui.game_state.borrow_mut().edit(Universe::clear);
         clear_ui.update_statistics();
         clear_ui.drawing_area.queue_draw();
     });
@@ -642,7 +1605,7 @@ fn build_ui(app: &Application) {
     // Set up random button
     let random_ui = ui.clone();
     random_button.connect_clicked(move |_| {
-        random_ui.game_state.borrow_mut().randomize();
+        random_ui.game_state.borrow_mut().edit(Universe::randomize);
         random_ui.update_statistics();
         random_ui.drawing_area.queue_draw();
     });
@@ -650,73 +1613,365 @@ fn build_ui(app: &Application) {
     // Set up center random button
     let center_ui = ui.clone();
     center_random_button.connect_clicked(move |_| {
-        center_ui.game_state.borrow_mut().randomize_center();
+        center_ui.game_state.borrow_mut().edit(Universe::randomize_center);
         center_ui.update_statistics();
         center_ui.drawing_area.queue_draw();
     });
 
-    // Set up glider button
-    let glider_ui = ui.clone();
-    glider_button.connect_clicked(move |_| {
-        glider_ui.game_state.borrow_mut().add_glider(10, 10);
-        glider_ui.update_statistics();
-        glider_ui.drawing_area.queue_draw();
+    // Set up the library list, refilled whenever the search or category changes
+    let refresh_library = {
+        let library = library.clone();
+        let pattern_list = pattern_list.clone();
+        let search_entry = search_entry.clone();
+        let category_dropdown = category_dropdown.clone();
+        Rc::new(move || {
+            while let Some(row) = pattern_list.row_at_index(0) {
+                pattern_list.remove(&row);
+            }
+
+            // The first dropdown item stands for every category
+            let category = (category_dropdown.selected() as usize)
+                .checked_sub(1)
+                .map(|index| Category::ALL[index]);
+            let query = search_entry.text();
+            for entry in library.search(&query, category) {
+                let pattern = &entry.pattern;
+                let label = gtk::Label::new(None);
+                label.set_markup(&format!(
+                    "{}\n<small>{} · {}x{}</small>",
+                    gtk::glib::markup_escape_text(entry.name()),
+                    entry.category,
+                    pattern.width(),
+                    pattern.height()
+                ));
+                label.set_xalign(0.0);
+                label.set_tooltip_text(Some(&pattern.comments.join("\n")));
+
+                let row = gtk::ListBoxRow::new();
+                row.set_widget_name(entry.name());
+                row.set_child(Some(&label));
+                pattern_list.append(&row);
+            }
+        })
+    };
+    refresh_library();
+    let search_refresh = refresh_library.clone();
+    search_entry.connect_search_changed(move |_| search_refresh());
+    category_dropdown.connect_selected_notify(move |_| refresh_library());
+
+    // Set up placing library patterns
+    let place_ui = ui.clone();
+    let place_library = library.clone();
+    let place_selected = Rc::new(move |list: &gtk::ListBox| {
+        let Some(row) = list.selected_row() else {
+            return;
+        };
+        let Some(entry) = place_library.get(&row.widget_name()) else {
+            return;
+        };
+
+        let result = place_ui.game_state.borrow_mut().begin_placement(entry.pattern.clone());
+        place_ui.update_placement_hint();
+        place_ui.update_statistics();
+        place_ui.drawing_area.queue_draw();
+        if let Err(err) = result {
+            let parent = list.root().and_downcast::<gtk::Window>();
+            show_error(parent.as_ref(), &format!("Could not place {}", entry.name()), &err);
+        }
     });
+    let place_list = pattern_list.clone();
+    let button_place = place_selected.clone();
+    place_button.connect_clicked(move |_| button_place(&place_list));
+    pattern_list.connect_row_activated(move |list, _| place_selected(list));
+
+    // Set up view buttons
+    let fit_ui = ui.clone();
+    fit_button.connect_clicked(move |_| {
+        let area = &fit_ui.drawing_area;
+        fit_ui.game_state.borrow_mut().fit_view(f64::from(area.width()), f64::from(area.height()));
+        area.queue_draw();
+    });
+    let origin_ui = ui.clone();
+    origin_button.connect_clicked(move |_| {
+        let area = &origin_ui.drawing_area;
+        origin_ui.game_state.borrow_mut().camera.center_on(0.0, 0.0, f64::from(area.width()), f64::from(area.height()));
+        area.queue_draw();
+    });
+
+    // Set up grow button
+    let grow_ui = ui.clone();
+    grow_button.connect_clicked(move |_| {
+        let mut state = grow_ui.game_state.borrow_mut();
+        if state.edit(Universe::grow) {
+            println!("Universe expanded to {}x{}", state.universe.width(), state.universe.height());
+        }
+        drop(state);
 
-    // Set up spaceship button
-    let spaceship_ui = ui.clone();
-    spaceship_button.connect_clicked(move |_| {
-        spaceship_ui.game_state.borrow_mut().add_spaceship(20, 20);
-        spaceship_ui.update_statistics();
-        spaceship_ui.drawing_area.queue_draw();
+        grow_ui.update_statistics();
+        grow_ui.drawing_area.queue_draw();
     });
 
-    // Set up glider gun button
-    let gun_ui = ui.clone();
-    glider_gun_button.connect_clicked(move |_| {
-        gun_ui.game_state.borrow_mut().add_glider_gun(10, 10);
-        gun_ui.update_statistics();
-        gun_ui.drawing_area.queue_draw();
+    // Set up open button
+    let open_ui = ui.clone();
+    let open_rule_entry = rule_entry.clone();
+    let open_lattice_dropdown = lattice_dropdown.clone();
+    let open_topology_dropdown = topology_dropdown.clone();
+    let open_engine_dropdown = engine_dropdown.clone();
+    open_button.connect_clicked(move |button| {
+        let parent = button.root().and_downcast::<gtk::Window>();
+        let open_ui = open_ui.clone();
+        let open_rule_entry = open_rule_entry.clone();
+        let open_lattice_dropdown = open_lattice_dropdown.clone();
+        let open_topology_dropdown = open_topology_dropdown.clone();
+        let open_engine_dropdown = open_engine_dropdown.clone();
+        let error_parent = parent.clone();
+        choose_pattern_file(parent, gtk::FileChooserAction::Open, move |path| {
+            let mut state = open_ui.game_state.borrow_mut();
+            let result = read_pattern(path).and_then(|file| match file {
+                PatternFile::Cells(pattern) => state.load_pattern(&pattern),
+                PatternFile::Tree(macrocell) => state.load_tree(macrocell),
+            });
+            let (rule, topology, backend) = (state.universe.rule(), state.universe.topology(), state.stepper.backend);
+            drop(state);
+
+            open_rule_entry.set_text(&rule.to_string());
+            select_lattice(&open_lattice_dropdown, rule.lattice());
+            if let Some(index) = Topology::ALL.iter().position(|&item| item == topology) {
+                open_topology_dropdown.set_selected(index as u32);
+            }
+            if let Some(index) = Backend::ALL.iter().position(|&item| item == backend) {
+                open_engine_dropdown.set_selected(index as u32);
+            }
+            open_ui.update_statistics();
+            open_ui.drawing_area.queue_draw();
+            if let Err(err) = result {
+                show_error(error_parent.as_ref(), "Could not open pattern", &err);
+            }
+        });
     });
 
-    // Set up pulsar button
-    let pulsar_ui = ui.clone();
-    pulsar_button.connect_clicked(move |_| {
-        pulsar_ui.game_state.borrow_mut().add_pulsar(30, 20);
-        pulsar_ui.update_statistics();
-        pulsar_ui.drawing_area.queue_draw();
+    // Set up save button
+    let save_ui = ui.clone();
+    save_button.connect_clicked(move |button| {
+        let parent = button.root().and_downcast::<gtk::Window>();
+        let save_ui = save_ui.clone();
+        let error_parent = parent.clone();
+        choose_pattern_file(parent, gtk::FileChooserAction::Save, move |path| {
+            let state = save_ui.game_state.borrow();
+            if let Err(err) = write_pattern(path, &state.universe, state.stepper.tree()) {
+                show_error(error_parent.as_ref(), "Could not save pattern", &err.to_string());
+            }
+        });
     });
 
-    // Set up r-pentomino button 
-    let r_ui = ui.clone();
-    r_pentomino_button.connect_clicked(move |_| {
-        // Cache the current width and height
-        let grid_width;
-        let grid_height;
-        {
-            let state = r_ui.game_state.borrow();
-            grid_width = state.grid_width;
-            grid_height = state.grid_height;
+    // Set up session files, bringing the controls in line with the restored
+    // session
+    let session_ui = ui.clone();
+    let session_rule_entry = rule_entry.clone();
+    let session_lattice_dropdown = lattice_dropdown.clone();
+    let session_topology_dropdown = topology_dropdown.clone();
+    let session_auto_grow_check = auto_grow_check.clone();
+    let session_speed_scale = speed_scale.clone();
+    let restore_session = Rc::new(move |session: Session| {
+        let mut state = session_ui.game_state.borrow_mut();
+        state.restore_session(session);
+        let universe = &state.universe;
+        let (rule, topology, auto_grow) = (universe.rule(), universe.topology(), universe.auto_grow());
+        let (backend, speed) = (state.stepper.backend, state.speed);
+        drop(state);
+
+        session_rule_entry.set_text(&rule.to_string());
+        select_lattice(&session_lattice_dropdown, rule.lattice());
+        if let Some(index) = Topology::ALL.iter().position(|&item| item == topology) {
+            session_topology_dropdown.set_selected(index as u32);
         }
+        session_auto_grow_check.set_active(auto_grow);
+        session_auto_grow_check.set_sensitive(topology != Topology::Unbounded && backend == Backend::Grid);
+        session_speed_scale.set_value(speed as f64);
+        session_ui.update_placement_hint();
+        session_ui.update_statistics();
+        session_ui.drawing_area.queue_draw();
+    });
 
-        // Now use the cached values
-        r_ui.game_state.borrow_mut().add_r_pentomino(grid_width / 2, grid_height / 2);
-        r_ui.update_statistics();
-        r_ui.drawing_area.queue_draw();
+    let open_session = restore_session.clone();
+    open_session_button.connect_clicked(move |button| {
+        let parent = button.root().and_downcast::<gtk::Window>();
+        let open_session = open_session.clone();
+        let error_parent = parent.clone();
+        choose_session_file(parent, gtk::FileChooserAction::Open, move |path| {
+            match Session::load(path) {
+                Ok(session) => open_session(session),
+                Err(err) => show_error(error_parent.as_ref(), "Could not open session", &err),
+            }
+        });
     });
 
-    // Set up grow button
-    let grow_ui = ui.clone();
-    grow_button.connect_clicked(move |_| {
-        grow_ui.game_state.borrow_mut().grow_universe();
-        grow_ui.update_statistics();
-        grow_ui.drawing_area.queue_draw();
+    let save_session_ui = ui.clone();
+    save_session_button.connect_clicked(move |button| {
+        let parent = button.root().and_downcast::<gtk::Window>();
+        let save_session_ui = save_session_ui.clone();
+        let error_parent = parent.clone();
+        choose_session_file(parent, gtk::FileChooserAction::Save, move |path| {
+            if let Err(err) = save_session_ui.game_state.borrow_mut().session().save(path) {
+                show_error(error_parent.as_ref(), "Could not save session", &err);
+            }
+        });
+    });
+
+    // Set up undo and redo, bringing the rule and topology controls in line
+    // with the restored universe
+    let history_ui = ui.clone();
+    let history_rule_entry = rule_entry.clone();
+    let history_lattice_dropdown = lattice_dropdown.clone();
+    let history_topology_dropdown = topology_dropdown.clone();
+    let step_history = Rc::new(move |redo: bool| {
+        let mut state = history_ui.game_state.borrow_mut();
+        state.step_history(redo);
+        let (rule, topology) = (state.universe.rule(), state.universe.topology());
+        drop(state);
+
+        history_rule_entry.set_text(&rule.to_string());
+        select_lattice(&history_lattice_dropdown, rule.lattice());
+        if let Some(index) = Topology::ALL.iter().position(|&item| item == topology) {
+            history_topology_dropdown.set_selected(index as u32);
+        }
+        history_ui.update_statistics();
+        history_ui.drawing_area.queue_draw();
+    });
+    let undo_history = step_history.clone();
+    undo_button.connect_clicked(move |_| undo_history(false));
+    let redo_history = step_history.clone();
+    redo_button.connect_clicked(move |_| redo_history(true));
+
+    // Set up undo memory limit
+    let memory_ui = ui.clone();
+    undo_memory_spin.connect_value_changed(move |spin| {
+        let limit = (spin.value() * 1024.0 * 1024.0) as usize;
+        memory_ui.game_state.borrow_mut().history.set_memory_limit(limit);
     });
 
     // Set up auto-grow checkbox
     let auto_ui = ui.clone();
     auto_grow_check.connect_toggled(move |check| {
-        auto_ui.game_state.borrow_mut().auto_grow = check.is_active();
+        auto_ui.game_state.borrow_mut().universe.set_auto_grow(check.is_active());
+    });
+
+    // Set up topology selector
+    let topology_ui = ui.clone();
+    let topology_auto_grow_check = auto_grow_check.clone();
+    topology_dropdown.connect_selected_notify(move |dropdown| {
+        let topology = Topology::ALL[dropdown.selected() as usize];
+        let mut state = topology_ui.game_state.borrow_mut();
+        // Undo and redo set the dropdown to match the universe
+        if state.universe.topology() == topology {
+            return;
+        }
+        state.edit(|universe| universe.set_topology(topology));
+        topology_auto_grow_check.set_sensitive(topology != Topology::Unbounded && state.stepper.backend == Backend::Grid);
+        drop(state);
+
+        topology_ui.update_statistics();
+        topology_ui.drawing_area.queue_draw();
+    });
+
+    // Set up lattice selector, moving the rule onto the chosen lattice
+    let lattice_ui = ui.clone();
+    let lattice_rule_entry = rule_entry.clone();
+    lattice_dropdown.connect_selected_notify(move |dropdown| {
+        let lattice = Lattice::ALL[dropdown.selected() as usize];
+        let mut state = lattice_ui.game_state.borrow_mut();
+        // Undo, redo and new rules set the dropdown to match the universe
+        if state.universe.rule().lattice() == lattice {
+            return;
+        }
+        let rule = state.universe.rule().with_lattice(lattice);
+        state.edit(|universe| universe.set_rule(rule.clone()));
+        drop(state);
+
+        // Larger than Life rules stay on the square lattice
+        lattice_rule_entry.set_text(&rule.to_string());
+        select_lattice(dropdown, rule.lattice());
+        lattice_ui.drawing_area.queue_draw();
+    });
+
+    // Set up rule entry, applying the rule when Enter is pressed
+    let rule_ui = ui.clone();
+    let rule_lattice_dropdown = lattice_dropdown.clone();
+    rule_entry.connect_activate(move |entry| {
+        match entry.text().parse::<Rule>() {
+            Ok(rule) => {
+                rule_ui.game_state.borrow_mut().edit(|universe| universe.set_rule(rule.clone()));
+                entry.set_text(&rule.to_string());
+                select_lattice(&rule_lattice_dropdown, rule.lattice());
+                rule_ui.drawing_area.queue_draw();
+                entry.remove_css_class("error");
+                entry.set_tooltip_text(Some(RULE_TOOLTIP));
+            }
+            Err(err) => {
+                entry.add_css_class("error");
+                entry.set_tooltip_text(Some(&err.to_string()));
+            }
+        }
+    });
+
+    // Set up rule file button, replacing the rule with the file's table
+    let rule_file_ui = ui.clone();
+    let rule_file_entry = rule_entry.clone();
+    let rule_file_lattice_dropdown = lattice_dropdown.clone();
+    rule_file_button.connect_clicked(move |button| {
+        let parent = button.root().and_downcast::<gtk::Window>();
+        let rule_file_ui = rule_file_ui.clone();
+        let rule_file_entry = rule_file_entry.clone();
+        let rule_file_lattice_dropdown = rule_file_lattice_dropdown.clone();
+        let error_parent = parent.clone();
+        choose_rule_file(parent, move |path| {
+            let table = std::fs::read_to_string(path)
+                .map_err(|err| err.to_string())
+                .and_then(|text| text.parse::<RuleTable>().map_err(|err| err.to_string()));
+            let rule = match table {
+                Ok(table) => Rule::from_table(table),
+                Err(err) => {
+                    show_error(error_parent.as_ref(), "Could not load rule", &err);
+                    return;
+                }
+            };
+            rule_file_ui.game_state.borrow_mut().edit(|universe| universe.set_rule(rule.clone()));
+            rule_file_entry.set_text(&rule.to_string());
+            rule_file_entry.remove_css_class("error");
+            rule_file_entry.set_tooltip_text(Some(RULE_TOOLTIP));
+            select_lattice(&rule_file_lattice_dropdown, rule.lattice());
+            rule_file_ui.drawing_area.queue_draw();
+        });
+    });
+
+    // Set up engine selector
+    let engine_ui = ui.clone();
+    let engine_auto_grow_check = auto_grow_check.clone();
+    engine_dropdown.connect_selected_notify(move |dropdown| {
+        let backend = Backend::ALL[dropdown.selected() as usize];
+        let mut state = engine_ui.game_state.borrow_mut();
+        // Opening a Macrocell file switches to HashLife itself, keeping its tree
+        if state.stepper.backend != backend {
+            state.sync_engines();
+            state.stepper.backend = backend;
+            state.invalidate_engines();
+        }
+        engine_auto_grow_check.set_sensitive(backend == Backend::Grid && state.universe.topology() != Topology::Unbounded);
+        drop(state);
+
+        engine_ui.update_statistics();
+    });
+
+    // Set up the state the draw tools paint
+    let draw_state_ui = ui.clone();
+    draw_state_spin.connect_value_changed(move |spin| {
+        draw_state_ui.game_state.borrow_mut().draw_state = spin.value_as_int() as u8;
+    });
+
+    // Set up HashLife step size
+    let step_ui = ui.clone();
+    step_spin.connect_value_changed(move |spin| {
+        step_ui.game_state.borrow_mut().step_exponent = spin.value_as_int() as u8;
     });
 
     // Set up speed slider
@@ -748,5 +2003,102 @@ fn build_ui(app: &Application) {
     window.set_title(Some("Conway's Game of Life - Pattern Explorer"));
     window.set_default_size(1024, 768);
     window.set_child(Some(&main_box));
+
+    // Set up placement keys: rotate, flip, paste mode and cancel
+    let key_ui = ui.clone();
+    let key_controller = gtk::EventControllerKey::new();
+    let key_density = density_spin.clone();
+    key_controller.connect_key_pressed(move |_, key, _, modifiers| {
+        let control = modifiers.contains(gtk::gdk::ModifierType::CONTROL_MASK);
+        if control && key.to_lower() == gtk::gdk::Key::z {
+            step_history(modifiers.contains(gtk::gdk::ModifierType::SHIFT_MASK));
+            return glib::Propagation::Stop;
+        }
+
+        let mut state = key_ui.game_state.borrow_mut();
+        if state.placement.is_none() && !control {
+            // Stepping shortcuts
+            let step: Option<&dyn Fn()> = match key.to_lower() {
+                gtk::gdk::Key::s => Some(&*step_once),
+                gtk::gdk::Key::n => Some(&*step_n),
+                gtk::gdk::Key::u => Some(&*toggle_run_until),
+                _ => None,
+            };
+            if let Some(step) = step {
+                drop(state);
+                step();
+                return glib::Propagation::Stop;
+            }
+        }
+
+        if state.placement.is_none() {
+            // Selection shortcuts
+            let action = match key.to_lower() {
+                gtk::gdk::Key::c if control => SelectionAction::Copy,
+                gtk::gdk::Key::x if control => SelectionAction::Cut,
+                gtk::gdk::Key::v if control => SelectionAction::Paste,
+                gtk::gdk::Key::Delete => SelectionAction::Clear,
+                gtk::gdk::Key::Escape if state.selection.is_some() => {
+                    state.selection = None;
+                    drop(state);
+                    key_ui.update_placement_hint();
+                    key_ui.drawing_area.queue_draw();
+                    return glib::Propagation::Stop;
+                }
+                _ => return glib::Propagation::Proceed,
+            };
+            drop(state);
+            key_ui.apply_selection_action(action, key_density.value() / 100.0);
+            return glib::Propagation::Stop;
+        }
+
+        if key == gtk::gdk::Key::Escape {
+            state.placement = None;
+        } else if let Some(placement) = state.placement.as_mut() {
+            match key.to_lower() {
+                gtk::gdk::Key::r => placement.pattern = placement.pattern.rotated_clockwise(),
+                gtk::gdk::Key::h => placement.pattern = placement.pattern.flipped_horizontally(),
+                gtk::gdk::Key::v => placement.pattern = placement.pattern.flipped_vertically(),
+                gtk::gdk::Key::m => {
+                    let index = PasteMode::ALL.iter().position(|&mode| mode == placement.mode).unwrap_or(0);
+                    placement.mode = PasteMode::ALL[(index + 1) % PasteMode::ALL.len()];
+                }
+                _ => return glib::Propagation::Proceed,
+            }
+        }
+        drop(state);
+
+        key_ui.update_placement_hint();
+        key_ui.drawing_area.queue_draw();
+        glib::Propagation::Stop
+    });
+    window.add_controller(key_controller);
+
+    // Save the session on close if it is to be restored, and forget it otherwise
+    let close_state = game_state.clone();
+    window.connect_close_request(move |_| {
+        let path = session::autosave_path();
+        let result = if autosave_check.is_active() {
+            close_state.borrow_mut().session().save(&path)
+        } else if path.exists() {
+            std::fs::remove_file(&path).map_err(|err| err.to_string())
+        } else {
+            Ok(())
+        };
+        if let Err(err) = result {
+            eprintln!("Could not save the session to {}: {}", path.display(), err);
+        }
+        glib::Propagation::Proceed
+    });
+
     window.present();
+
+    // Pick up where the last session left off
+    let autosave_path = session::autosave_path();
+    if autosave_path.exists() {
+        match Session::load(&autosave_path) {
+            Ok(session) => restore_session(session),
+            Err(err) => show_error(Some(window.upcast_ref()), "Could not restore the last session", &err),
+        }
+    }
 }
@@ -0,0 +1,492 @@
+// Draws the universe through the camera.
+//
+// Live cells are rasterized into a cached bitmap with one pixel per cell,
+// which the camera scales onto the screen, so a frame costs a single blit
+// however many cells are alive. Only the cells in (or just around) the view
+// are rasterized. The bitmap is rebuilt when the universe steps or the view
+// leaves the cached area; edits repaint just the cells they touched.
+//
+// Hexagonal and triangular cells are drawn as shapes, one path per state,
+// once they are large enough to tell apart. Hexagons are sheared into
+// Golly's skewed layout, the bitmap along with them when zoomed out. Rules read from `.rule` files
+// bring their own colours, and icons that replace the plain cells when
+// zoomed in.
+//
+// While HashLife runs ahead of the universe its tree is drawn instead. Far
+// out, whole blocks of the tree are counted per pixel, so even a pattern with
+// billions of cells is shaded without visiting them one by one.
+
+use gtk4 as gtk;
+use gtk::cairo;
+
+use cellverse_core::{HashLife, Icon, Lattice, Rule, RuleTable, Topology, Universe};
+
+use crate::camera::Camera;
+
+const BACKGROUND: (f64, f64, f64) = (0.1, 0.1, 0.1);
+const OUTSIDE: (f64, f64, f64) = (0.05, 0.05, 0.05); // Beyond the edges of a bounded universe
+const GRID: (f64, f64, f64) = (0.2, 0.2, 0.2);
+const LIVE: [u8; 4] = [204, 204, 204, 255]; // Live cell pixel, in Cairo's BGRA byte order
+const DYING_FIRST: (f64, f64, f64) = (1.0, 0.75, 0.2); // Cells that just stopped living under a Generations rule
+const DYING_LAST: (f64, f64, f64) = (0.35, 0.1, 0.25); // Cells about to die
+
+// Grid lines are drawn from this zoom on
+const GRID_LINE_ZOOM: f64 = 5.0;
+
+// Hexagons and triangles are drawn as shapes from this zoom on, and as
+// squares below it
+const SHAPE_ZOOM: f64 = 3.0;
+
+// Rule table icons are drawn from this zoom on
+const ICON_ZOOM: f64 = 7.0;
+
+type Rect = (i64, i64, i64, i64); // Cells from (left, top) up to (right, bottom), exclusive
+
+// The cells to draw: the universe's, or the HashLife tree's while it runs ahead
+#[derive(Clone, Copy)]
+enum Cells<'a> {
+    Universe(&'a Universe),
+    Tree(&'a HashLife),
+}
+
+impl Cells<'_> {
+    fn for_each_cell_in(self, (left, top, right, bottom): Rect, f: &mut dyn FnMut(i64, i64, u8)) {
+        match self {
+            Cells::Universe(universe) => universe.for_each_cell_in(left, top, right, bottom, f),
+            Cells::Tree(tree) => tree.for_each_cell(left, top, (right - left) as u64, (bottom - top) as u64, f),
+        }
+    }
+
+    // Call `f` with blocks of up to `size` x `size` cells in `area`, by their
+    // top-left cell and how many of their cells are shown: live ones, or any
+    // not dead when `all_states`
+    fn for_each_block_in(self, area: Rect, size: i64, all_states: bool, f: &mut dyn FnMut(i64, i64, u64)) {
+        let (left, top, right, bottom) = area;
+        match self {
+            Cells::Tree(tree) => {
+                let level = (size.max(1) as u64).ilog2() as u8;
+                tree.for_each_block(left, top, (right - left) as u64, (bottom - top) as u64, level, &mut |x, y, live, occupied| {
+                    f(x, y, if all_states { occupied } else { live })
+                });
+            }
+            Cells::Universe(universe) if all_states => universe.for_each_cell_in(left, top, right, bottom, &mut |x, y, _| f(x, y, 1)),
+            Cells::Universe(universe) => universe.for_each_live_in(left, top, right, bottom, &mut |x, y| f(x, y, 1)),
+        }
+    }
+}
+
+// A bitmap of the universe and what it shows
+enum Cache {
+    // One pixel per cell for the cells in `window`, scaled by the camera
+    Cells { window: Rect, surface: cairo::ImageSurface },
+    // One pixel per screen pixel, shaded by the density of the cells under it
+    Density { camera: Camera, size: (i32, i32), surface: cairo::ImageSurface },
+}
+
+#[derive(Default)]
+pub struct Renderer {
+    cache: Option<Cache>,
+    dirty: Option<Rect>, // Cells edited since the cache was drawn
+}
+
+impl Renderer {
+    /// Forgets the cached bitmap, e.g. after the universe stepped.
+    pub fn invalidate(&mut self) {
+        self.cache = None;
+        self.dirty = None;
+    }
+
+    /// Marks the cells in `left..right` x `top..bottom` as changed, so only
+    /// they are repainted on the next draw.
+    pub fn invalidate_cells(&mut self, left: i64, top: i64, right: i64, bottom: i64) {
+        self.dirty = Some(match self.dirty {
+            None => (left, top, right, bottom),
+            Some(dirty) => (dirty.0.min(left), dirty.1.min(top), dirty.2.max(right), dirty.3.max(bottom)),
+        });
+    }
+
+    /// Draws `universe` into a `width` x `height` area, with the cells of
+    /// `tree` in place of its own if HashLife has run ahead of it.
+    pub fn draw(&mut self, cr: &cairo::Context, universe: &Universe, tree: Option<&HashLife>, camera: &Camera, width: i32, height: i32) {
+        let bounds = (universe.topology() != Topology::Unbounded).then(|| (universe.width() as i64, universe.height() as i64));
+        let rule = universe.rule();
+        let background = rule.table().and_then(|table| table.colour(0)).map_or(BACKGROUND, rgb);
+
+        let lattice = rule.lattice();
+        let cells = tree.map_or(Cells::Universe(universe), Cells::Tree);
+
+        // Clear background, marking the area outside a bounded universe darker
+        if let Some((grid_width, grid_height)) = bounds {
+            set_colour(cr, OUTSIDE);
+            cr.paint().unwrap();
+            set_colour(cr, background);
+            trace_area(cr, camera, lattice, (0, 0, grid_width, grid_height));
+            cr.fill().unwrap();
+        } else {
+            set_colour(cr, background);
+            cr.paint().unwrap();
+        }
+
+        self.apply_dirty(cells, &rule, bounds);
+
+        let visible = clip(visible_cells(camera, lattice, f64::from(width), f64::from(height)), bounds);
+        let (left, top, right, bottom) = visible;
+        if left >= right || top >= bottom {
+            return;
+        }
+
+        let icons = rule
+            .table()
+            .filter(|table| table.has_icons() && lattice == Lattice::Square && camera.zoom() >= ICON_ZOOM);
+        if lattice != Lattice::Square && camera.zoom() >= SHAPE_ZOOM {
+            // Shapes reach up to a cell beyond their square
+            let visible = clip((left - 1, top - 1, right + 1, bottom + 1), bounds);
+            draw_shapes(cr, cells, &rule, camera, visible, lattice);
+        } else if let Some(table) = icons {
+            draw_icons(cr, cells, camera, visible, table);
+            if camera.zoom() >= GRID_LINE_ZOOM {
+                draw_grid(cr, camera, visible);
+            }
+        } else if camera.zoom() >= 1.0 {
+            self.draw_cells(cr, cells, &rule, camera, visible, bounds);
+            if camera.zoom() >= GRID_LINE_ZOOM {
+                draw_grid(cr, camera, visible);
+            }
+        } else {
+            self.draw_density(cr, cells, &rule, camera, visible, (width, height));
+        }
+    }
+
+    // Repaint the edited cells in the cached bitmap, or drop it if that is not possible
+    fn apply_dirty(&mut self, cells: Cells, rule: &Rule, bounds: Option<(i64, i64)>) {
+        let Some(dirty) = self.dirty.take() else {
+            return;
+        };
+        let Some(Cache::Cells { window, surface }) = &mut self.cache else {
+            self.cache = None;
+            return;
+        };
+
+        // Edits that wrap around the edges of a bounded universe touch cells
+        // outside the marked rectangle
+        let wraps = bounds.is_some_and(|(width, height)| dirty.0 < 0 || dirty.1 < 0 || dirty.2 > width || dirty.3 > height);
+        if wraps {
+            self.cache = None;
+            return;
+        }
+
+        let (left, top) = (dirty.0.max(window.0), dirty.1.max(window.1));
+        let (right, bottom) = (dirty.2.min(window.2), dirty.3.min(window.3));
+        if left >= right || top >= bottom {
+            return;
+        }
+
+        let origin = (window.0, window.1);
+        let stride = surface.stride() as usize;
+        let Ok(mut data) = surface.data() else {
+            self.cache = None;
+            return;
+        };
+        for y in top..bottom {
+            let row = (y - origin.1) as usize * stride;
+            data[row + (left - origin.0) as usize * 4..row + (right - origin.0) as usize * 4].fill(0);
+        }
+        cells.for_each_cell_in((left, top, right, bottom), &mut |x, y, state| {
+            let offset = (y - origin.1) as usize * stride + (x - origin.0) as usize * 4;
+            data[offset..offset + 4].copy_from_slice(&state_pixel(state, rule));
+        });
+    }
+
+    fn draw_cells(
+        &mut self,
+        cr: &cairo::Context,
+        cells: Cells,
+        rule: &Rule,
+        camera: &Camera,
+        visible: Rect,
+        bounds: Option<(i64, i64)>,
+    ) {
+        let (left, top, right, bottom) = visible;
+        let covered = matches!(
+            &self.cache,
+            Some(Cache::Cells { window, .. }) if window.0 <= left && window.1 <= top && window.2 >= right && window.3 >= bottom
+        );
+
+        if !covered {
+            // Rasterize half a view of margin on each side so that small pans reuse the bitmap
+            let (margin_x, margin_y) = ((right - left) / 2, (bottom - top) / 2);
+            let window = clip((left - margin_x, top - margin_y, right + margin_x, bottom + margin_y), bounds);
+            let Some(surface) = rasterize(cells, rule, window) else {
+                return;
+            };
+            self.cache = Some(Cache::Cells { window, surface });
+        }
+
+        let Some(Cache::Cells { window, surface }) = &self.cache else {
+            return;
+        };
+        let lattice = rule.lattice();
+        let (left, top) = (window.0 as f64, window.1 as f64);
+        let (x, y) = camera.cell_to_screen(lattice.shear(left, top), top);
+        cr.save().unwrap();
+        cr.translate(x, y);
+        cr.scale(camera.zoom(), camera.zoom());
+        // Each row of pixels moves along with its row of cells
+        cr.transform(cairo::Matrix::new(1.0, 0.0, lattice.shear(0.0, 1.0), 1.0, 0.0, 0.0));
+        cr.set_source_surface(surface, 0.0, 0.0).unwrap();
+        cr.source().set_filter(cairo::Filter::Nearest);
+        cr.paint().unwrap();
+        cr.restore().unwrap();
+    }
+
+    fn draw_density(
+        &mut self,
+        cr: &cairo::Context,
+        cells: Cells,
+        rule: &Rule,
+        camera: &Camera,
+        visible: Rect,
+        size: (i32, i32),
+    ) {
+        let cached = matches!(&self.cache, Some(Cache::Density { camera: cached, size: cached_size, .. }) if cached == camera && *cached_size == size);
+
+        if !cached {
+            let Some(surface) = rasterize_density(cells, rule, camera, visible, size) else {
+                return;
+            };
+            self.cache = Some(Cache::Density {
+                camera: *camera,
+                size,
+                surface,
+            });
+        }
+
+        if let Some(Cache::Density { surface, .. }) = &self.cache {
+            cr.set_source_surface(surface, 0.0, 0.0).unwrap();
+            cr.paint().unwrap();
+        }
+    }
+}
+
+/// The cells drawn at least partly inside a view of `width` x `height`
+/// pixels, as `(left, top, right, bottom)` with exclusive ends.
+pub fn visible_cells(camera: &Camera, lattice: Lattice, width: f64, height: f64) -> Rect {
+    let (left, top, right, bottom) = camera.visible_cells(width, height);
+    match lattice {
+        // Row y is drawn y / 2 cells left, so further rows show further columns
+        Lattice::Hexagonal => (left + top.div_euclid(2) - 1, top, right + bottom.div_euclid(2) + 2, bottom),
+        _ => (left, top, right, bottom),
+    }
+}
+
+/// Adds the closed outline through `points`, in cell coordinates, to the path.
+pub fn trace(cr: &cairo::Context, camera: &Camera, points: &[(f64, f64)]) {
+    for (index, &(x, y)) in points.iter().enumerate() {
+        let (sx, sy) = camera.cell_to_screen(x, y);
+        if index == 0 {
+            cr.move_to(sx, sy);
+        } else {
+            cr.line_to(sx, sy);
+        }
+    }
+    cr.close_path();
+}
+
+/// Adds the outline of the cells in `area` to the path: a rectangle, or a
+/// parallelogram on the sheared hexagonal lattice.
+pub fn trace_area(cr: &cairo::Context, camera: &Camera, lattice: Lattice, area: Rect) {
+    let (left, top, right, bottom) = (area.0 as f64, area.1 as f64, area.2 as f64, area.3 as f64);
+    let corners = [(left, top), (right, top), (right, bottom), (left, bottom)];
+    trace(cr, camera, &corners.map(|(x, y)| (lattice.shear(x, y), y)));
+}
+
+// Intersect a cell rectangle with a bounded universe of the given size
+fn clip(rect: Rect, bounds: Option<(i64, i64)>) -> Rect {
+    let (left, top, right, bottom) = rect;
+    match bounds {
+        Some((width, height)) => (left.max(0), top.max(0), right.min(width), bottom.min(height)),
+        None => rect,
+    }
+}
+
+// Draw the lines between the visible cells as one path
+fn draw_grid(cr: &cairo::Context, camera: &Camera, visible: Rect) {
+    let (left, top, right, bottom) = visible;
+    let (x0, y0) = camera.cell_to_screen(left as f64, top as f64);
+    let (x1, y1) = camera.cell_to_screen(right as f64, bottom as f64);
+
+    set_colour(cr, GRID);
+    cr.set_line_width(0.5);
+    for x in left..=right {
+        let (sx, _) = camera.cell_to_screen(x as f64, 0.0);
+        cr.move_to(sx, y0);
+        cr.line_to(sx, y1);
+    }
+    for y in top..=bottom {
+        let (_, sy) = camera.cell_to_screen(0.0, y as f64);
+        cr.move_to(x0, sy);
+        cr.line_to(x1, sy);
+    }
+    cr.stroke().unwrap();
+}
+
+// Draw the cells of `visible` as hexagons or triangles, filling each state's
+// cells as one path and outlining all of them when zoomed in far enough
+fn draw_shapes(cr: &cairo::Context, cells: Cells, rule: &Rule, camera: &Camera, visible: Rect, lattice: Lattice) {
+    let (left, top, right, bottom) = visible;
+    let shape = |x: i64, y: i64| trace(cr, camera, &lattice.outline(x, y));
+
+    let mut cells_by_state = vec![Vec::new(); usize::from(rule.states())];
+    cells.for_each_cell_in(visible, &mut |x, y, state| {
+        cells_by_state[usize::from(state)].push((x, y));
+    });
+    for (state, cells) in cells_by_state.iter().enumerate().skip(1) {
+        if cells.is_empty() {
+            continue;
+        }
+        let [blue, green, red, _] = state_pixel(state as u8, rule);
+        cr.set_source_rgb(f64::from(red) / 255.0, f64::from(green) / 255.0, f64::from(blue) / 255.0);
+        for &(x, y) in cells {
+            shape(x, y);
+        }
+        cr.fill().unwrap();
+    }
+
+    if camera.zoom() >= GRID_LINE_ZOOM {
+        set_colour(cr, GRID);
+        cr.set_line_width(0.5);
+        for y in top..bottom {
+            for x in left..right {
+                shape(x, y);
+            }
+        }
+        cr.stroke().unwrap();
+    }
+}
+
+// Draw each cell of `visible` as its state's icon, scaled to the cell
+fn draw_icons(cr: &cairo::Context, cells: Cells, camera: &Camera, visible: Rect, table: &RuleTable) {
+    let size = camera.zoom().round() as u32;
+    let surfaces: Vec<Option<(cairo::ImageSurface, u32)>> = (0..table.states())
+        .map(|state| table.icon(state, size).and_then(|icon| Some((icon_surface(icon)?, icon.size))))
+        .collect();
+
+    cells.for_each_cell_in(visible, &mut |x, y, state| {
+        let Some((surface, icon_size)) = &surfaces[usize::from(state)] else {
+            return;
+        };
+        let (sx, sy) = camera.cell_to_screen(x as f64, y as f64);
+        let scale = camera.zoom() / f64::from(*icon_size);
+        cr.save().unwrap();
+        cr.translate(sx, sy);
+        cr.scale(scale, scale);
+        cr.set_source_surface(surface, 0.0, 0.0).unwrap();
+        cr.source().set_filter(cairo::Filter::Nearest);
+        cr.rectangle(0.0, 0.0, f64::from(*icon_size), f64::from(*icon_size));
+        cr.fill().unwrap();
+        cr.restore().unwrap();
+    });
+}
+
+// An icon as a bitmap, in Cairo's BGRA byte order
+fn icon_surface(icon: &Icon) -> Option<cairo::ImageSurface> {
+    let size = icon.size as i32;
+    let mut surface = cairo::ImageSurface::create(cairo::Format::ARgb32, size, size).ok()?;
+    let stride = surface.stride() as usize;
+    {
+        let mut data = surface.data().ok()?;
+        for (index, &[red, green, blue, alpha]) in icon.pixels.iter().enumerate() {
+            // Icon pixels are either opaque or transparent, so premultiplying
+            // only has to clear the transparent ones
+            let offset = index / icon.size as usize * stride + index % icon.size as usize * 4;
+            let pixel = if alpha == 0 { [0; 4] } else { [blue, green, red, 255] };
+            data[offset..offset + 4].copy_from_slice(&pixel);
+        }
+    }
+    Some(surface)
+}
+
+// A transparent bitmap with one pixel per cell of `window`, opaque where the
+// cell is alive or dying
+fn rasterize(cells: Cells, rule: &Rule, window: Rect) -> Option<cairo::ImageSurface> {
+    let (left, top, right, bottom) = window;
+    let mut surface = cairo::ImageSurface::create(cairo::Format::ARgb32, (right - left) as i32, (bottom - top) as i32).ok()?;
+    let stride = surface.stride() as usize;
+    {
+        let mut data = surface.data().ok()?;
+        cells.for_each_cell_in(window, &mut |x, y, state| {
+            let offset = (y - top) as usize * stride + (x - left) as usize * 4;
+            data[offset..offset + 4].copy_from_slice(&state_pixel(state, rule));
+        });
+    }
+    Some(surface)
+}
+
+// The pixel for a cell state under `rule`: the colour a rule table gives
+// it, or else for dying states a shade fading from `DYING_FIRST` to
+// `DYING_LAST`, so each one has its own colour
+fn state_pixel(state: u8, rule: &Rule) -> [u8; 4] {
+    if let Some((red, green, blue)) = rule.table().and_then(|table| table.colour(state)) {
+        return [blue, green, red, 255];
+    }
+    if state <= 1 {
+        return LIVE;
+    }
+    let states = rule.states();
+    let t = if states > 3 { f64::from(state - 2) / f64::from(states - 3) } else { 0.0 };
+    let mix = |first: f64, last: f64| (255.0 * (first + (last - first) * t)) as u8;
+    [
+        mix(DYING_FIRST.2, DYING_LAST.2),
+        mix(DYING_FIRST.1, DYING_LAST.1),
+        mix(DYING_FIRST.0, DYING_LAST.0),
+        255,
+    ]
+}
+
+// A screen-sized bitmap where several cells share each pixel: count the live
+// ones and shade every pixel with any life by its density
+fn rasterize_density(
+    cells: Cells,
+    rule: &Rule,
+    camera: &Camera,
+    visible: Rect,
+    (width, height): (i32, i32),
+) -> Option<cairo::ImageSurface> {
+    let lattice = rule.lattice();
+    let (pixels_x, pixels_y) = (width.max(0) as usize, height.max(0) as usize);
+    let mut counts = vec![0u64; pixels_x * pixels_y];
+    // Blocks no wider than a pixel, counted where their top-left cell falls
+    let block = (1.0 / camera.zoom()) as i64;
+    // Every state of a rule table is a cell worth showing
+    cells.for_each_block_in(visible, block, rule.table().is_some(), &mut |x, y, cells| {
+        let (sx, sy) = camera.cell_to_screen(lattice.shear(x as f64, y as f64), y as f64);
+        let (px, py) = (sx as usize, sy as usize);
+        if sx >= 0.0 && sy >= 0.0 && px < pixels_x && py < pixels_y {
+            counts[py * pixels_x + px] += cells;
+        }
+    });
+
+    let cells_per_pixel = 1.0 / (camera.zoom() * camera.zoom());
+    let mut surface = cairo::ImageSurface::create(cairo::Format::ARgb32, width, height).ok()?;
+    let stride = surface.stride() as usize;
+    {
+        let mut data = surface.data().ok()?;
+        for (index, &count) in counts.iter().enumerate() {
+            if count > 0 {
+                let density = (count as f64 / cells_per_pixel).min(1.0);
+                let level = (255.0 * (0.35 + 0.45 * density)) as u8;
+                let offset = (index / pixels_x) * stride + (index % pixels_x) * 4;
+                data[offset..offset + 4].copy_from_slice(&[level, level, level, 255]);
+            }
+        }
+    }
+    Some(surface)
+}
+
+fn rgb((red, green, blue): (u8, u8, u8)) -> (f64, f64, f64) {
+    (f64::from(red) / 255.0, f64::from(green) / 255.0, f64::from(blue) / 255.0)
+}
+
+fn set_colour(cr: &cairo::Context, (red, green, blue): (f64, f64, f64)) {
+    cr.set_source_rgb(red, green, blue);
+}
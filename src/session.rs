@@ -0,0 +1,214 @@
+// Saving and restoring a whole session: the universe with its counters and
+// settings, plus the speed, viewport and selection of the window.
+//
+// A session file is a gzip-compressed text file. It starts with a version
+// line, followed by one `key value` line per setting and finally the cells
+// in every state as RLE, without a rule, after a `cells` line. A rule loaded
+// from a `.rule` file is kept whole, as a `rule_table` line with its line
+// count followed by the file's lines. Uncompressed files are read as well,
+// which helps when editing one by hand.
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use gtk4 as gtk;
+use gtk::{gio, glib};
+use gtk::prelude::*;
+
+use cellverse_core::formats::rle;
+use cellverse_core::{Pattern, Rule, RuleTable, Selection, Stats, Topology, Universe};
+
+use crate::camera::Camera;
+
+const MAGIC: &str = "cellverse-session";
+const VERSION: u32 = 1;
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Everything needed to pick up where a session left off.
+pub struct Session {
+    pub universe: Universe,
+    pub speed: u64, // in milliseconds
+    pub camera: Camera,
+    pub selection: Option<Selection>,
+}
+
+impl Session {
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let data = compress(&self.to_text()).map_err(|err| err.to_string())?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+        }
+        std::fs::write(path, data).map_err(|err| err.to_string())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let data = std::fs::read(path).map_err(|err| err.to_string())?;
+        let text = if data.starts_with(&GZIP_MAGIC) {
+            decompress(data).map_err(|err| err.to_string())?
+        } else {
+            String::from_utf8(data).map_err(|err| err.to_string())?
+        };
+        Self::from_text(&text)
+    }
+
+    fn to_text(&self) -> String {
+        let universe = &self.universe;
+        let stats = universe.stats();
+        let mut text = format!("{} {}\n", MAGIC, VERSION);
+        text += &format!("width {}\n", universe.width());
+        text += &format!("height {}\n", universe.height());
+        text += &format!("topology {}\n", universe.topology());
+        match universe.rule().table() {
+            Some(table) => {
+                text += &format!("rule_table {}\n", table.source().lines().count());
+                for line in table.source().lines() {
+                    text += line;
+                    text += "\n";
+                }
+            }
+            None => text += &format!("rule {}\n", universe.rule()),
+        }
+        text += &format!("auto_grow {}\n", universe.auto_grow());
+        text += &format!("generation {}\n", stats.generation);
+        text += &format!("births {}\n", stats.births);
+        text += &format!("deaths {}\n", stats.deaths);
+        text += &format!("speed {}\n", self.speed);
+        text += &format!("camera {} {} {}\n", self.camera.left(), self.camera.top(), self.camera.zoom());
+        if let Some(selection) = self.selection {
+            text += &format!(
+                "selection {} {} {} {}\n",
+                selection.left, selection.top, selection.right, selection.bottom
+            );
+        }
+
+        // RLE places the pattern at the origin, so note where it really starts
+        let mut origin: Option<(i64, i64)> = None;
+        universe.for_each_cell_in(i64::MIN, i64::MIN, i64::MAX, i64::MAX, &mut |x, y, _| {
+            origin = Some(origin.map_or((x, y), |(left, top)| (left.min(x), top.min(y))));
+        });
+        let (left, top) = origin.unwrap_or((0, 0));
+        text += &format!("origin {} {}\n", left, top);
+        // The rule is saved above; a rule table's name alone could not be read back
+        let mut cells = Pattern::from_universe(universe);
+        cells.rule = None;
+        text += "cells\n";
+        text += &rle::write(&cells);
+        text
+    }
+
+    fn from_text(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines();
+        let version = lines
+            .next()
+            .and_then(|line| line.strip_prefix(MAGIC))
+            .and_then(|version| version.trim().parse::<u32>().ok())
+            .ok_or("This is not a Cellverse session file.")?;
+        if version != VERSION {
+            return Err(format!(
+                "The session was saved in version {} of the format; only version {} can be read.",
+                version, VERSION
+            ));
+        }
+
+        let mut size = (0, 0);
+        let mut topology = Topology::default();
+        let mut rule = Rule::default();
+        let mut auto_grow = true;
+        let mut stats = Stats::default();
+        let mut speed = 100;
+        let mut camera = None;
+        let mut selection = None;
+        let mut origin = (0, 0);
+        let mut cells = None;
+        while let Some(line) = lines.next() {
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "width" => size.0 = parse(key, value)?,
+                "height" => size.1 = parse(key, value)?,
+                "topology" => {
+                    topology = Topology::ALL
+                        .into_iter()
+                        .find(|topology| topology.to_string() == value)
+                        .ok_or_else(|| format!("Unknown topology \"{}\".", value))?;
+                }
+                "rule" => rule = value.parse::<Rule>().map_err(|err| err.to_string())?,
+                "rule_table" => {
+                    let count: usize = parse(key, value)?;
+                    let source = lines.by_ref().take(count).collect::<Vec<_>>().join("\n");
+                    rule = Rule::from_table(source.parse::<RuleTable>().map_err(|err| err.to_string())?);
+                }
+                "auto_grow" => auto_grow = parse(key, value)?,
+                "generation" => stats.generation = parse(key, value)?,
+                "births" => stats.births = parse(key, value)?,
+                "deaths" => stats.deaths = parse(key, value)?,
+                "speed" => speed = parse(key, value)?,
+                "camera" => {
+                    let [left, top, zoom] = parse_numbers(key, value)?;
+                    camera = Some(Camera::at(left, top, zoom));
+                }
+                "selection" => {
+                    let [left, top, right, bottom] = parse_numbers(key, value)?;
+                    selection = Some(Selection { left, top, right, bottom });
+                }
+                "origin" => origin = parse_numbers(key, value).map(|[x, y]| (x, y))?,
+                "cells" => {
+                    cells = Some(rle::read(&lines.by_ref().collect::<Vec<_>>().join("\n")).map_err(|err| err.to_string())?);
+                    break;
+                }
+                // Settings from later versions of the same format
+                _ => {}
+            }
+        }
+
+        let mut universe = Universe::new(size.0, size.1);
+        universe.set_topology(topology);
+        universe.set_rule(rule);
+        universe.set_auto_grow(auto_grow);
+        if let Some(cells) = cells {
+            cells.stamp(&mut universe, origin.0, origin.1);
+        }
+        universe.set_stats(stats);
+        Ok(Self {
+            universe,
+            speed,
+            camera: camera.unwrap_or(Camera::new(crate::INITIAL_ZOOM)),
+            selection,
+        })
+    }
+}
+
+/// The session saved on exit and restored on start.
+pub fn autosave_path() -> PathBuf {
+    glib::user_config_dir().join("cellverse").join("session.cellverse")
+}
+
+fn parse<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value.trim().parse().map_err(|_| format!("Invalid {} \"{}\".", key, value))
+}
+
+fn parse_numbers<T: std::str::FromStr, const N: usize>(key: &str, value: &str) -> Result<[T; N], String> {
+    let numbers = value
+        .split_whitespace()
+        .map(|number| parse(key, number))
+        .collect::<Result<Vec<T>, String>>()?;
+    numbers.try_into().map_err(|_| format!("Expected {} numbers for {}.", N, key))
+}
+
+fn compress(text: &str) -> Result<Vec<u8>, glib::Error> {
+    let memory = gio::MemoryOutputStream::new_resizable();
+    let compressor = gio::ZlibCompressor::new(gio::ZlibCompressorFormat::Gzip, -1);
+    let stream = gio::ConverterOutputStream::new(&memory, &compressor);
+    stream.write_all(text.as_bytes(), None::<&gio::Cancellable>)?;
+    stream.close(None::<&gio::Cancellable>)?;
+    Ok(memory.steal_as_bytes().to_vec())
+}
+
+fn decompress(data: Vec<u8>) -> std::io::Result<String> {
+    let memory = gio::MemoryInputStream::from_bytes(&glib::Bytes::from_owned(data));
+    let decompressor = gio::ZlibDecompressor::new(gio::ZlibCompressorFormat::Gzip);
+    let mut text = String::new();
+    gio::ConverterInputStream::new(&memory, &decompressor)
+        .into_read()
+        .read_to_string(&mut text)?;
+    Ok(text)
+}
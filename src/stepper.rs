@@ -0,0 +1,131 @@
+// Advances a universe on the engine the user picked. The window's game loop
+// and the headless `run` command both step through here, so a run gives the
+// same result with or without a display.
+//
+// HashLife's tree is not copied back into the universe after every jump, as
+// that would cost as much as the pattern is large. The tree runs ahead
+// instead, and is drawn, counted and saved from directly; the universe only
+// catches up with `sync` when its cells are needed, e.g. to edit them.
+
+use cellverse_core::{BitGrid, Engine, HashLife, Stats, Topology, Universe};
+
+// Engine used to advance the universe
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub enum Backend {
+    #[default]
+    Grid,
+    BitPacked,
+    HashLife,
+}
+
+impl Backend {
+    pub const ALL: [Backend; 3] = [Backend::Grid, Backend::BitPacked, Backend::HashLife];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Backend::Grid => "Grid",
+            Backend::BitPacked => "Bit-packed",
+            Backend::HashLife => "HashLife",
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Stepper {
+    pub backend: Backend,
+    packed: Option<BitGrid>, // Bit-packed copy of the universe while it runs
+    hashlife: Option<HashLife>, // HashLife copy of the universe while fast-forwarding
+    ahead: bool,                // Whether the HashLife copy has run past the universe
+}
+
+impl Stepper {
+    /// Drops the engine copies so that they are rebuilt from the universe on
+    /// the next step. Needed whenever the universe changes outside the stepper;
+    /// a tree that ran ahead is lost unless the universe was synced first.
+    pub fn invalidate(&mut self) {
+        self.packed = None;
+        self.hashlife = None;
+        self.ahead = false;
+    }
+
+    /// Switches to HashLife with `hashlife` as its copy of the universe, e.g.
+    /// a Macrocell file read as a quadtree. The tree is ahead of the universe
+    /// from the start, so the universe's cells stay as they are until `sync`.
+    pub fn load_tree(&mut self, hashlife: HashLife) {
+        self.backend = Backend::HashLife;
+        self.packed = None;
+        self.hashlife = Some(hashlife);
+        self.ahead = true;
+    }
+
+    /// The HashLife tree while it is ahead of the universe, to be shown in
+    /// its place.
+    pub fn tree(&self) -> Option<&HashLife> {
+        self.hashlife.as_ref().filter(|_| self.ahead)
+    }
+
+    /// Copies the tree into `universe` if it ran ahead, for when the cells
+    /// themselves are needed.
+    pub fn sync(&mut self, universe: &mut Universe) {
+        if let Some(hashlife) = self.tree() {
+            hashlife.copy_to(universe);
+            self.ahead = false;
+        }
+    }
+
+    /// The counters of the run. While the tree is ahead the generation and
+    /// population are its own; it does not count births and deaths, so those
+    /// hold still until the next `sync`.
+    pub fn stats(&self, universe: &Universe) -> Stats {
+        match self.tree() {
+            Some(hashlife) => Stats {
+                generation: hashlife.stats().generation,
+                population: hashlife.stats().population,
+                ..universe.stats()
+            },
+            None => universe.stats(),
+        }
+    }
+
+    /// Whether the selected engine can run `universe`; otherwise it steps on
+    /// the grid engine.
+    pub fn runs(&self, universe: &Universe) -> bool {
+        let rule = universe.rule();
+        match self.backend {
+            Backend::Grid => true,
+            // The bit-packed kernel only knows two-state rules on the torus
+            Backend::BitPacked => rule.is_life_like() && universe.topology() == Topology::Torus,
+            // HashLife needs every cell to look at its nearest neighbours only,
+            // on a plane with no edges to wrap or clip at
+            Backend::HashLife => HashLife::supports(&rule) && universe.topology() == Topology::Unbounded,
+        }
+    }
+
+    /// Advances `universe` by `generations` on the selected engine.
+    pub fn step(&mut self, universe: &mut Universe, generations: u64) {
+        // Other engines carry on from where the tree got to
+        if self.backend != Backend::HashLife || !self.runs(universe) {
+            self.sync(universe);
+        }
+        match self.backend {
+            Backend::Grid => {}
+            _ if !self.runs(universe) => {}
+            Backend::BitPacked => {
+                let packed = self.packed.get_or_insert_with(|| BitGrid::from(&*universe));
+                packed.step_by(generations);
+                packed.copy_to(universe);
+                return;
+            }
+            Backend::HashLife => {
+                let hashlife = self.hashlife.get_or_insert_with(|| HashLife::from(&*universe));
+                hashlife.step_by(generations);
+                self.ahead = true;
+                return;
+            }
+        }
+
+        for _ in 0..generations {
+            universe.step();
+        }
+    }
+}